@@ -0,0 +1,96 @@
+use opengl_window::*;
+
+// Some Application settings
+const WIDTH: u32 = 900;
+const HEIGHT: u32 = 700;
+const TIME_PER_FRAME: u64 = 16_666_667;
+const DEPTH_BUFFER: u8 = 24;
+const FOV: u32 = 60;
+
+fn main() -> Result<(), Error> {
+    // Path of the .gltf or .glb file, relative to the assets folder. Defaults
+    // to the triangle next to this example:
+    let file = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| concat!(env!("CARGO_MANIFEST_DIR"), "/examples/triangle.gltf").into());
+    let assets = find_folder::Search::ParentsThenKids(3, 3)
+        .for_folder("assets")
+        .expect("Couldn't find the assets folder.");
+
+    // Display setup:
+    let (display, event_loop, shaders, draw_params) =
+        setup(WIDTH, HEIGHT, "glTF viewer", DEPTH_BUFFER)?;
+
+    let scene = import_gltf(&display, assets.join(file))?;
+
+    // Use the cameras and lights of the file when it has any:
+    let mut camera = match scene.cameras.first() {
+        Some(camera) => *camera,
+        None => Camera::new(
+            Vector3::new(0.0, 1.0, 3.0),
+            Vector3::new(0.0, -0.3, -1.0),
+            (WIDTH, HEIGHT),
+            FOV,
+        )?,
+    };
+    let light = match scene.lights.first() {
+        Some(light) => *light,
+        None => Light::directional(Vector3::new(1.0, -0.4, -0.9))?,
+    };
+    let mut shadow_map = ShadowMap::new(&display, ShadowSettings::default())?;
+
+    // Main loop:
+    event_loop.run(move |event, _, control_flow| {
+        // Limit frame rate
+        let next_frame_time = Instant::now() + Duration::from_nanos(TIME_PER_FRAME);
+        *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
+
+        // Handle events
+        match event {
+            Event::LoopDestroyed => return,
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                _ => (),
+            },
+            Event::RedrawRequested(_) => (),
+            _ => return,
+        }
+
+        /* Rendering process */
+
+        let mut frame = init_frame(&display, SKYBLUE);
+
+        // If the frame dimensions changed -> update the cameras dimensions.
+        camera.update_resolution(frame.get_dimensions());
+
+        // Do the rendering
+        let result = shadow_map
+            .update(&display, &scene.objects, &camera, &light)
+            .and_then(|()| {
+                render(
+                    &mut frame,
+                    &scene.objects,
+                    &camera,
+                    &light,
+                    RenderOptions::new().with_shadows(&shadow_map),
+                    &shaders,
+                    &draw_params,
+                )
+            });
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            *control_flow = ControlFlow::Exit;
+        }
+
+        // Do the swapchain
+        frame.finish().expect("Couldn't swap buffers.");
+    });
+}
@@ -1,14 +1,14 @@
 use opengl_window::*;
 
-fn main() {
+fn main() -> Result<(), Error> {
     // Display setup:
-    let (display, event_loop, program, draw_params) =
-        setup(800, 600, "egui hello world.", 16);
+    let (_display, _event_loop, _shaders, _draw_params) =
+        setup(800, 600, "egui hello world.", 16)?;
 
 
     // Add gui:
 
 
     println!("Closing app");
-
+    Ok(())
 }
\ No newline at end of file
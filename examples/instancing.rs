@@ -0,0 +1,128 @@
+use opengl_window::*;
+
+// Some Application settings
+const WIDTH: u32 = 900;
+const HEIGHT: u32 = 700;
+const TIME_PER_FRAME: u64 = 16_666_667;
+const DEPTH_BUFFER: u8 = 24;
+const FOV: u32 = 60;
+
+// Teapots per side of the grid
+const GRID: usize = 60;
+const SPACING: Scalar = 0.6;
+
+fn main() -> Result<(), Error> {
+    // Display setup:
+    let (display, event_loop, shaders, draw_params) =
+        setup(WIDTH, HEIGHT, "Instanced teapots", DEPTH_BUFFER)?;
+
+    // The teapot in a few levels of detail, each level drawn in a single call.
+    // Teapots far from the camera use the simplified meshes:
+    let lod = Lod::new(
+        &display,
+        &teapot::teapot(teapot::DEFAULT_LEVEL),
+        LodSettings::default(),
+    )?;
+    let mut teapot = SceneObject::with_lod(std::rc::Rc::new(lod));
+    teapot.set_scale(0.2);
+
+    let half = (GRID - 1) as Scalar * SPACING / 2.0;
+    let mut object_list = Vec::with_capacity(GRID * GRID);
+    for i in 0..GRID {
+        for j in 0..GRID {
+            let mut object = teapot.clone();
+            object.set_position(Vector3::new(
+                i as Scalar * SPACING - half,
+                0.0,
+                j as Scalar * SPACING - half,
+            ));
+            object.set_color(Color::new(
+                i as Scalar / GRID as Scalar,
+                0.5,
+                j as Scalar / GRID as Scalar,
+            ));
+            object_list.push(Box::new(object));
+        }
+    }
+
+    // Make camera:
+    let mut camera = Camera::new(
+        Vector3::new(0.0, 8.0, 20.0),
+        Vector3::new(0.0, -0.5, -1.0),
+        (WIDTH, HEIGHT),
+        FOV,
+    )?;
+
+    // Make light and its shadow maps:
+    let light = Light::directional(Vector3::new(1.0, -0.8, -0.6))?;
+    let mut shadow_map = ShadowMap::new(&display, ShadowSettings::default())?;
+
+    let start = Instant::now();
+
+    // Main loop:
+    event_loop.run(move |event, _, control_flow| {
+        // Limit frame rate
+        let next_frame_time = Instant::now() + Duration::from_nanos(TIME_PER_FRAME);
+        *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
+
+        // Handle events
+        match event {
+            Event::LoopDestroyed => return,
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            ..
+                        },
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                _ => (),
+            },
+            Event::RedrawRequested(_) => (),
+            // Keep animating when no window events arrive
+            Event::NewEvents(glutin::event::StartCause::ResumeTimeReached { .. }) => (),
+            _ => return,
+        }
+
+        // Spin every teapot around the vertical axis:
+        let angle = start.elapsed().as_secs_f32();
+        for (k, object) in object_list.iter_mut().enumerate() {
+            let offset = k as Scalar * 0.1;
+            object.set_rotation(UnitQuaternion::from_axis_angle(
+                &Vector3::y_axis(),
+                angle + offset,
+            ));
+        }
+
+        /* Rendering process */
+
+        let mut frame = init_frame(&display, SKYBLUE);
+
+        // If the frame dimensions changed -> update the cameras dimensions.
+        camera.update_resolution(frame.get_dimensions());
+
+        // Do the rendering
+        let result = shadow_map
+            .update(&display, &object_list, &camera, &light)
+            .and_then(|()| {
+                render(
+                    &mut frame,
+                    &object_list,
+                    &camera,
+                    &light,
+                    RenderOptions::new().with_shadows(&shadow_map),
+                    &shaders,
+                    &draw_params,
+                )
+            });
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            *control_flow = ControlFlow::Exit;
+        }
+
+        // Do the swapchain
+        frame.finish().expect("Couldn't swap buffers.");
+    });
+}
@@ -0,0 +1,225 @@
+use opengl_window::*;
+
+// Some Application settings
+const TIME_PER_FRAME: u64 = 16_666_667;
+
+fn main() -> Result<(), Error> {
+    // Scene file to show, defaults to the teapots next to this example:
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| concat!(env!("CARGO_MANIFEST_DIR"), "/examples/teapots.ron").into());
+
+    // Display setup, window and context settings come from a config file:
+    let Renderer {
+        display,
+        event_loop,
+        mut shaders,
+        draw_params,
+    } = RendererConfig::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/examples/renderer.ron"
+    ))?
+    .build()?;
+
+    let mut resources = Resources::new(&display);
+    let mut scene = match load_scene(&mut resources, &path) {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    println!("{:?}", resources.stats());
+
+    // An equirectangular .hdr panorama as second argument lights the scene and
+    // replaces the gradient sky:
+    let environment = match std::env::args().nth(2) {
+        Some(panorama) => Some(Environment::load(&display, panorama)?),
+        None => None,
+    };
+    let sky = environment
+        .as_ref()
+        .map_or_else(Sky::default, Environment::sky);
+    let mut shadow_map = ShadowMap::new(&display, ShadowSettings::default())?;
+    let mut ssao = Ssao::new(&display, SsaoSettings::default())?;
+    let mut ssao_enabled = true;
+
+    // Forward shading uses the first light only, the deferred path all of them:
+    let mut deferred = DeferredRenderer::new(&display, &draw_params)?;
+    let mut render_path = RenderPath::Forward;
+
+    // The scene is rendered in HDR, then tonemapped and post-processed into the window:
+    let mut hdr = HdrTarget::new(&display, display.get_framebuffer_dimensions())?;
+    let mut post = PostProcessor::new(&display, PostSettings::default())?;
+    post.settings.bloom.enabled = true;
+    post.settings.fxaa = true;
+
+    // Gizmos for the world origin, the objects and the lights:
+    let mut debug = DebugDraw::new(&display)?;
+    let mut show_gizmos = false;
+    let grid = Grid::new(&display, GridSettings::default())?;
+    let mut show_grid = true;
+
+    // Main loop:
+    event_loop.run(move |event, _, control_flow| {
+        // Limit frame rate
+        let next_frame_time = Instant::now() + Duration::from_nanos(TIME_PER_FRAME);
+        *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
+
+        // Handle events
+        match event {
+            Event::LoopDestroyed => return,
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(virtual_code),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => match virtual_code {
+                    VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
+                    VirtualKeyCode::T => {
+                        let tone_mapping = &mut post.settings.tone_mapping;
+                        tone_mapping.operator = match tone_mapping.operator {
+                            Tonemapper::Clamp => Tonemapper::Reinhard,
+                            Tonemapper::Reinhard => Tonemapper::Aces,
+                            Tonemapper::Aces => Tonemapper::Clamp,
+                        };
+                        println!("{:?}", tone_mapping.operator);
+                    }
+                    VirtualKeyCode::Up => post.settings.tone_mapping.exposure *= 1.25,
+                    VirtualKeyCode::Down => post.settings.tone_mapping.exposure /= 1.25,
+                    // Toggle the post effects
+                    VirtualKeyCode::B => post.settings.bloom.enabled ^= true,
+                    VirtualKeyCode::C => post.settings.chromatic_aberration.enabled ^= true,
+                    VirtualKeyCode::H => post.settings.sharpen.enabled ^= true,
+                    VirtualKeyCode::V => post.settings.vignette.enabled ^= true,
+                    VirtualKeyCode::F => post.settings.fxaa ^= true,
+                    VirtualKeyCode::O => ssao_enabled ^= true,
+                    VirtualKeyCode::G => show_gizmos ^= true,
+                    VirtualKeyCode::X => show_grid ^= true,
+                    // Cycle through the wireframe and inspection modes
+                    VirtualKeyCode::M => {
+                        shaders.render_mode = shaders.render_mode.next();
+                        println!("{:?}", shaders.render_mode);
+                    }
+                    VirtualKeyCode::D => {
+                        render_path = match render_path {
+                            RenderPath::Forward => RenderPath::Deferred,
+                            RenderPath::Deferred => RenderPath::Forward,
+                        };
+                        println!("{:?}", render_path);
+                    }
+                    // Save a copy next to the loaded file
+                    VirtualKeyCode::S => {
+                        let copy = std::path::Path::new(&path).with_extension("saved.ron");
+                        match save_scene(&scene, &copy) {
+                            Ok(()) => println!("Saved {}", copy.display()),
+                            Err(err) => eprintln!("{}", err),
+                        }
+                    }
+                    // Export the objects as they are placed in the scene next to the loaded file
+                    VirtualKeyCode::E => {
+                        for (i, object) in scene.objects.iter().enumerate() {
+                            let file =
+                                std::path::Path::new(&path).with_extension(format!("{}.obj", i));
+                            match export_object(object, &file, true) {
+                                Ok(()) => println!("Exported {}", file.display()),
+                                Err(err) => eprintln!("{}", err),
+                            }
+                        }
+                    }
+                    _ => (),
+                },
+                _ => (),
+            },
+            Event::RedrawRequested(_) => (),
+            _ => return,
+        }
+
+        /* Rendering process */
+
+        let mut frame = init_frame(&display, BLACK);
+
+        // If the frame dimensions changed -> update the cameras dimensions.
+        let dimensions = frame.get_dimensions();
+        for camera in scene.cameras.iter_mut() {
+            camera.update_resolution(dimensions);
+        }
+
+        // Render the scene into the HDR target, then tonemap it into the frame
+        if let (Some(camera), Some(light)) = (scene.cameras.first(), scene.lights.first()) {
+            let result = hdr
+                .resize(&display, dimensions)
+                .and_then(|()| shadow_map.update(&display, &scene.objects, camera, light))
+                .and_then(|()| {
+                    if ssao_enabled {
+                        ssao.update(&display, &scene.objects, camera)
+                    } else {
+                        Ok(())
+                    }
+                })
+                .and_then(|()| hdr.framebuffer(&display, BLACK))
+                .and_then(|mut target| {
+                    let options = RenderOptions {
+                        shadow_map: Some(&shadow_map),
+                        environment: environment.as_ref(),
+                        ssao: Some(&ssao).filter(|_| ssao_enabled),
+                    };
+                    match render_path {
+                        RenderPath::Forward => render(
+                            &mut target,
+                            &scene.objects,
+                            camera,
+                            light,
+                            options,
+                            &shaders,
+                            &draw_params,
+                        )?,
+                        RenderPath::Deferred => deferred.render(
+                            &mut target,
+                            &scene.objects,
+                            camera,
+                            &scene.lights,
+                            options,
+                            &shaders,
+                        )?,
+                    }
+                    render_sky(&mut target, camera, &sky, &shaders)?;
+                    if show_grid {
+                        grid.render(&mut target, camera)?;
+                    }
+
+                    if show_gizmos {
+                        debug.axes(&Matrix4::identity(), 1.0);
+                        for object in scene.objects.iter() {
+                            let transform = object.transform();
+                            let unscaled = Transform::new(
+                                transform.position,
+                                transform.rotation,
+                                Vector3::repeat(1.0),
+                            );
+                            debug.axes(&unscaled.matrix(), 0.25);
+                        }
+                        for light in scene.lights.iter() {
+                            debug.light(light, light.color);
+                        }
+                    }
+                    debug.render(&mut target, camera)
+                })
+                .and_then(|()| post.apply(&display, &mut frame, &hdr))
+                // The orientation widget goes on top, after tonemapping
+                .and_then(|()| debug.orientation(&mut frame, camera, 96));
+            if let Err(err) = result {
+                eprintln!("{}", err);
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+
+        // Do the swapchain
+        frame.finish().expect("Couldn't swap buffers.");
+    });
+}
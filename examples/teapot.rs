@@ -7,15 +7,17 @@ const TIME_PER_FRAME: u64 = 16_666_667;
 const DEPTH_BUFFER: u8 = 24;
 const FOV: u32 = 60;
 
-fn main() {
+fn main() -> Result<(), Error> {
 
     // Display setup:
-    let (display, event_loop, program, draw_params) =
-        setup(WIDTH, HEIGHT, "OpenGl Hello World.", DEPTH_BUFFER);
+    let (display, event_loop, shaders, draw_params) =
+        setup(WIDTH, HEIGHT, "OpenGl Hello World.", DEPTH_BUFFER)?;
 
-    // Allocate teapots:
-    let teapot1 = SceneObject::teapot(&display);
-    let teapot2 = SceneObject::teapot(&display);
+    // Allocate teapots, both share the GPU mesh held by the resource manager:
+    let mut resources = Resources::new(&display);
+    let teapot1 = SceneObject::from_mesh(resources.teapot()?);
+    let teapot2 = SceneObject::from_mesh(resources.teapot()?);
+    println!("{:?}", resources.stats());
 
     let mut object_list = vec![Box::new(teapot1), Box::new(teapot2)];
 
@@ -25,7 +27,11 @@ fn main() {
         Vector3::new(0.0, 1.0, -1.0),
         (WIDTH, HEIGHT),
         FOV,
-    );
+    )?;
+
+    // Make light and its shadow maps:
+    let light = Light::directional(Vector3::new(1.0, -0.4, -0.9))?;
+    let mut shadow_map = ShadowMap::new(&display, ShadowSettings::default())?;
 
     // Main loop:
     event_loop.run(move |event, _, control_flow| {
@@ -57,6 +63,23 @@ fn main() {
                         println!("Pressed S");
                         object_list[0].scale_down();
                     }
+                    // Swap the second teapot for a subdivided one, or back
+                    (VirtualKeyCode::C, ElementState::Pressed)
+                    | (VirtualKeyCode::L, ElementState::Pressed)
+                    | (VirtualKeyCode::R, ElementState::Pressed) => {
+                        let mut data = teapot::teapot(teapot::DEFAULT_LEVEL);
+                        data.weld(1e-5);
+                        let data = match virtual_code {
+                            VirtualKeyCode::C => catmull_clark(&data, 1),
+                            VirtualKeyCode::L => loop_subdivide(&data, 1),
+                            _ => data,
+                        };
+                        println!("{} triangles", data.triangle_count());
+                        match Mesh::from_data(&display, &data) {
+                            Ok(mesh) => object_list[1].mesh = std::rc::Rc::new(mesh),
+                            Err(err) => eprintln!("{}", err),
+                        }
+                    }
                     _ => (),
                 },
                 _ => (),
@@ -73,7 +96,23 @@ fn main() {
         camera.update_resolution(frame.get_dimensions());
 
         // Do the rendering
-        render(&mut frame, &object_list, &camera, &program, &draw_params);
+        let result = shadow_map
+            .update(&display, &object_list, &camera, &light)
+            .and_then(|()| {
+                render(
+                    &mut frame,
+                    &object_list,
+                    &camera,
+                    &light,
+                    RenderOptions::new().with_shadows(&shadow_map),
+                    &shaders,
+                    &draw_params,
+                )
+            });
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            *control_flow = ControlFlow::Exit;
+        }
 
         // Do the swapchain
         frame.finish().expect("Couldn't swap buffers.");
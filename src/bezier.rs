@@ -0,0 +1,214 @@
+use crate::{MeshData, Normal, Scalar, Tangent, TexCoord, Vector3, Vertex, NORM_EPS};
+
+/// Bicubic Bezier patch. `u` runs along the rows of `control_points`, `v`
+/// down the columns, and the surface faces the side of `du x dv`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BezierPatch {
+    pub control_points: [[Vector3; 4]; 4],
+}
+
+impl BezierPatch {
+    pub fn new(control_points: [[Vector3; 4]; 4]) -> Self {
+        Self { control_points }
+    }
+
+    pub fn position(&self, u: Scalar, v: Scalar) -> Vector3 {
+        self.evaluate(bernstein(u), bernstein(v))
+    }
+
+    /// Partial derivatives along `u` and `v`
+    pub fn derivatives(&self, u: Scalar, v: Scalar) -> (Vector3, Vector3) {
+        (
+            self.evaluate(bernstein_derivative(u), bernstein(v)),
+            self.evaluate(bernstein(u), bernstein_derivative(v)),
+        )
+    }
+
+    /// Unit normal. Where an edge collapses into a point, like at the tip of the
+    /// teapot's lid, a derivative vanishes and the normal is taken a little
+    /// further inside the patch.
+    pub fn normal(&self, u: Scalar, v: Scalar) -> Vector3 {
+        let inside = |t: Scalar| t + (0.5 - t) * 1e-3;
+        let (du, dv) = self.derivatives(u, v);
+        let (du, dv) = if is_degenerate(du, dv) {
+            self.derivatives(inside(u), inside(v))
+        } else {
+            (du, dv)
+        };
+        du.cross(&dv)
+            .try_normalize(NORM_EPS)
+            .unwrap_or_else(Vector3::zeros)
+    }
+
+    /// Whether all control points of an edge coincide, 0 to 3 for the edges
+    /// at v = 0, u = 1, v = 1 and u = 0
+    fn is_collapsed(&self, edge: usize) -> bool {
+        let points = &self.control_points;
+        let point = |i: usize| match edge {
+            0 => points[0][i],
+            1 => points[i][3],
+            2 => points[3][i],
+            _ => points[i][0],
+        };
+        (1..4).all(|i| point(i) == point(0))
+    }
+
+    fn evaluate(&self, weights_u: [Scalar; 4], weights_v: [Scalar; 4]) -> Vector3 {
+        let mut sum = Vector3::zeros();
+        for (row, &weight_v) in self.control_points.iter().zip(weights_v.iter()) {
+            for (point, &weight_u) in row.iter().zip(weights_u.iter()) {
+                sum += point * (weight_u * weight_v);
+            }
+        }
+        sum
+    }
+}
+
+/// Derivatives that are (nearly) parallel or one of them vanishes, measured
+/// against the longer one since rounding leaves a vanishing derivative
+/// slightly off zero
+fn is_degenerate(du: Vector3, dv: Vector3) -> bool {
+    let scale = du.norm().max(dv.norm());
+    du.cross(&dv).norm() <= 1e-4 * scale * scale + NORM_EPS
+}
+
+/// The cubic Bernstein polynomials at `t`
+fn bernstein(t: Scalar) -> [Scalar; 4] {
+    let s = 1.0 - t;
+    [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t]
+}
+
+fn bernstein_derivative(t: Scalar) -> [Scalar; 4] {
+    let s = 1.0 - t;
+    [
+        -3.0 * s * s,
+        3.0 * s * s - 6.0 * t * s,
+        6.0 * t * s - 3.0 * t * t,
+        3.0 * t * t,
+    ]
+}
+
+/// Triangulates every patch into a `level` by `level` grid of quads, with
+/// analytic normals and tangents, and the patch parameters as texture
+/// coordinates. Neighbouring patches don't share vertices, and the triangles
+/// along an edge collapsed into a point are left out.
+pub fn tessellate(patches: &[BezierPatch], level: u32) -> MeshData {
+    let level = level.max(1) as usize;
+    let side = level + 1;
+
+    let mut data = MeshData::default();
+    for patch in patches {
+        let first = data.vertices.len();
+        for row in 0..side {
+            let v = row as Scalar / level as Scalar;
+            for column in 0..side {
+                let u = column as Scalar / level as Scalar;
+                let position = patch.position(u, v);
+                let normal = patch.normal(u, v);
+                // With the normal along du x dv, cross(normal, du) points along +v
+                let (du, _) = patch.derivatives(u, v);
+                let tangent = (du - normal * normal.dot(&du))
+                    .try_normalize(NORM_EPS)
+                    .unwrap_or_else(Vector3::zeros);
+
+                data.vertices.push(Vertex {
+                    position: (position.x, position.y, position.z),
+                });
+                data.normals.push(Normal {
+                    normal: (normal.x, normal.y, normal.z),
+                });
+                data.tex_coords.push(TexCoord { tex_coords: (u, v) });
+                data.tangents.push(Tangent {
+                    tangent: (tangent.x, tangent.y, tangent.z, 1.0),
+                });
+            }
+        }
+
+        let collapsed = [0, 1, 2, 3].map(|edge| patch.is_collapsed(edge));
+        let index = |row: usize, column: usize| (first + row * side + column) as u32;
+        for row in 0..level {
+            for column in 0..level {
+                let (a, b) = (index(row, column), index(row, column + 1));
+                let (c, d) = (index(row + 1, column + 1), index(row + 1, column));
+                // a-b lies on the edge v = 0 in the first row, b-c on u = 1 in the last column
+                if !(row == 0 && collapsed[0] || column == level - 1 && collapsed[1]) {
+                    data.indices.extend_from_slice(&[a, b, c]);
+                }
+                if !(row == level - 1 && collapsed[2] || column == 0 && collapsed[3]) {
+                    data.indices.extend_from_slice(&[a, c, d]);
+                }
+            }
+        }
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The unit square in the xy plane, facing +z
+    fn square() -> BezierPatch {
+        let mut control_points = [[Vector3::zeros(); 4]; 4];
+        for (row, points) in control_points.iter_mut().enumerate() {
+            for (column, point) in points.iter_mut().enumerate() {
+                *point = Vector3::new(column as Scalar, row as Scalar, 0.0) / 3.0;
+            }
+        }
+        BezierPatch::new(control_points)
+    }
+
+    /// A cone over the square, its edge at v = 0 collapsed into the apex
+    fn cone() -> BezierPatch {
+        let mut patch = square();
+        patch.control_points[0] = [Vector3::new(0.5, 0.0, 1.0); 4];
+        patch
+    }
+
+    fn min_area(data: &MeshData) -> Scalar {
+        data.triangles()
+            .map(|triangle| data.face_normal(triangle).norm() / 2.0)
+            .fold(Scalar::INFINITY, Scalar::min)
+    }
+
+    #[test]
+    fn tessellates_a_grid_per_patch() {
+        for level in 1..6 {
+            let data = tessellate(&[square(), square()], level);
+            let side = (level + 1) as usize;
+            assert_eq!(data.vertex_count(), 2 * side * side);
+            assert_eq!(data.triangle_count(), 2 * 2 * (level * level) as usize);
+            assert_eq!(data.tex_coords.len(), data.vertex_count());
+            assert_eq!(data.tangents.len(), data.vertex_count());
+            assert!((data.stats().area - 2.0).abs() < 1e-5);
+        }
+
+        let data = tessellate(&[square()], 4);
+        for triangle in data.triangles() {
+            assert!(data.face_normal(triangle).normalize().z > 0.999);
+        }
+        for (normal, tangent) in data.normals.iter().zip(&data.tangents) {
+            assert_eq!(normal.normal, (0.0, 0.0, 1.0));
+            assert_eq!(tangent.tangent, (1.0, 0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn collapsed_edges_leave_no_triangles() {
+        assert!(cone().is_collapsed(0));
+        assert!((1..4).all(|edge| !cone().is_collapsed(edge)));
+        for level in 1..6 {
+            let data = tessellate(&[cone()], level);
+            let level = level as usize;
+            // One triangle of each quad along the collapsed edge is left out
+            assert_eq!(data.triangle_count(), 2 * level * level - level);
+            assert!(min_area(&data) > 1e-6);
+        }
+
+        // The normal at the apex comes from just inside the patch
+        let normal = cone().normal(0.5, 0.0);
+        assert!((normal.norm() - 1.0).abs() < 1e-5);
+        assert!(normal.z > 0.0);
+    }
+}
@@ -1,6 +1,6 @@
-use crate::consts::*;
-use crate::{normalize, Scalar, Unit, Vector3};
+use crate::{look_to, normalize, perspective, view_basis, Error, Scalar, Unit, Vector3};
 
+#[derive(Copy, Clone)]
 struct ClippingPlanes {
     near: Scalar,
     far: Scalar,
@@ -12,6 +12,7 @@ impl ClippingPlanes {
     }
 }
 
+#[derive(Copy, Clone)]
 pub struct Camera {
     position: Vector3,
     direction: Unit<Vector3>,
@@ -22,15 +23,21 @@ pub struct Camera {
 }
 
 impl Camera {
-    pub fn new(position: Vector3, direction: Vector3, resolution: (u32, u32), fov: u32) -> Self {
-        Self {
+    /// Fails if `direction` has (nearly) zero length
+    pub fn new(
+        position: Vector3,
+        direction: Vector3,
+        resolution: (u32, u32),
+        fov: u32,
+    ) -> Result<Self, Error> {
+        Ok(Self {
             position,
-            direction: normalize(direction),
+            direction: normalize(direction)?,
             resolution,
             fov,
             aspect_ratio: resolution.0 as Scalar / resolution.1 as Scalar,
             clipping_planes: ClippingPlanes::new(0.1, 1024.0),
-        }
+        })
     }
 
     pub fn update_resolution(&mut self, resolution: (u32, u32)) {
@@ -38,39 +45,83 @@ impl Camera {
         self.aspect_ratio = resolution.0 as Scalar / resolution.1 as Scalar
     }
 
+    /// Width and height of the frame in pixels
+    pub fn resolution(&self) -> (u32, u32) {
+        self.resolution
+    }
+
+    pub fn set_position(&mut self, position: Vector3) {
+        self.position = position;
+    }
+
+    /// Keeps the old direction if `direction` has (nearly) zero length
+    pub fn set_direction(&mut self, direction: Vector3) -> Result<(), Error> {
+        self.direction = normalize(direction)?;
+        Ok(())
+    }
+
+    pub fn set_clipping_planes(&mut self, near: Scalar, far: Scalar) {
+        self.clipping_planes = ClippingPlanes::new(near, far);
+    }
+
+    pub fn position(&self) -> Vector3 {
+        self.position
+    }
+
+    pub fn direction(&self) -> Vector3 {
+        self.direction.into_inner()
+    }
+
+    pub fn near(&self) -> Scalar {
+        self.clipping_planes.near
+    }
+
+    pub fn far(&self) -> Scalar {
+        self.clipping_planes.far
+    }
+
+    /// Vertical field of view in radians
+    pub fn fov(&self) -> Scalar {
+        (self.fov as Scalar).to_radians()
+    }
+
+    /// Vertical field of view in degrees, as passed to `new`
+    pub fn fov_degrees(&self) -> u32 {
+        self.fov
+    }
+
+    pub fn aspect_ratio(&self) -> Scalar {
+        self.aspect_ratio
+    }
+
     /// Builds a frame specific perspective transform
     pub fn get_perspective_matrix(&self) -> [[f32; 4]; 4] {
-        let fov = (self.fov as Scalar).to_radians();
-        let f = 1.0 / (fov / 2.0).tan();
-        let (znear, zfar) = (self.clipping_planes.near, self.clipping_planes.far);
-
-        [
-            [f / self.aspect_ratio, 0.0, 0.0, 0.0],
-            [0.0, f, 0.0, 0.0],
-            [0.0, 0.0, (zfar + znear) / (zfar - znear), 1.0],
-            [0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0],
-        ]
+        perspective(self.fov(), self.aspect_ratio, self.near(), self.far()).into()
     }
 
     /// Builds the frame specific view matrix from a given camera state
     pub fn get_view_matrix(&self) -> [[f32; 4]; 4] {
-        let up = UP;
-        let s = normalize(up.cross(&self.direction));
-        let u = self.direction.cross(&s);
-
-        let p = Vector3::new(
-            -self.position.dot(&s),
-            -self.position.dot(&u),
-            -self.position.dot(&self.direction),
-        );
-
-        let f = self.direction;
-
-        [
-            [s[0], u[0], f[0], 0.0],
-            [s[1], u[1], f[1], 0.0],
-            [s[2], u[2], f[2], 0.0],
-            [p[0], p[1], p[2], 1.0],
-        ]
+        look_to(self.position, self.direction).into()
+    }
+
+    /// World space corners of the view frustum slice between the `near` and `far` distances.
+    /// The first four corners lie on the near plane, the last four on the far plane.
+    pub fn frustum_corners(&self, near: Scalar, far: Scalar) -> [Vector3; 8] {
+        let (s, u, f) = view_basis(self.direction);
+        let tan_half = (self.fov() / 2.0).tan();
+
+        let mut corners = [Vector3::zeros(); 8];
+        for (i, &depth) in [near, far].iter().enumerate() {
+            let center = self.position + f * depth;
+            let half_height = depth * tan_half;
+            let half_width = half_height * self.aspect_ratio;
+            for (j, &(sx, sy)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                .iter()
+                .enumerate()
+            {
+                corners[i * 4 + j] = center + s * (sx * half_width) + u * (sy * half_height);
+            }
+        }
+        corners
     }
 }
@@ -1,5 +1,84 @@
-use crate::{Unit, Vector3, NORM_EPS};
+use crate::consts::*;
+use crate::{Error, Matrix4, Scalar, Unit, Vector3, NORM_EPS};
 
-pub fn normalize(v: Vector3) -> Unit<Vector3> {
-    Unit::try_new(v, NORM_EPS).unwrap()
+/// Scales `v` to unit length, failing for vectors shorter than `NORM_EPS`
+pub fn normalize(v: Vector3) -> Result<Unit<Vector3>, Error> {
+    Unit::try_new(v, NORM_EPS).ok_or(Error::DegenerateVector(v))
+}
+
+/// Side, up and forward axes of a view looking along `direction`.
+/// Falls back to a forward up-vector when looking straight up or down.
+pub fn view_basis(direction: Unit<Vector3>) -> (Vector3, Vector3, Vector3) {
+    let f = direction.into_inner();
+    let up = if f.dot(&UP).abs() > 0.999 {
+        FORWARD
+    } else {
+        UP
+    };
+    // The fallback keeps `up` away from `f`, so the cross product never vanishes
+    let s = up.cross(&f).normalize();
+    let u = f.cross(&s);
+    (s, u, f)
+}
+
+/// Builds a view matrix looking from `position` along `direction`.
+#[rustfmt::skip]
+pub fn look_to(position: Vector3, direction: Unit<Vector3>) -> Matrix4 {
+    let (s, u, f) = view_basis(direction);
+
+    Matrix4::new(
+        s[0], s[1], s[2], -position.dot(&s),
+        u[0], u[1], u[2], -position.dot(&u),
+        f[0], f[1], f[2], -position.dot(&f),
+        0.0, 0.0, 0.0, 1.0,
+    )
+}
+
+/// Perspective projection with the depth axis pointing into the screen
+#[rustfmt::skip]
+pub fn perspective(fov: Scalar, aspect_ratio: Scalar, znear: Scalar, zfar: Scalar) -> Matrix4 {
+    let f = 1.0 / (fov / 2.0).tan();
+
+    Matrix4::new(
+        f / aspect_ratio, 0.0, 0.0, 0.0,
+        0.0, f, 0.0, 0.0,
+        0.0, 0.0, (zfar + znear) / (zfar - znear), -(2.0 * zfar * znear) / (zfar - znear),
+        0.0, 0.0, 1.0, 0.0,
+    )
+}
+
+/// Orthographic projection of the box [left, right] x [bottom, top] x [znear, zfar]
+#[rustfmt::skip]
+pub fn orthographic(
+    left: Scalar,
+    right: Scalar,
+    bottom: Scalar,
+    top: Scalar,
+    znear: Scalar,
+    zfar: Scalar,
+) -> Matrix4 {
+    Matrix4::new(
+        2.0 / (right - left), 0.0, 0.0, -(right + left) / (right - left),
+        0.0, 2.0 / (top - bottom), 0.0, -(top + bottom) / (top - bottom),
+        0.0, 0.0, 2.0 / (zfar - znear), -(zfar + znear) / (zfar - znear),
+        0.0, 0.0, 0.0, 1.0,
+    )
+}
+
+/// Decodes an sRGB encoded channel in [0, 1] to linear light
+pub fn srgb_to_linear(value: Scalar) -> Scalar {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear channel in [0, 1] as sRGB
+pub fn linear_to_srgb(value: Scalar) -> Scalar {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
 }
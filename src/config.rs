@@ -0,0 +1,252 @@
+use crate::{
+    glutin, Display, DrawParameters, Error, EventLoop, LogicalSize, Shaders, WindowBuilder,
+};
+use glium::draw_parameters::{BackfaceCullingMode, DepthTest};
+use glutin::window::Fullscreen;
+use glutin::{Api, GlRequest};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// How the window is placed on the screen
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WindowMode {
+    Windowed,
+    /// A window without title bar and borders
+    Borderless,
+    /// Borderless fullscreen on the current monitor
+    Fullscreen,
+}
+
+/// Which triangles are skipped, seen from the camera
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Culling {
+    None,
+    Clockwise,
+    CounterClockwise,
+}
+
+impl From<Culling> for BackfaceCullingMode {
+    fn from(culling: Culling) -> Self {
+        match culling {
+            Culling::None => BackfaceCullingMode::CullingDisabled,
+            Culling::Clockwise => BackfaceCullingMode::CullClockwise,
+            Culling::CounterClockwise => BackfaceCullingMode::CullCounterClockwise,
+        }
+    }
+}
+
+/// Size, title and placement of the window
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+    pub mode: WindowMode,
+    pub resizable: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            title: "OpenGL".to_string(),
+            mode: WindowMode::Windowed,
+            resizable: true,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn new(width: u32, height: u32, title: &str) -> Self {
+        Self {
+            width,
+            height,
+            title: title.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_mode(mut self, mode: WindowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    fn builder(&self) -> WindowBuilder {
+        let builder = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(self.width, self.height))
+            .with_title(&self.title)
+            .with_resizable(self.resizable);
+
+        match self.mode {
+            WindowMode::Windowed => builder,
+            WindowMode::Borderless => builder.with_decorations(false),
+            WindowMode::Fullscreen => builder.with_fullscreen(Some(Fullscreen::Borderless(None))),
+        }
+    }
+}
+
+/// Everything needed to open the window and create the OpenGL context. Build it
+/// with the `with_*` methods or load it from a RON or JSON file, then call `build`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RendererConfig {
+    pub window: WindowConfig,
+    pub vsync: bool,
+    /// Samples per pixel, 0 disables multisampling. Must be a power of two.
+    pub msaa: u16,
+    pub depth_bits: u8,
+    pub stencil_bits: u8,
+    /// Let the framebuffer convert the linear shader output to sRGB
+    pub srgb: bool,
+    pub culling: Culling,
+    /// OpenGL version as (major, minor), `None` takes the latest the driver offers
+    pub gl_version: Option<(u8, u8)>,
+    /// Ask for a debug context, drivers report more errors and warnings on it
+    pub debug: bool,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            vsync: true,
+            msaa: 4,
+            depth_bits: 24,
+            stencil_bits: 0,
+            srgb: true,
+            culling: Culling::Clockwise,
+            gl_version: None,
+            debug: false,
+        }
+    }
+}
+
+impl RendererConfig {
+    pub fn new(window: WindowConfig) -> Self {
+        Self {
+            window,
+            ..Default::default()
+        }
+    }
+
+    /// Loads a config saved as RON, or as JSON when the file name ends in `.json`.
+    /// Missing fields keep their default values.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| Error::Config(format!("{}: {}", path.display(), err)))?;
+
+        if crate::scene_file::is_json(path) {
+            serde_json::from_str(&text).map_err(|err| err.to_string())
+        } else {
+            ron::de::from_str(&text).map_err(|err| err.to_string())
+        }
+        .map_err(|message| Error::Config(format!("{}: {}", path.display(), message)))
+    }
+
+    pub fn with_vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    pub fn with_msaa(mut self, samples: u16) -> Self {
+        self.msaa = samples;
+        self
+    }
+
+    pub fn with_depth_bits(mut self, bits: u8) -> Self {
+        self.depth_bits = bits;
+        self
+    }
+
+    pub fn with_stencil_bits(mut self, bits: u8) -> Self {
+        self.stencil_bits = bits;
+        self
+    }
+
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn with_culling(mut self, culling: Culling) -> Self {
+        self.culling = culling;
+        self
+    }
+
+    pub fn with_gl_version(mut self, major: u8, minor: u8) -> Self {
+        self.gl_version = Some((major, minor));
+        self
+    }
+
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Opens the window and compiles the shaders
+    pub fn build(&self) -> Result<Renderer, Error> {
+        if self.msaa != 0 && !self.msaa.is_power_of_two() {
+            return Err(Error::Config(format!(
+                "MSAA sample count {} is not a power of two",
+                self.msaa
+            )));
+        }
+
+        // Event handling
+        let event_loop = EventLoop::new();
+
+        // Open GL context specifier:
+        let gl_request = match self.gl_version {
+            Some(version) => GlRequest::Specific(Api::OpenGl, version),
+            None => GlRequest::Latest,
+        };
+        let context_builder = glutin::ContextBuilder::new()
+            .with_gl(gl_request)
+            .with_gl_debug_flag(self.debug)
+            .with_depth_buffer(self.depth_bits)
+            .with_stencil_buffer(self.stencil_bits)
+            .with_srgb(self.srgb)
+            .with_vsync(self.vsync)
+            .with_multisampling(self.msaa);
+
+        // Display object: OpenGL window
+        let display = Display::new(self.window.builder(), context_builder, &event_loop)?;
+
+        Ok(Renderer {
+            shaders: Shaders::new(&display)?,
+            draw_params: self.draw_parameters(),
+            display,
+            event_loop,
+        })
+    }
+
+    /// Depth testing and culling as configured, for drawing the scene
+    pub fn draw_parameters(&self) -> DrawParameters<'static> {
+        DrawParameters {
+            backface_culling: self.culling.into(),
+            depth: glium::Depth {
+                test: DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// The window, its event loop and what `render` needs to draw into it
+pub struct Renderer {
+    pub display: Display,
+    pub event_loop: EventLoop<()>,
+    pub shaders: Shaders,
+    pub draw_params: DrawParameters<'static>,
+}
@@ -0,0 +1,277 @@
+use crate::error::{allocation, shader};
+use crate::{
+    implement_vertex, orthographic, view_basis, Camera, Color, Display, DrawParameters, Error,
+    Light, LightKind, Matrix4, Point3, Program, Scalar, Surface, Unit, Vector3, VertexBuffer, BLUE,
+    FORWARD, GREEN, ORIGIN, PI, RED, RIGHT, UP,
+};
+use glium::backend::{Context, Facade};
+use glium::draw_parameters::{Blend, DepthTest};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::Rect;
+use std::rc::Rc;
+
+/// Segments of the circles making up spheres
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[derive(Copy, Clone, Debug)]
+struct DebugVertex {
+    position: (f32, f32, f32),
+    color: (f32, f32, f32, f32),
+}
+
+implement_vertex!(DebugVertex, position, color);
+
+/// Immediate mode line drawing for gizmos and visual debugging. The shapes are
+/// collected during a frame and drawn with one call to `render`, which also
+/// forgets them again. All positions are in world space.
+pub struct DebugDraw {
+    /// Hide the lines behind the scene's geometry, draw them on top otherwise
+    pub depth_test: bool,
+    lines: Vec<DebugVertex>,
+    program: Program,
+    context: Rc<Context>,
+    buffer: VertexBuffer<DebugVertex>,
+}
+
+impl DebugDraw {
+    pub fn new(display: &Display) -> Result<Self, Error> {
+        let program = Program::from_source(
+            display,
+            include_str!(r"shaders/debug_vertex.glsl"),
+            include_str!(r"shaders/debug_fragment.glsl"),
+            None,
+        )
+        .map_err(shader("debug lines"))?;
+
+        Ok(Self {
+            depth_test: true,
+            lines: Vec::new(),
+            program,
+            context: Rc::clone(display.get_context()),
+            buffer: VertexBuffer::empty_dynamic(display, 1024)
+                .map_err(allocation("debug line buffer"))?,
+        })
+    }
+
+    pub fn line(&mut self, a: Vector3, b: Vector3, color: Color) {
+        let color = color.to_linear();
+        let color = (color.r, color.g, color.b, color.a);
+        self.lines.push(DebugVertex {
+            position: (a.x, a.y, a.z),
+            color,
+        });
+        self.lines.push(DebugVertex {
+            position: (b.x, b.y, b.z),
+            color,
+        });
+    }
+
+    /// Closed polygon through the points
+    pub fn polygon(&mut self, points: &[Vector3], color: Color) {
+        for (i, &point) in points.iter().enumerate() {
+            self.line(point, points[(i + 1) % points.len()], color);
+        }
+    }
+
+    /// Axis aligned box between the `min` and `max` corners
+    pub fn aabb(&mut self, min: Vector3, max: Vector3, color: Color) {
+        let corner = |x: bool, y: bool, z: bool| {
+            Vector3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            )
+        };
+        for &(a, b) in [(false, false), (true, false), (true, true), (false, true)].iter() {
+            // Four edges along each axis
+            self.line(corner(false, a, b), corner(true, a, b), color);
+            self.line(corner(a, false, b), corner(a, true, b), color);
+            self.line(corner(a, b, false), corner(a, b, true), color);
+        }
+    }
+
+    /// Circle around `center` in the plane facing `normal`
+    pub fn circle(&mut self, center: Vector3, normal: Vector3, radius: Scalar, color: Color) {
+        let (s, u, _) = match Unit::try_new(normal, crate::NORM_EPS) {
+            Some(normal) => view_basis(normal),
+            None => return,
+        };
+        let points: Vec<Vector3> = (0..CIRCLE_SEGMENTS)
+            .map(|i| {
+                let angle = 2.0 * PI * i as Scalar / CIRCLE_SEGMENTS as Scalar;
+                center + (s * angle.cos() + u * angle.sin()) * radius
+            })
+            .collect();
+        self.polygon(&points, color);
+    }
+
+    /// Three circles around the axes
+    pub fn sphere(&mut self, center: Vector3, radius: Scalar, color: Color) {
+        self.circle(center, Vector3::x(), radius, color);
+        self.circle(center, Vector3::y(), radius, color);
+        self.circle(center, Vector3::z(), radius, color);
+    }
+
+    /// Line from `from` to `to` with a small pyramid as head
+    pub fn arrow(&mut self, from: Vector3, to: Vector3, color: Color) {
+        self.line(from, to, color);
+        let direction = to - from;
+        let (s, u, f) = match Unit::try_new(direction, crate::NORM_EPS) {
+            Some(direction) => view_basis(direction),
+            None => return,
+        };
+        let size = direction.norm() * 0.1;
+        let base = to - f * size;
+        for &side in [s, -s, u, -u].iter() {
+            self.line(to, base + side * (size * 0.5), color);
+        }
+    }
+
+    /// The x, y and z axes of a transform in red, green and blue, `size` long before scaling
+    pub fn axes(&mut self, transform: &Matrix4, size: Scalar) {
+        let origin = transform.transform_point(&Point3::origin()).coords;
+        for &(axis, color) in [
+            (Vector3::x(), RED),
+            (Vector3::y(), GREEN),
+            (Vector3::z(), BLUE),
+        ]
+        .iter()
+        {
+            let tip = transform.transform_point(&Point3::from(axis * size)).coords;
+            self.arrow(origin, tip, color);
+        }
+    }
+
+    /// The view frustum of `camera` between its near and far plane
+    pub fn frustum(&mut self, camera: &Camera, color: Color) {
+        let corners = camera.frustum_corners(camera.near(), camera.far());
+        self.polygon(&corners[..4], color);
+        self.polygon(&corners[4..], color);
+        for i in 0..4 {
+            self.line(corners[i], corners[i + 4], color);
+        }
+    }
+
+    /// Square grid on the XZ plane through `center` with `cells` cells of `spacing` per side
+    pub fn grid(&mut self, center: Vector3, spacing: Scalar, cells: u32, color: Color) {
+        let half = spacing * cells as Scalar / 2.0;
+        for i in 0..=cells {
+            let offset = i as Scalar * spacing - half;
+            self.line(
+                center + Vector3::new(offset, 0.0, -half),
+                center + Vector3::new(offset, 0.0, half),
+                color,
+            );
+            self.line(
+                center + Vector3::new(-half, 0.0, offset),
+                center + Vector3::new(half, 0.0, offset),
+                color,
+            );
+        }
+    }
+
+    /// Where a light is and where it shines: an arrow along its direction, and
+    /// its range as a sphere for point lights
+    pub fn light(&mut self, light: &Light, color: Color) {
+        match light.kind {
+            LightKind::Directional => {
+                self.arrow(light.position, light.position + light.direction(), color)
+            }
+            LightKind::Spot { range, .. } => self.arrow(
+                light.position,
+                light.position + light.direction() * range,
+                color,
+            ),
+            LightKind::Point { range } => self.sphere(light.position, range, color),
+        }
+    }
+
+    /// Draws the lines collected since the last call and clears them
+    pub fn render<S: Surface>(&mut self, frame: &mut S, camera: &Camera) -> Result<(), Error> {
+        let view_perspective = Matrix4::from(camera.get_perspective_matrix())
+            * Matrix4::from(camera.get_view_matrix());
+        let params = DrawParameters {
+            depth: glium::Depth {
+                test: if self.depth_test {
+                    DepthTest::IfLess
+                } else {
+                    DepthTest::Overwrite
+                },
+                write: false,
+                ..Default::default()
+            },
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+        self.draw_lines(frame, view_perspective, &params)
+    }
+
+    /// Draws the world axes as seen from `camera` into a `size` pixels wide
+    /// square in the lower left corner of `frame`, on top of everything else.
+    /// Lines collected for `render` are kept.
+    pub fn orientation<S: Surface>(
+        &mut self,
+        frame: &mut S,
+        camera: &Camera,
+        size: u32,
+    ) -> Result<(), Error> {
+        let collected = std::mem::take(&mut self.lines);
+        for &(axis, color) in [(RIGHT, RED), (UP, GREEN), (FORWARD, BLUE)].iter() {
+            self.arrow(ORIGIN, axis, color);
+            self.line(ORIGIN, -axis, color.with_alpha(0.3));
+        }
+
+        // Only the rotation of the view, looking at the origin from outside the unit sphere
+        let mut view = Matrix4::from(camera.get_view_matrix());
+        for row in 0..3 {
+            view[(row, 3)] = 0.0;
+        }
+        let projection = orthographic(-1.2, 1.2, -1.2, 1.2, -1.2, 1.2);
+        let margin = size / 8;
+        let params = DrawParameters {
+            viewport: Some(Rect {
+                left: margin,
+                bottom: margin,
+                width: size,
+                height: size,
+            }),
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+        let result = self.draw_lines(frame, projection * view, &params);
+        self.lines = collected;
+        result
+    }
+
+    /// Uploads and draws the collected lines, then clears them
+    fn draw_lines<S: Surface>(
+        &mut self,
+        frame: &mut S,
+        view_perspective: Matrix4,
+        params: &DrawParameters,
+    ) -> Result<(), Error> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        if self.lines.len() > self.buffer.len() {
+            self.buffer =
+                VertexBuffer::empty_dynamic(&self.context, self.lines.len().next_power_of_two())
+                    .map_err(allocation("debug line buffer"))?;
+        }
+        // The buffer holds at least `lines.len()` vertices at this point
+        let slice = self.buffer.slice(0..self.lines.len()).unwrap();
+        slice.write(&self.lines);
+        self.lines.clear();
+
+        let view_perspective: [[f32; 4]; 4] = view_perspective.into();
+        frame.draw(
+            slice,
+            NoIndices(PrimitiveType::LinesList),
+            &self.program,
+            &crate::uniform! { view_perspective: view_perspective },
+            params,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,417 @@
+use crate::error::{allocation, shader};
+use crate::instancing::{self, InstanceBuffer};
+use crate::lod;
+use crate::ssao::nearest;
+use crate::{
+    ibl, light, material, paste_after_version, shadow, ssao, uniform, with_lighting,
+    with_pbr_material, Camera, Display, DrawParameters, Error, Light, LightKind, Material, Matrix4,
+    Point3, Program, RenderOptions, Scalar, SceneObject, Shaders, Surface, UniformChain, Vector3,
+    BLACK,
+};
+use glium::backend::{Context, Facade};
+use glium::draw_parameters::{Blend, BlendingFunction, DepthTest, LinearBlendingFactor};
+use glium::framebuffer::MultiOutputFrameBuffer;
+use glium::texture::{
+    DepthFormat, DepthTexture2d, MipmapsOption, Texture2d, UncompressedFloatFormat,
+};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use glium::Rect;
+use std::rc::Rc;
+
+/// Which renderer draws the opaque objects
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RenderPath {
+    /// Every object is shaded as it is drawn, see `render`
+    #[default]
+    Forward,
+    /// Objects are written to a G-buffer first, then each light shades the pixels it
+    /// reaches, see `DeferredRenderer`
+    Deferred,
+}
+
+/// The surface attributes of the visible objects, one texel per pixel
+pub struct GBuffer {
+    /// Phong diffuse or PBR base color. Alpha is 0 for Phong and 1 for PBR surfaces.
+    pub albedo: Texture2d,
+    /// View space normal
+    pub normal: Texture2d,
+    /// PBR metallic, roughness and occlusion, or the Phong specular color
+    pub material: Texture2d,
+    /// PBR emissive or Phong ambient color. Alpha holds the Phong shininess.
+    pub emission: Texture2d,
+    pub depth: DepthTexture2d,
+}
+
+impl GBuffer {
+    pub fn new<F: Facade>(facade: &F, (width, height): (u32, u32)) -> Result<Self, Error> {
+        let texture = |format, what| {
+            Texture2d::empty_with_format(facade, format, MipmapsOption::NoMipmap, width, height)
+                .map_err(allocation(what))
+        };
+        Ok(Self {
+            albedo: texture(UncompressedFloatFormat::U8U8U8U8, "G-buffer albedo")?,
+            normal: texture(UncompressedFloatFormat::F16F16F16F16, "G-buffer normals")?,
+            material: texture(UncompressedFloatFormat::U8U8U8U8, "G-buffer material")?,
+            emission: texture(UncompressedFloatFormat::F16F16F16F16, "G-buffer emission")?,
+            depth: DepthTexture2d::empty_with_format(
+                facade,
+                DepthFormat::F32,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )
+            .map_err(allocation("G-buffer depth"))?,
+        })
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.albedo.dimensions()
+    }
+}
+
+struct Programs {
+    phong: Program,
+    pbr: Program,
+    ambient: Program,
+    light: Program,
+}
+
+/// Deferred shading: the objects are drawn once into a `GBuffer`, then the
+/// lights are added up in screen space. Point and spot lights only shade the
+/// screen rectangle their range covers, so many small lights stay cheap.
+pub struct DeferredRenderer {
+    context: Rc<Context>,
+    gbuffer: GBuffer,
+    programs: Programs,
+    draw_params: DrawParameters<'static>,
+    instances: InstanceBuffer,
+}
+
+impl DeferredRenderer {
+    /// `draw_params` are used for the G-buffer pass, as `render` uses them
+    pub fn new(display: &Display, draw_params: &DrawParameters<'static>) -> Result<Self, Error> {
+        let fullscreen = include_str!(r"shaders/fullscreen_vertex.glsl");
+        let with_gbuffer = |f_shader: &str| {
+            with_lighting(&paste_after_version(
+                f_shader,
+                include_str!(r"shaders/gbuffer.glsl"),
+            ))
+        };
+
+        let programs = Programs {
+            phong: Program::from_source(
+                display,
+                include_str!(r"shaders/vertex_shader.glsl"),
+                include_str!(r"shaders/gbuffer_phong_fragment.glsl"),
+                None,
+            )
+            .map_err(shader("Phong G-buffer"))?,
+            pbr: Program::from_source(
+                display,
+                include_str!(r"shaders/pbr_vertex_shader.glsl"),
+                &with_pbr_material(include_str!(r"shaders/gbuffer_pbr_fragment.glsl")),
+                None,
+            )
+            .map_err(shader("PBR G-buffer"))?,
+            ambient: Program::from_source(
+                display,
+                fullscreen,
+                &with_gbuffer(include_str!(r"shaders/deferred_ambient_fragment.glsl")),
+                None,
+            )
+            .map_err(shader("deferred ambient light"))?,
+            light: Program::from_source(
+                display,
+                fullscreen,
+                &with_gbuffer(include_str!(r"shaders/deferred_light_fragment.glsl")),
+                None,
+            )
+            .map_err(shader("deferred light accumulation"))?,
+        };
+
+        Ok(Self {
+            context: display.get_context().clone(),
+            gbuffer: GBuffer::new(display, (1, 1))?,
+            programs,
+            draw_params: draw_params.clone(),
+            instances: InstanceBuffer::new(display)?,
+        })
+    }
+
+    /// The G-buffer of the last frame
+    pub fn gbuffer(&self) -> &GBuffer {
+        &self.gbuffer
+    }
+
+    /// Draws the objects lit by all `lights`. The shadow map belongs to the first light.
+    /// Transparent objects are drawn forward after the lighting passes. Afterwards
+    /// `target` holds the depth of the opaque objects, so the sky can follow with `render_sky`.
+    pub fn render<S: Surface>(
+        &mut self,
+        target: &mut S,
+        object_list: &[Box<SceneObject>],
+        camera: &Camera,
+        lights: &[Light],
+        options: RenderOptions,
+        shaders: &Shaders,
+    ) -> Result<(), Error> {
+        let dimensions = target.get_dimensions();
+        if self.gbuffer.dimensions() != dimensions {
+            self.gbuffer = GBuffer::new(&self.context, dimensions)?;
+        }
+
+        lod::select_levels(object_list, camera);
+        let perspective = camera.get_perspective_matrix();
+        let view = camera.get_view_matrix();
+        self.fill_gbuffer(object_list, view, perspective, shaders)?;
+
+        let view = Matrix4::from(view);
+        let inverse_perspective: [[f32; 4]; 4] = Matrix4::from(perspective)
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .into();
+        let inverse_view: [[f32; 4]; 4] =
+            view.try_inverse().unwrap_or_else(Matrix4::identity).into();
+        let gbuffer = &self.gbuffer;
+        let gbuffer_uniforms = || {
+            uniform! {
+                g_albedo: nearest(&gbuffer.albedo),
+                g_normal: nearest(&gbuffer.normal),
+                g_material: nearest(&gbuffer.material),
+                g_emission: nearest(&gbuffer.emission),
+                g_depth: gbuffer
+                    .depth
+                    .sampled()
+                    .minify_filter(MinifySamplerFilter::Nearest)
+                    .magnify_filter(MagnifySamplerFilter::Nearest),
+                inverse_perspective: inverse_perspective,
+                inverse_view: inverse_view,
+            }
+        };
+        let scene_lighting = UniformChain(
+            ibl::EnvironmentUniforms::new(options.environment, &view, &shaders.fallback),
+            ssao::SsaoUniforms {
+                ssao: options.ssao,
+                fallback: &shaders.fallback.white,
+            },
+        );
+
+        // Ambient and emitted light, writing the depth of the objects into the target
+        let params = DrawParameters {
+            depth: glium::Depth {
+                test: DepthTest::Overwrite,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let no_shadows = shadow::ShadowUniforms {
+            shadow_map: None,
+            fallback: &shaders.no_shadows,
+        };
+        let uniforms = UniformChain(UniformChain(gbuffer_uniforms(), scene_lighting), no_shadows);
+        draw_fullscreen(target, &self.programs.ambient, &uniforms, &params)?;
+
+        // Every light adds onto the ambient light
+        let blend = BlendingFunction::Addition {
+            source: LinearBlendingFactor::One,
+            destination: LinearBlendingFactor::One,
+        };
+        for (i, light) in lights.iter().enumerate() {
+            let scissor = match light.kind {
+                LightKind::Directional => None,
+                LightKind::Spot { range, .. } | LightKind::Point { range } => {
+                    match light_bounds(camera, &view, light.position, range, dimensions) {
+                        Some(bounds) => Some(bounds),
+                        // Entirely off screen
+                        None => continue,
+                    }
+                }
+            };
+            let params = DrawParameters {
+                blend: Blend {
+                    color: blend,
+                    alpha: blend,
+                    constant_value: (0.0, 0.0, 0.0, 0.0),
+                },
+                scissor,
+                ..Default::default()
+            };
+            let shadows = shadow::ShadowUniforms {
+                shadow_map: options.shadow_map.filter(|_| i == 0),
+                fallback: &shaders.no_shadows,
+            };
+            let uniforms = UniformChain(
+                UniformChain(gbuffer_uniforms(), scene_lighting),
+                UniformChain(light::LightUniforms::new(light, &view), shadows),
+            );
+            draw_fullscreen(target, &self.programs.light, &uniforms, &params)?;
+        }
+
+        // Transparent objects don't fit into the G-buffer, they are shaded forward
+        // on top, lit by the first light
+        let unlit;
+        let light = match lights.first() {
+            Some(light) => light,
+            None => {
+                unlit = Light::directional(-Vector3::y())?.with_color(BLACK);
+                &unlit
+            }
+        };
+        let lighting = crate::lighting_uniforms(camera, light, options, shaders);
+        crate::render_transparent(
+            target,
+            object_list,
+            camera,
+            lighting,
+            shaders,
+            &self.draw_params,
+        )
+    }
+
+    /// Writes the surface attributes of the objects into the G-buffer
+    fn fill_gbuffer(
+        &mut self,
+        object_list: &[Box<SceneObject>],
+        view: [[f32; 4]; 4],
+        perspective: [[f32; 4]; 4],
+        shaders: &Shaders,
+    ) -> Result<(), Error> {
+        let gbuffer = &self.gbuffer;
+        let mut framebuffer = MultiOutputFrameBuffer::with_depth_buffer(
+            &self.context,
+            [
+                ("albedo", &gbuffer.albedo),
+                ("normal", &gbuffer.normal),
+                ("material", &gbuffer.material),
+                ("emission", &gbuffer.emission),
+            ]
+            .iter()
+            .cloned(),
+            &gbuffer.depth,
+        )
+        .map_err(allocation("G-buffer framebuffer"))?;
+        framebuffer.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
+
+        let opaque = object_list
+            .iter()
+            .filter(|object| !object.material.is_transparent());
+        for batch in instancing::batches(opaque, true) {
+            let mesh = batch.object.drawn_mesh();
+            let instance_slice = self.instances.upload(&batch.instances)?;
+            let instances = instance_slice
+                .per_instance()
+                .map_err(|_| Error::InstancingNotSupported)?;
+            let uniforms = uniform! {
+                view: view,
+                perspective: perspective,
+            };
+
+            match &batch.object.material {
+                Material::Phong(material) => framebuffer.draw(
+                    (
+                        &mesh.vertex_bfr,
+                        &mesh.normal_bfr,
+                        &mesh.color_bfr,
+                        instances,
+                    ),
+                    &mesh.ind_bfr,
+                    &self.programs.phong,
+                    &UniformChain(uniforms, material::PhongUniforms(material)),
+                    &self.draw_params,
+                ),
+                Material::Pbr(material) => framebuffer.draw(
+                    (
+                        &mesh.vertex_bfr,
+                        &mesh.normal_bfr,
+                        &mesh.uv_bfr,
+                        &mesh.tangent_bfr,
+                        &mesh.color_bfr,
+                        instances,
+                    ),
+                    &mesh.ind_bfr,
+                    &self.programs.pbr,
+                    &UniformChain(
+                        uniforms,
+                        material::PbrUniforms {
+                            material,
+                            fallback: &shaders.fallback,
+                        },
+                    ),
+                    &self.draw_params,
+                ),
+            }?;
+        }
+
+        Ok(())
+    }
+}
+
+fn draw_fullscreen<S: Surface, U: glium::uniforms::Uniforms>(
+    target: &mut S,
+    program: &Program,
+    uniforms: &U,
+    params: &DrawParameters,
+) -> Result<(), Error> {
+    target.draw(
+        glium::vertex::EmptyVertexAttributes { len: 3 },
+        glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+        program,
+        uniforms,
+        params,
+    )?;
+    Ok(())
+}
+
+/// The pixels covered by a light's range, `None` if they are all off screen.
+/// Projects the corners of the view space box around the range sphere.
+fn light_bounds(
+    camera: &Camera,
+    view: &Matrix4,
+    position: Vector3,
+    range: Scalar,
+    (width, height): (u32, u32),
+) -> Option<Rect> {
+    let center = view.transform_point(&Point3::from(position));
+    if center.z + range < camera.near() {
+        return None;
+    }
+    let full = Rect {
+        left: 0,
+        bottom: 0,
+        width,
+        height,
+    };
+    // The camera is inside or too close to the light's range to project it
+    if center.z - range <= camera.near() {
+        return Some(full);
+    }
+
+    let perspective = Matrix4::from(camera.get_perspective_matrix());
+    let (mut min, mut max) = ([1.0 as Scalar; 2], [-1.0 as Scalar; 2]);
+    for &dx in [-range, range].iter() {
+        for &dy in [-range, range].iter() {
+            for &dz in [-range, range].iter() {
+                let corner = center + Vector3::new(dx, dy, dz);
+                let projected = perspective.transform_point(&corner);
+                for axis in 0..2 {
+                    min[axis] = min[axis].min(projected[axis]);
+                    max[axis] = max[axis].max(projected[axis]);
+                }
+            }
+        }
+    }
+
+    let to_pixels = |ndc: Scalar, size: u32| (ndc.clamp(-1.0, 1.0) * 0.5 + 0.5) * size as Scalar;
+    let (left, right) = (to_pixels(min[0], width), to_pixels(max[0], width));
+    let (bottom, top) = (to_pixels(min[1], height), to_pixels(max[1], height));
+    if right <= left || top <= bottom {
+        return None;
+    }
+    Some(Rect {
+        left: left.floor() as u32,
+        bottom: bottom.floor() as u32,
+        width: (right.ceil() - left.floor()) as u32,
+        height: (top.ceil() - bottom.floor()) as u32,
+    })
+}
@@ -0,0 +1,126 @@
+use crate::{ImportError, SceneFileError, Vector3};
+use glium::backend::glutin::DisplayCreationError;
+use glium::buffer::ReadError;
+use glium::{DrawError, ProgramCreationError};
+use std::fmt;
+
+/// Everything that can go wrong in the library
+#[derive(Debug)]
+pub enum Error {
+    /// The window or the OpenGL context couldn't be created
+    Context(DisplayCreationError),
+    /// A renderer config file couldn't be read or holds invalid settings
+    Config(String),
+    /// A shader program failed to compile or link, the source holds the driver log
+    Shader {
+        name: &'static str,
+        source: ProgramCreationError,
+    },
+    /// A buffer, texture or framebuffer couldn't be allocated on the GPU
+    Allocation {
+        what: &'static str,
+        source: Box<dyn std::error::Error>,
+    },
+    /// The OpenGL version has no instanced drawing
+    InstancingNotSupported,
+    Draw(DrawError),
+    /// A buffer couldn't be read back from the GPU
+    Read(ReadError),
+    /// A mesh or scene file couldn't be loaded
+    Asset(Box<dyn std::error::Error>),
+    /// A direction of (nearly) zero length was given where a unit vector is needed
+    DegenerateVector(Vector3),
+    /// A mesh needs more vertices than its u16 indices can address
+    TooManyVertices(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Context(source) => write!(f, "couldn't create the OpenGL context: {}", source),
+            Error::Config(message) => write!(f, "invalid renderer config: {}", message),
+            Error::Shader { name, source } => {
+                write!(f, "couldn't build the {} shaders: {}", name, source)
+            }
+            Error::Allocation { what, source } => {
+                write!(f, "couldn't allocate {}: {}", what, source)
+            }
+            Error::InstancingNotSupported => write!(f, "instanced drawing is not supported"),
+            Error::Draw(source) => write!(f, "draw call failed: {}", source),
+            Error::Read(source) => write!(f, "couldn't read back a buffer: {}", source),
+            Error::Asset(source) => write!(f, "{}", source),
+            Error::DegenerateVector(v) => write!(
+                f,
+                "vector ({}, {}, {}) is too short to define a direction",
+                v.x, v.y, v.z
+            ),
+            Error::TooManyVertices(count) => write!(
+                f,
+                "{} vertices don't fit into a mesh with 16 bit indices",
+                count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Context(source) => Some(source),
+            Error::Shader { source, .. } => Some(source),
+            Error::Allocation { source, .. } => Some(source.as_ref()),
+            Error::Draw(source) => Some(source),
+            Error::Read(source) => Some(source),
+            Error::Asset(source) => Some(source.as_ref()),
+            Error::Config(_)
+            | Error::InstancingNotSupported
+            | Error::DegenerateVector(_)
+            | Error::TooManyVertices(_) => None,
+        }
+    }
+}
+
+impl From<DisplayCreationError> for Error {
+    fn from(source: DisplayCreationError) -> Self {
+        Error::Context(source)
+    }
+}
+
+impl From<DrawError> for Error {
+    fn from(source: DrawError) -> Self {
+        Error::Draw(source)
+    }
+}
+
+impl From<ReadError> for Error {
+    fn from(source: ReadError) -> Self {
+        Error::Read(source)
+    }
+}
+
+impl From<ImportError> for Error {
+    fn from(source: ImportError) -> Self {
+        Error::Asset(Box::new(source))
+    }
+}
+
+impl From<SceneFileError> for Error {
+    fn from(source: SceneFileError) -> Self {
+        Error::Asset(Box::new(source))
+    }
+}
+
+/// Wraps glium allocation errors: `.map_err(allocation("vertex buffer"))?`
+pub(crate) fn allocation<E: std::error::Error + 'static>(
+    what: &'static str,
+) -> impl FnOnce(E) -> Error {
+    move |source| Error::Allocation {
+        what,
+        source: Box::new(source),
+    }
+}
+
+/// Wraps glium shader errors: `.map_err(shader("Phong"))?`
+pub(crate) fn shader(name: &'static str) -> impl FnOnce(ProgramCreationError) -> Error {
+    move |source| Error::Shader { name, source }
+}
@@ -0,0 +1,445 @@
+use crate::{
+    linear_to_srgb, Error, Material, MeshData, SceneObject, Vector3, VertexColor, NORM_EPS,
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// OBJ, STL and PLY are read as right-handed by other tools, while the view
+// matrix of `Camera` mirrors the X axis. Exported geometry is mirrored along X
+// like `gltf_import` does on the way in, so files look the same elsewhere.
+
+/// File formats meshes can be written in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MeshFormat {
+    /// Wavefront OBJ, with an MTL file for the material when exporting objects
+    Obj,
+    Stl {
+        binary: bool,
+    },
+    Ply {
+        binary: bool,
+    },
+}
+
+impl MeshFormat {
+    /// Guesses the format from a `.obj`, `.stl` or `.ply` extension, preferring binary
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "obj" => Some(MeshFormat::Obj),
+            "stl" => Some(MeshFormat::Stl { binary: true }),
+            "ply" => Some(MeshFormat::Ply { binary: true }),
+            _ => None,
+        }
+    }
+}
+
+/// Writes a mesh to a file. OBJ files get no material library, see `export_object`.
+pub fn export_mesh<P: AsRef<Path>>(
+    data: &MeshData,
+    path: P,
+    format: MeshFormat,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let mut writer = BufWriter::new(File::create(path).map_err(io_error(path))?);
+    write_mesh(&mut writer, data, format)
+        .and_then(|()| writer.flush())
+        .map_err(io_error(path))
+}
+
+/// Writes an object's mesh to a file in the format its extension names. With
+/// `world_transform` the object's placement in the scene is baked in. OBJ files
+/// get an MTL file with the object's material next to them.
+pub fn export_object<P: AsRef<Path>>(
+    object: &SceneObject,
+    path: P,
+    world_transform: bool,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let format = MeshFormat::from_path(path)
+        .ok_or_else(|| Error::Asset(format!("{}: unknown mesh format", path.display()).into()))?;
+
+    let mut data = object.mesh.to_data()?;
+    if world_transform {
+        data.transform(&object.get_model_matrix().into());
+    }
+    if format != MeshFormat::Obj {
+        return export_mesh(&data, path, format);
+    }
+
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("mesh");
+    let mtl_path = path.with_extension("mtl");
+    let mtl_name = mtl_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("mesh.mtl");
+
+    let mut writer = BufWriter::new(File::create(&mtl_path).map_err(io_error(&mtl_path))?);
+    write_mtl(&mut writer, name, &object.material)
+        .and_then(|()| writer.flush())
+        .map_err(io_error(&mtl_path))?;
+
+    let mut writer = BufWriter::new(File::create(path).map_err(io_error(path))?);
+    write_obj(&mut writer, &data, Some((mtl_name, name)))
+        .and_then(|()| writer.flush())
+        .map_err(io_error(path))
+}
+
+/// Writes a mesh in any of the formats, OBJ without a material library
+pub fn write_mesh<W: Write>(writer: &mut W, data: &MeshData, format: MeshFormat) -> io::Result<()> {
+    match format {
+        MeshFormat::Obj => write_obj(writer, data, None),
+        MeshFormat::Stl { binary: true } => write_stl_binary(writer, data),
+        MeshFormat::Stl { binary: false } => write_stl_ascii(writer, data, "mesh"),
+        MeshFormat::Ply { binary } => write_ply(writer, data, binary),
+    }
+}
+
+/// Wavefront OBJ with positions, and texture coordinates and normals when the
+/// mesh has them for every vertex. `material` names an MTL file and the
+/// material in it to use.
+pub fn write_obj<W: Write>(
+    writer: &mut W,
+    data: &MeshData,
+    material: Option<(&str, &str)>,
+) -> io::Result<()> {
+    writeln!(writer, "# opengl-window")?;
+    if let Some((library, _)) = material {
+        writeln!(writer, "mtllib {}", library)?;
+    }
+
+    let has_tex_coords =
+        !data.tex_coords.is_empty() && data.tex_coords.len() == data.vertex_count();
+    let has_normals = !data.normals.is_empty() && data.normals.len() == data.vertex_count();
+    for vertex in &data.vertices {
+        let (x, y, z) = vertex.position;
+        writeln!(writer, "v {} {} {}", -x, y, z)?;
+    }
+    if has_tex_coords {
+        for tex_coord in &data.tex_coords {
+            // OBJ puts the origin of the texture at the bottom, we at the top
+            let (u, v) = tex_coord.tex_coords;
+            writeln!(writer, "vt {} {}", u, 1.0 - v)?;
+        }
+    }
+    if has_normals {
+        for normal in &data.normals {
+            let (x, y, z) = normal.normal;
+            writeln!(writer, "vn {} {} {}", -x, y, z)?;
+        }
+    }
+
+    if let Some((_, name)) = material {
+        writeln!(writer, "usemtl {}", name)?;
+    }
+    // 1-based, the same index for all attributes a vertex has
+    let corner = |vertex: usize| match (has_tex_coords, has_normals) {
+        (true, true) => format!("{0}/{0}/{0}", vertex + 1),
+        (true, false) => format!("{0}/{0}", vertex + 1),
+        (false, true) => format!("{0}//{0}", vertex + 1),
+        (false, false) => format!("{}", vertex + 1),
+    };
+    for [a, b, c] in mirrored_triangles(data) {
+        writeln!(writer, "f {} {} {}", corner(a), corner(b), corner(c))?;
+    }
+    Ok(())
+}
+
+/// The material as an MTL entry. PBR materials use the common PBR extension
+/// (`Pr`, `Pm`, `Ke`), textures aren't written.
+pub fn write_mtl<W: Write>(writer: &mut W, name: &str, material: &Material) -> io::Result<()> {
+    writeln!(writer, "newmtl {}", name)?;
+    match material {
+        Material::Phong(phong) => {
+            let (ambient, diffuse, specular) = (phong.ambient, phong.diffuse, phong.specular);
+            writeln!(writer, "Ka {} {} {}", ambient.r, ambient.g, ambient.b)?;
+            writeln!(writer, "Kd {} {} {}", diffuse.r, diffuse.g, diffuse.b)?;
+            writeln!(writer, "Ks {} {} {}", specular.r, specular.g, specular.b)?;
+            writeln!(writer, "Ns {}", phong.shininess)?;
+            writeln!(writer, "d {}", diffuse.a)?;
+        }
+        Material::Pbr(pbr) => {
+            let (base, emissive) = (pbr.base_color, pbr.emissive);
+            writeln!(writer, "Kd {} {} {}", base.r, base.g, base.b)?;
+            writeln!(writer, "Ke {} {} {}", emissive.r, emissive.g, emissive.b)?;
+            writeln!(writer, "Pr {}", pbr.roughness)?;
+            writeln!(writer, "Pm {}", pbr.metallic)?;
+            writeln!(writer, "d {}", base.a)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_stl_ascii<W: Write>(writer: &mut W, data: &MeshData, name: &str) -> io::Result<()> {
+    writeln!(writer, "solid {}", name)?;
+    for triangle in mirrored_triangles(data) {
+        let normal = face_normal(data, triangle);
+        writeln!(
+            writer,
+            "  facet normal {} {} {}",
+            normal.x, normal.y, normal.z
+        )?;
+        writeln!(writer, "    outer loop")?;
+        for &vertex in &triangle {
+            let (x, y, z) = data.vertices[vertex].position;
+            writeln!(writer, "      vertex {} {} {}", -x, y, z)?;
+        }
+        writeln!(writer, "    endloop")?;
+        writeln!(writer, "  endfacet")?;
+    }
+    writeln!(writer, "endsolid {}", name)
+}
+
+/// Binary STL: an 80 byte header, the triangle count, then per triangle its
+/// normal and corners as little endian floats and two unused bytes
+pub fn write_stl_binary<W: Write>(writer: &mut W, data: &MeshData) -> io::Result<()> {
+    let mut header = [0u8; 80];
+    let title = b"binary STL written by opengl-window";
+    header[..title.len()].copy_from_slice(title);
+    writer.write_all(&header)?;
+    writer.write_all(&(data.triangle_count() as u32).to_le_bytes())?;
+
+    for triangle in mirrored_triangles(data) {
+        let normal = face_normal(data, triangle);
+        let mut floats = vec![normal.x, normal.y, normal.z];
+        for &vertex in &triangle {
+            let (x, y, z) = data.vertices[vertex].position;
+            floats.extend_from_slice(&[-x, y, z]);
+        }
+        for float in floats {
+            writer.write_all(&float.to_le_bytes())?;
+        }
+        writer.write_all(&[0, 0])?;
+    }
+    Ok(())
+}
+
+/// PLY with positions, normals and texture coordinates, binary little endian or
+/// ASCII. Vertex colors other than white are written as sRGB bytes.
+pub fn write_ply<W: Write>(writer: &mut W, data: &MeshData, binary: bool) -> io::Result<()> {
+    writeln!(writer, "ply")?;
+    if binary {
+        writeln!(writer, "format binary_little_endian 1.0")?;
+    } else {
+        writeln!(writer, "format ascii 1.0")?;
+    }
+    writeln!(writer, "comment opengl-window")?;
+    writeln!(writer, "element vertex {}", data.vertex_count())?;
+    for property in &["x", "y", "z", "nx", "ny", "nz", "s", "t"] {
+        writeln!(writer, "property float {}", property)?;
+    }
+    let colors = data.colors.iter().any(|&c| c != VertexColor::default());
+    if colors {
+        for property in &["red", "green", "blue", "alpha"] {
+            writeln!(writer, "property uchar {}", property)?;
+        }
+    }
+    writeln!(writer, "element face {}", data.triangle_count())?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    for i in 0..data.vertex_count() {
+        let (x, y, z) = data.vertices[i].position;
+        let (nx, ny, nz) = data.normals.get(i).map_or((0.0, 0.0, 0.0), |n| n.normal);
+        let (s, t) = data.tex_coords.get(i).map_or((0.0, 0.0), |t| t.tex_coords);
+        let values = [-x, y, z, -nx, ny, nz, s, 1.0 - t];
+        if binary {
+            for value in &values {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        } else {
+            let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+            write!(writer, "{}", values.join(" "))?;
+        }
+        if colors {
+            let (r, g, b, a) = data.colors.get(i).copied().unwrap_or_default().color;
+            let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            let bytes = [
+                to_byte(linear_to_srgb(r)),
+                to_byte(linear_to_srgb(g)),
+                to_byte(linear_to_srgb(b)),
+                to_byte(a),
+            ];
+            if binary {
+                writer.write_all(&bytes)?;
+            } else {
+                write!(
+                    writer,
+                    " {} {} {} {}",
+                    bytes[0], bytes[1], bytes[2], bytes[3]
+                )?;
+            }
+        }
+        if !binary {
+            writeln!(writer)?;
+        }
+    }
+
+    for triangle in mirrored_triangles(data) {
+        if binary {
+            writer.write_all(&[3])?;
+            for &vertex in &triangle {
+                writer.write_all(&(vertex as u32).to_le_bytes())?;
+            }
+        } else {
+            writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+        }
+    }
+    Ok(())
+}
+
+/// The triangles with reversed winding, which mirroring the positions turns back
+fn mirrored_triangles(data: &MeshData) -> impl Iterator<Item = [usize; 3]> + '_ {
+    data.triangles().map(|[a, b, c]| [a, c, b])
+}
+
+/// Unit normal of a mirrored triangle in the exported coordinates
+fn face_normal(data: &MeshData, [a, b, c]: [usize; 3]) -> Vector3 {
+    let position = |i: usize| {
+        let (x, y, z) = data.vertices[i].position;
+        Vector3::new(-x, y, z)
+    };
+    let a = position(a);
+    (position(b) - a)
+        .cross(&(position(c) - a))
+        .try_normalize(NORM_EPS)
+        .unwrap_or_else(Vector3::zeros)
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |err| Error::Asset(format!("{}: {}", path.display(), err).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh_data::tests::cube;
+    use crate::mesh_import::{read_ply, read_stl};
+    use crate::{TexCoord, PI};
+
+    /// Positive when the triangles face outwards
+    fn signed_volume(data: &MeshData) -> f32 {
+        data.triangles()
+            .map(|[a, b, c]| data.position(a).dot(&data.face_normal([a, b, c])) / 6.0)
+            .sum()
+    }
+
+    fn assert_same_surface(read: &MeshData, written: &MeshData) {
+        assert_eq!(read.triangle_count(), written.triangle_count());
+        let (read_stats, written_stats) = (read.stats(), written.stats());
+        assert!((read_stats.area - written_stats.area).abs() < 1e-4);
+        assert!((signed_volume(read) - signed_volume(written)).abs() < 1e-4);
+        assert!(read_stats.is_closed());
+    }
+
+    fn colored_cube() -> MeshData {
+        let mut data = cube();
+        data.smooth_normals(PI);
+        data.tex_coords = (0..8)
+            .map(|i| TexCoord {
+                tex_coords: ((i & 1) as f32, 0.25),
+            })
+            .collect();
+        data.colors = (0..8)
+            .map(|i| VertexColor {
+                color: ((i & 1) as f32, (i >> 1 & 1) as f32, 0.5, 1.0),
+            })
+            .collect();
+        data
+    }
+
+    #[test]
+    fn stl_round_trips() {
+        let data = cube();
+        assert!((signed_volume(&data) - 8.0).abs() < 1e-4);
+        for binary in [false, true] {
+            let mut bytes = Vec::new();
+            write_mesh(&mut bytes, &data, MeshFormat::Stl { binary }).unwrap();
+            let read = read_stl(&bytes).unwrap();
+            // Welded, then split again along the hard edges
+            assert_eq!(read.vertex_count(), 24);
+            assert_same_surface(&read, &data);
+        }
+    }
+
+    #[test]
+    fn ply_round_trips() {
+        let data = colored_cube();
+        for binary in [false, true] {
+            let mut bytes = Vec::new();
+            write_mesh(&mut bytes, &data, MeshFormat::Ply { binary }).unwrap();
+            let read = read_ply(&bytes).unwrap();
+            assert_eq!(read.vertex_count(), 8);
+            assert_same_surface(&read, &data);
+            for vertex in 0..8 {
+                assert_eq!(
+                    read.vertices[vertex].position,
+                    data.vertices[vertex].position
+                );
+                assert_eq!(read.normals[vertex].normal, data.normals[vertex].normal);
+                assert_eq!(
+                    read.tex_coords[vertex].tex_coords,
+                    data.tex_coords[vertex].tex_coords
+                );
+                // Colors go through sRGB bytes
+                let (r, g, b, a) = read.colors[vertex].color;
+                let (er, eg, eb, ea) = data.colors[vertex].color;
+                for (value, expected) in [(r, er), (g, eg), (b, eb), (a, ea)] {
+                    assert!((value - expected).abs() < 0.005);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ply_leaves_out_white_colors() {
+        let mut data = colored_cube();
+        data.colors = vec![VertexColor::default(); 8];
+        let mut bytes = Vec::new();
+        write_ply(&mut bytes, &data, false).unwrap();
+        assert!(!String::from_utf8(bytes.clone()).unwrap().contains("red"));
+        assert!(read_ply(&bytes).unwrap().colors.is_empty());
+    }
+
+    fn obj_faces(data: &MeshData) -> Vec<String> {
+        let mut bytes = Vec::new();
+        write_obj(&mut bytes, data, None).unwrap();
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .filter(|line| line.starts_with("f "))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn obj_faces_name_the_attributes_written() {
+        let mut data = cube();
+        data.indices.truncate(3);
+        assert_eq!(obj_faces(&data), ["f 1/1/1 7/7/7 5/5/5"]);
+
+        data.normals.clear();
+        assert_eq!(obj_faces(&data), ["f 1/1 7/7 5/5"]);
+
+        data.tex_coords.clear();
+        assert_eq!(obj_faces(&data), ["f 1 7 5"]);
+
+        data = cube();
+        data.indices.truncate(3);
+        data.tex_coords.clear();
+        assert_eq!(obj_faces(&data), ["f 1//1 7//7 5//5"]);
+
+        let mut bytes = Vec::new();
+        write_obj(&mut bytes, &data, Some(("cube.mtl", "cube"))).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.contains("mtllib cube.mtl\n"));
+        assert!(text.contains("usemtl cube\n"));
+        assert_eq!(text.matches("\nv ").count(), 8);
+        assert_eq!(text.matches("\nvt ").count(), 0);
+        assert_eq!(text.matches("\nvn ").count(), 8);
+    }
+}
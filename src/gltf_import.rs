@@ -0,0 +1,554 @@
+use crate::error::allocation;
+use crate::scene::{MeshSource, Node, Scene};
+use crate::scene_object::Transform;
+use crate::{
+    BlendMode, Camera, Color, Display, Error, Light, Material, Mesh, MeshData, Normal, PbrMaterial,
+    SceneObject, Tangent, TexCoord, UnitQuaternion, Vector3, Vertex, FORWARD, PI, WHITE,
+};
+use glium::texture::{RawImage2d, SrgbTexture2d, Texture2d};
+use gltf::khr_lights_punctual::Kind;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file could not be read or parsed, or a buffer or image it references is missing
+    Gltf(gltf::Error),
+    /// The file has no scene to instantiate
+    NoScene,
+    /// The requested mesh index is not in the file
+    NoMesh { mesh: usize },
+    /// A mesh primitive has no POSITION attribute
+    MissingPositions { mesh: usize },
+    /// Uploading the meshes or textures failed
+    Renderer(Error),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Gltf(err) => write!(f, "glTF error: {}", err),
+            ImportError::NoScene => write!(f, "glTF file contains no scene"),
+            ImportError::NoMesh { mesh } => write!(f, "glTF file has no mesh {}", mesh),
+            ImportError::MissingPositions { mesh } => {
+                write!(f, "mesh {} has a primitive without positions", mesh)
+            }
+            ImportError::Renderer(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Gltf(err) => Some(err),
+            ImportError::Renderer(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<gltf::Error> for ImportError {
+    fn from(err: gltf::Error) -> Self {
+        ImportError::Gltf(err)
+    }
+}
+
+impl From<Error> for ImportError {
+    fn from(err: Error) -> Self {
+        ImportError::Renderer(err)
+    }
+}
+
+/// Loads the default scene of a .gltf or .glb file. External buffers and
+/// images are resolved relative to the file, embedded ones are decoded.
+pub fn import_gltf<P: AsRef<Path>>(display: &Display, path: P) -> Result<Scene, ImportError> {
+    let (document, buffers, images) = gltf::import(path.as_ref())?;
+    build_scene(display, &document, &buffers, &images, Some(path.as_ref()))
+}
+
+/// Loads the default scene of a .glb file or a .gltf file without external references
+pub fn import_gltf_slice(display: &Display, bytes: &[u8]) -> Result<Scene, ImportError> {
+    let (document, buffers, images) = gltf::import_slice(bytes)?;
+    build_scene(display, &document, &buffers, &images, None)
+}
+
+/// Loads a single mesh of a glTF file, one object per primitive, with its materials
+pub fn import_gltf_mesh<P: AsRef<Path>>(
+    display: &Display,
+    path: P,
+    mesh: usize,
+) -> Result<Vec<SceneObject>, ImportError> {
+    let (document, buffers, images) = gltf::import(path)?;
+    let gltf_mesh = document
+        .meshes()
+        .nth(mesh)
+        .ok_or(ImportError::NoMesh { mesh })?;
+
+    let importer = Importer::new(display, &document, &images)?;
+    importer.upload(&read_mesh(&gltf_mesh, &buffers)?)
+}
+
+// glTF is right-handed, while the view matrix of `Camera` mirrors the X axis.
+// Imported geometry and node transforms are mirrored along X so the scene shows
+// the way other viewers do, and counter-clockwise front faces survive `CullClockwise`.
+
+/// Mirrors a glTF node transform along X: conjugates it with diag(-1, 1, 1)
+fn mirror_transform(translation: [f32; 3], rotation: [f32; 4], scale: [f32; 3]) -> Transform {
+    let [tx, ty, tz] = translation;
+    let [x, y, z, w] = rotation;
+    Transform::new(
+        Vector3::new(-tx, ty, tz),
+        UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(w, x, -y, -z)),
+        scale.into(),
+    )
+}
+
+/// Geometry of one mesh primitive as read from the buffers
+struct Primitive {
+    data: MeshData,
+    material: Option<usize>,
+}
+
+struct Importer<'a> {
+    display: &'a Display,
+    source: Option<&'a Path>,
+    images: &'a [gltf::image::Data],
+    textures: Vec<Option<Rc<Texture2d>>>,
+    srgb_textures: Vec<Option<Rc<SrgbTexture2d>>>,
+    materials: Vec<PbrMaterial>,
+    /// Uploaded primitives of every mesh, cloned for each node using the mesh
+    meshes: Vec<Vec<SceneObject>>,
+}
+
+fn build_scene(
+    display: &Display,
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+    images: &[gltf::image::Data],
+    source: Option<&Path>,
+) -> Result<Scene, ImportError> {
+    let mut importer = Importer::new(display, document, images)?;
+    importer.source = source;
+
+    for mesh in document.meshes() {
+        let objects = importer.upload(&read_mesh(&mesh, buffers)?)?;
+        importer.meshes.push(objects);
+    }
+
+    let gltf_scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or(ImportError::NoScene)?;
+
+    let mut scene = Scene::new();
+    for node in gltf_scene.nodes() {
+        importer.add_node(&mut scene, &node, None)?;
+    }
+    scene.update_transforms();
+
+    Ok(scene)
+}
+
+impl<'a> Importer<'a> {
+    /// Prepares the materials, textures are uploaded as materials reference them
+    fn new(
+        display: &'a Display,
+        document: &gltf::Document,
+        images: &'a [gltf::image::Data],
+    ) -> Result<Self, ImportError> {
+        let mut importer = Importer {
+            display,
+            source: None,
+            images,
+            textures: vec![None; images.len()],
+            srgb_textures: vec![None; images.len()],
+            materials: Vec::new(),
+            meshes: Vec::new(),
+        };
+
+        for material in document.materials() {
+            let material = importer.material(&material)?;
+            importer.materials.push(material);
+        }
+
+        Ok(importer)
+    }
+
+    fn add_node(
+        &self,
+        scene: &mut Scene,
+        node: &gltf::Node,
+        parent: Option<usize>,
+    ) -> Result<(), ImportError> {
+        let (translation, rotation, scale) = node.transform().decomposed();
+
+        let mut scene_node = Node {
+            name: node.name().map(String::from),
+            transform: mirror_transform(translation, rotation, scale),
+            ..Default::default()
+        };
+
+        if let Some(mesh) = node.mesh() {
+            for object in self.meshes[mesh.index()].iter().cloned() {
+                scene_node.objects.push(scene.objects.len());
+                scene.objects.push(Box::new(object));
+            }
+            scene_node.mesh = self.source.map(|path| MeshSource::Gltf {
+                path: path.to_path_buf(),
+                mesh: Some(mesh.index()),
+            });
+        }
+
+        if let Some(camera) = node.camera() {
+            if let Some(camera) = import_camera(&camera) {
+                scene_node.camera = Some(scene.cameras.len());
+                scene.cameras.push(camera);
+            }
+        }
+
+        if let Some(light) = node.light() {
+            scene_node.light = Some(scene.lights.len());
+            scene.lights.push(import_light(&light)?);
+        }
+
+        let index = scene.add_node(scene_node, parent);
+        for child in node.children() {
+            self.add_node(scene, &child, Some(index))?;
+        }
+        Ok(())
+    }
+
+    /// Uploads the primitives of a mesh, one object each, or several for
+    /// primitives with more vertices than a mesh can have
+    fn upload(&self, primitives: &[Primitive]) -> Result<Vec<SceneObject>, ImportError> {
+        let mut objects = Vec::new();
+        for primitive in primitives {
+            let material = primitive
+                .material
+                .map(|i| self.materials[i].clone())
+                // The default material of the glTF spec
+                .unwrap_or_else(|| PbrMaterial::new(WHITE, 1.0, 1.0));
+            for part in primitive.data.split() {
+                let mesh = Mesh::from_data(self.display, &part)?;
+                let mut object = SceneObject::from_mesh(Rc::new(mesh));
+                object.set_material(Material::Pbr(material.clone()));
+                objects.push(object);
+            }
+        }
+        Ok(objects)
+    }
+
+    fn material(&mut self, material: &gltf::Material) -> Result<PbrMaterial, ImportError> {
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, a] = pbr.base_color_factor();
+        let [er, eg, eb] = material.emissive_factor();
+
+        // glTF color factors are linear, our colors are sRGB
+        let mut result = PbrMaterial::new(
+            Color::from_linear(Color::rgba(r, g, b, a)),
+            pbr.metallic_factor(),
+            pbr.roughness_factor(),
+        );
+        result.emissive = Color::from_linear(Color::new(er, eg, eb));
+        // Masked materials are drawn opaque, there is no alpha testing
+        if material.alpha_mode() == gltf::material::AlphaMode::Blend {
+            result.blend = BlendMode::Alpha;
+        }
+        result.base_color_texture = pbr
+            .base_color_texture()
+            .map(|info| self.srgb_texture(info.texture().source().index()))
+            .transpose()?;
+        result.emissive_texture = material
+            .emissive_texture()
+            .map(|info| self.srgb_texture(info.texture().source().index()))
+            .transpose()?;
+        result.metallic_roughness_texture = pbr
+            .metallic_roughness_texture()
+            .map(|info| self.texture(info.texture().source().index()))
+            .transpose()?;
+
+        if let Some(normal) = material.normal_texture() {
+            result.normal_scale = normal.scale();
+            result.normal_texture = Some(self.texture(normal.texture().source().index())?);
+        }
+        if let Some(occlusion) = material.occlusion_texture() {
+            result.ambient_occlusion = occlusion.strength();
+            result.occlusion_texture = Some(self.texture(occlusion.texture().source().index())?);
+        }
+
+        Ok(result)
+    }
+
+    /// Linear texture for data maps, uploaded once per image
+    fn texture(&mut self, image: usize) -> Result<Rc<Texture2d>, Error> {
+        if let Some(texture) = &self.textures[image] {
+            return Ok(Rc::clone(texture));
+        }
+        let texture = Texture2d::new(self.display, raw_image(&self.images[image]))
+            .map_err(allocation("texture"))?;
+        let texture = Rc::new(texture);
+        self.textures[image] = Some(Rc::clone(&texture));
+        Ok(texture)
+    }
+
+    /// sRGB texture for color maps, uploaded once per image
+    fn srgb_texture(&mut self, image: usize) -> Result<Rc<SrgbTexture2d>, Error> {
+        if let Some(texture) = &self.srgb_textures[image] {
+            return Ok(Rc::clone(texture));
+        }
+        let texture = SrgbTexture2d::new(self.display, raw_image(&self.images[image]))
+            .map_err(allocation("texture"))?;
+        let texture = Rc::new(texture);
+        self.srgb_textures[image] = Some(Rc::clone(&texture));
+        Ok(texture)
+    }
+}
+
+/// Reads the triangle primitives of a mesh. Points and lines are skipped.
+fn read_mesh(
+    mesh: &gltf::Mesh,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Vec<Primitive>, ImportError> {
+    let mut primitives = Vec::new();
+
+    for primitive in mesh.primitives() {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            continue;
+        }
+
+        let reader =
+            primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| data.0.as_slice()));
+
+        let positions: Vec<[f32; 3]> = reader
+            .read_positions()
+            .ok_or(ImportError::MissingPositions { mesh: mesh.index() })?
+            .collect();
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        let normals = reader.read_normals().map(|normals| {
+            normals
+                .map(|[x, y, z]| Normal { normal: (x, y, z) })
+                .collect()
+        });
+
+        let tex_coords: Option<Vec<TexCoord>> = reader.read_tex_coords(0).map(|uv| {
+            uv.into_f32()
+                .map(|[u, v]| TexCoord { tex_coords: (u, v) })
+                .collect()
+        });
+
+        let tangents = reader.read_tangents().map(|tangents| {
+            tangents
+                .map(|[x, y, z, w]| Tangent {
+                    tangent: (x, y, z, w),
+                })
+                .collect()
+        });
+
+        let (has_normals, has_tex_coords) = (normals.is_some(), tex_coords.is_some());
+        let mut data = MeshData::new(
+            positions
+                .iter()
+                .map(|&[x, y, z]| Vertex {
+                    position: (x, y, z),
+                })
+                .collect(),
+            normals.unwrap_or_default(),
+            indices,
+        );
+        if let Some(tex_coords) = tex_coords {
+            data.tex_coords = tex_coords;
+        }
+        if !has_normals {
+            data.smooth_normals(PI);
+        }
+        match tangents {
+            Some(tangents) => data.tangents = tangents,
+            None if has_tex_coords => data.generate_tangents(),
+            None => (),
+        }
+
+        // Mirror along X, see `mirror_transform`. Flips the bitangent handedness as well.
+        for vertex in data.vertices.iter_mut() {
+            vertex.position.0 = -vertex.position.0;
+        }
+        for normal in data.normals.iter_mut() {
+            normal.normal.0 = -normal.normal.0;
+        }
+        for tangent in data.tangents.iter_mut() {
+            tangent.tangent.0 = -tangent.tangent.0;
+            tangent.tangent.3 = -tangent.tangent.3;
+        }
+        primitives.push(Primitive {
+            data,
+            material: primitive.material().index(),
+        });
+    }
+
+    Ok(primitives)
+}
+
+/// Perspective cameras only, `Camera` has no orthographic projection
+fn import_camera(camera: &gltf::Camera) -> Option<Camera> {
+    match camera.projection() {
+        gltf::camera::Projection::Perspective(perspective) => {
+            // The resolution only matters for the aspect ratio until the first frame updates it
+            let aspect_ratio = perspective.aspect_ratio().unwrap_or(4.0 / 3.0);
+            let resolution = ((1000.0 * aspect_ratio) as u32, 1000);
+            let fov = perspective.yfov().to_degrees().round() as u32;
+
+            let mut result = Camera::new(Vector3::zeros(), FORWARD, resolution, fov).ok()?;
+            result.set_clipping_planes(perspective.znear(), perspective.zfar().unwrap_or(1024.0));
+            Some(result)
+        }
+        gltf::camera::Projection::Orthographic(_) => None,
+    }
+}
+
+/// The intensity is taken over as is: lux for directional, candela for point and spot lights
+fn import_light(light: &gltf::khr_lights_punctual::Light) -> Result<Light, Error> {
+    let range = light.range().unwrap_or(1024.0);
+    let result = match light.kind() {
+        Kind::Directional => Light::directional(FORWARD)?,
+        Kind::Point => Light::point(Vector3::zeros(), range),
+        Kind::Spot {
+            outer_cone_angle, ..
+        } => Light::spot(Vector3::zeros(), FORWARD, outer_cone_angle, range)?,
+    };
+
+    let [r, g, b] = light.color();
+    Ok(result
+        .with_color(Color::from_linear(Color::new(r, g, b)))
+        .with_intensity(light.intensity()))
+}
+
+/// Converts any 8 or 16 bit image to the RGBA8 layout glium uploads.
+/// Rows stay top to bottom, which matches glTF texture coordinates as they are.
+fn raw_image(image: &gltf::image::Data) -> RawImage2d<'static, u8> {
+    use gltf::image::Format;
+
+    let (channels, bytes_per_channel) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 | Format::B8G8R8 => (3, 1),
+        Format::R8G8B8A8 | Format::B8G8R8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+    };
+    let bgr = matches!(image.format, Format::B8G8R8 | Format::B8G8R8A8);
+
+    let mut rgba = Vec::with_capacity((image.width * image.height * 4) as usize);
+    for pixel in image.pixels.chunks_exact(channels * bytes_per_channel) {
+        // Keep the most significant byte of 16 bit channels, they are stored little endian
+        let channel = |i: usize| pixel[i * bytes_per_channel + bytes_per_channel - 1];
+        let (r, g, b, a) = match channels {
+            1 => (channel(0), channel(0), channel(0), 255),
+            2 => (channel(0), channel(1), 0, 255),
+            3 => (channel(0), channel(1), channel(2), 255),
+            _ => (channel(0), channel(1), channel(2), channel(3)),
+        };
+        let (r, b) = if bgr { (b, r) } else { (r, b) };
+        rgba.extend_from_slice(&[r, g, b, a]);
+    }
+
+    RawImage2d::from_raw_rgba(rgba, (image.width, image.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A red triangle under a translated parent, a camera and a directional light
+    const TRIANGLE: &[u8] = include_bytes!("../examples/triangle.gltf");
+    /// The same scene as a binary file
+    const TRIANGLE_GLB: &[u8] = include_bytes!("../examples/triangle.glb");
+    /// The same scene with its buffer in triangle.bin next to it
+    const TRIANGLE_EXTERNAL: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/examples/triangle_external.gltf"
+    );
+
+    fn check_triangle(document: &gltf::Document, buffers: &[gltf::buffer::Data]) {
+        let mesh = document.meshes().next().unwrap();
+        let primitives = read_mesh(&mesh, buffers).unwrap();
+
+        assert_eq!(primitives.len(), 1);
+        let primitive = &primitives[0];
+        assert_eq!(primitive.material, Some(0));
+        assert_eq!(primitive.data.indices, vec![0, 1, 2]);
+        assert_eq!(primitive.data.vertices[1].position, (-1.0, 0.0, 0.0));
+        assert_eq!(primitive.data.vertices[2].position, (0.0, 1.0, 0.0));
+        // Computed from the winding, the file has no normals
+        for normal in &primitive.data.normals {
+            let (x, y, z) = normal.normal;
+            assert_eq!((x.abs(), y, z), (0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn reads_mesh_mirrored_along_x() {
+        let (document, buffers, _) = gltf::import_slice(TRIANGLE).unwrap();
+        check_triangle(&document, &buffers);
+    }
+
+    #[test]
+    fn reads_binary_files() {
+        let (document, buffers, _) = gltf::import_slice(TRIANGLE_GLB).unwrap();
+        check_triangle(&document, &buffers);
+    }
+
+    #[test]
+    fn reads_buffers_next_to_the_file() {
+        let (document, buffers, _) = gltf::import(TRIANGLE_EXTERNAL).unwrap();
+        check_triangle(&document, &buffers);
+
+        // Without the file's directory the buffer can't be found
+        let text = std::fs::read(TRIANGLE_EXTERNAL).unwrap();
+        let err = ImportError::from(gltf::import_slice(&text).unwrap_err());
+        assert!(matches!(err, ImportError::Gltf(_)));
+    }
+
+    #[test]
+    fn reads_nodes_camera_and_light() {
+        let (document, ..) = gltf::import_slice(TRIANGLE).unwrap();
+        let scene = document.default_scene().unwrap();
+        let names: Vec<_> = scene.nodes().map(|node| node.name()).collect();
+        assert_eq!(names, [Some("root"), Some("camera"), Some("sun")]);
+
+        let root = scene.nodes().next().unwrap();
+        let child = root.children().next().unwrap();
+        assert_eq!(child.name(), Some("triangle"));
+        let (translation, rotation, scale) = root.transform().decomposed();
+        let transform = mirror_transform(translation, rotation, scale);
+        assert_eq!(transform.position, Vector3::new(-1.0, 2.0, 3.0));
+
+        let camera = document.cameras().next().unwrap();
+        let camera = import_camera(&camera).unwrap();
+        assert_eq!(camera.fov_degrees(), 60);
+        assert_eq!((camera.near(), camera.far()), (0.1, 100.0));
+        assert!((camera.aspect_ratio() - 1.5).abs() < 1e-3);
+
+        let light = document.lights().unwrap().next().unwrap();
+        let light = import_light(&light).unwrap();
+        assert!(matches!(light.kind, crate::LightKind::Directional));
+        assert_eq!(light.intensity, 3.0);
+        assert_eq!(light.color, Color::from_linear(Color::new(1.0, 0.5, 0.25)));
+    }
+
+    #[test]
+    fn reports_invalid_files() {
+        // The glTF crate validates the document before we see it
+        let text = std::str::from_utf8(TRIANGLE).unwrap();
+        let broken = text.replace("\"POSITION\": 0", "\"NORMAL\": 0");
+        let err = ImportError::from(gltf::import_slice(broken.as_bytes()).unwrap_err());
+        assert!(matches!(err, ImportError::Gltf(_)));
+        assert!(err.to_string().starts_with("glTF error"));
+    }
+}
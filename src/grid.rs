@@ -0,0 +1,106 @@
+use crate::post::fullscreen_program;
+use crate::{
+    uniform, Camera, Color, Display, DrawParameters, Error, Matrix4, Program, Scalar, Surface,
+    BLUE, RED,
+};
+use glium::draw_parameters::{Blend, DepthTest};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::vertex::EmptyVertexAttributes;
+
+#[derive(Copy, Clone, Debug)]
+pub struct GridSettings {
+    /// Height of the XZ plane the grid lies on
+    pub height: Scalar,
+    /// Distance between the minor lines
+    pub spacing: Scalar,
+    /// Every how many minor lines a major one is drawn
+    pub major_every: u32,
+    /// Distance from the camera at which the grid has faded out completely
+    pub fade_distance: Scalar,
+    pub minor_color: Color,
+    pub major_color: Color,
+    /// Line along `RIGHT` through the origin
+    pub x_axis_color: Color,
+    /// Line along `FORWARD` through the origin
+    pub z_axis_color: Color,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            height: 0.0,
+            spacing: 0.1,
+            major_every: 10,
+            fade_distance: 20.0,
+            minor_color: Color::rgba(0.5, 0.5, 0.5, 0.25),
+            major_color: Color::rgba(0.75, 0.75, 0.75, 0.5),
+            x_axis_color: RED,
+            z_axis_color: BLUE,
+        }
+    }
+}
+
+/// Editor style ground grid, endless in all directions and fading with distance
+pub struct Grid {
+    pub settings: GridSettings,
+    program: Program,
+}
+
+impl Grid {
+    pub fn new(display: &Display, settings: GridSettings) -> Result<Self, Error> {
+        Ok(Self {
+            settings,
+            program: fullscreen_program(
+                display,
+                include_str!(r"shaders/grid_fragment.glsl"),
+                "grid",
+            )?,
+        })
+    }
+
+    /// Blends the grid over `frame`, hidden by the geometry already in its depth buffer
+    pub fn render<S: Surface>(&self, frame: &mut S, camera: &Camera) -> Result<(), Error> {
+        let settings = &self.settings;
+        let view_perspective = Matrix4::from(camera.get_perspective_matrix())
+            * Matrix4::from(camera.get_view_matrix());
+        let inverse_view_perspective: [[f32; 4]; 4] = view_perspective
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .into();
+        let view_perspective: [[f32; 4]; 4] = view_perspective.into();
+        let camera_position: [f32; 3] = camera.position().into();
+        let linear = |color: Color| <[f32; 4]>::from(color.to_linear());
+
+        let uniforms = uniform! {
+            view_perspective: view_perspective,
+            inverse_view_perspective: inverse_view_perspective,
+            camera_position: camera_position,
+            height: settings.height,
+            spacing: settings.spacing,
+            major_every: settings.major_every.max(1) as f32,
+            fade_distance: settings.fade_distance,
+            minor_color: linear(settings.minor_color),
+            major_color: linear(settings.major_color),
+            x_axis_color: linear(settings.x_axis_color),
+            z_axis_color: linear(settings.z_axis_color),
+        };
+        let params = DrawParameters {
+            depth: glium::Depth {
+                test: DepthTest::IfLess,
+                write: false,
+                ..Default::default()
+            },
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        frame.draw(
+            EmptyVertexAttributes { len: 3 },
+            NoIndices(PrimitiveType::TrianglesList),
+            &self.program,
+            &uniforms,
+            &params,
+        )?;
+        Ok(())
+    }
+}
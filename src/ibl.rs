@@ -0,0 +1,232 @@
+use crate::error::{allocation, shader};
+use crate::material::FallbackTextures;
+use crate::skybox::{cube_face_program, load_equirectangular, render_cube_faces};
+use crate::{paste_after_version, uniform, Display, Error, Matrix4, Program, Scalar, Sky, Surface};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::{Cubemap, MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{
+    MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior, SamplerWrapFunction, UniformValue,
+    Uniforms,
+};
+use glium::vertex::EmptyVertexAttributes;
+use std::path::Path;
+use std::rc::Rc;
+
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+/// Mipmap levels of the prefiltered map below the top one, roughness 1 maps to the last
+const PREFILTERED_LEVELS: u32 = 4;
+const BRDF_LUT_SIZE: u32 = 256;
+const SKYBOX_SIZE: u32 = 512;
+
+/// Precomputed image based lighting: the diffuse irradiance, the specular
+/// reflections for increasing roughness and the split sum BRDF lookup table.
+pub struct Environment {
+    /// The environment itself, to draw it as the sky
+    pub skybox: Rc<Cubemap>,
+    pub irradiance: Cubemap,
+    /// Mipmap `i` holds the reflections for roughness `i / PREFILTERED_LEVELS`
+    pub prefiltered: Cubemap,
+    pub brdf_lut: Texture2d,
+    pub intensity: Scalar,
+}
+
+impl Environment {
+    /// Lights the scene with an equirectangular Radiance `.hdr` panorama
+    pub fn load<P: AsRef<Path>>(display: &Display, path: P) -> Result<Self, Error> {
+        let skybox = load_equirectangular(display, path, SKYBOX_SIZE)?;
+        Self::from_cubemap(display, Rc::new(skybox))
+    }
+
+    /// Convolves the cubemap on the GPU, this takes a moment for large cubemaps
+    pub fn from_cubemap(display: &Display, skybox: Rc<Cubemap>) -> Result<Self, Error> {
+        let environment = skybox
+            .sampled()
+            .minify_filter(MinifySamplerFilter::Linear)
+            .magnify_filter(MagnifySamplerFilter::Linear);
+
+        // Diffuse irradiance
+        let irradiance = Cubemap::empty_with_format(
+            display,
+            UncompressedFloatFormat::F16F16F16,
+            MipmapsOption::NoMipmap,
+            IRRADIANCE_SIZE,
+        )
+        .map_err(allocation("irradiance map"))?;
+        let program = cube_face_program(
+            display,
+            include_str!(r"shaders/irradiance_fragment.glsl"),
+            "irradiance convolution",
+        )?;
+        render_cube_faces(display, &irradiance, 0, &program, |face| {
+            uniform! {
+                environment: environment,
+                face: face,
+            }
+        })?;
+
+        // Specular reflections, one mipmap level per roughness step
+        let prefiltered = Cubemap::empty_with_format(
+            display,
+            UncompressedFloatFormat::F16F16F16,
+            MipmapsOption::EmptyMipmapsMax(PREFILTERED_LEVELS),
+            PREFILTERED_SIZE,
+        )
+        .map_err(allocation("prefiltered environment map"))?;
+        let program = cube_face_program(
+            display,
+            &with_importance_sampling(include_str!(r"shaders/prefilter_fragment.glsl")),
+            "environment prefiltering",
+        )?;
+        for level in 0..=PREFILTERED_LEVELS {
+            let roughness = level as f32 / PREFILTERED_LEVELS as f32;
+            render_cube_faces(display, &prefiltered, level, &program, |face| {
+                uniform! {
+                    environment: environment,
+                    roughness: roughness,
+                    face: face,
+                }
+            })?;
+        }
+
+        Ok(Self {
+            skybox,
+            irradiance,
+            prefiltered,
+            brdf_lut: brdf_lut(display)?,
+            intensity: 1.0,
+        })
+    }
+
+    /// The environment as background
+    pub fn sky(&self) -> Sky {
+        Sky::Cubemap {
+            cubemap: Rc::clone(&self.skybox),
+            intensity: self.intensity,
+        }
+    }
+}
+
+/// Integrates the split sum BRDF into a two channel lookup table
+fn brdf_lut(display: &Display) -> Result<Texture2d, Error> {
+    let lut = Texture2d::empty_with_format(
+        display,
+        UncompressedFloatFormat::F16F16,
+        MipmapsOption::NoMipmap,
+        BRDF_LUT_SIZE,
+        BRDF_LUT_SIZE,
+    )
+    .map_err(allocation("BRDF lookup table"))?;
+
+    let program = Program::from_source(
+        display,
+        include_str!(r"shaders/fullscreen_vertex.glsl"),
+        &with_importance_sampling(include_str!(r"shaders/brdf_fragment.glsl")),
+        None,
+    )
+    .map_err(shader("BRDF integration"))?;
+
+    let mut framebuffer = SimpleFrameBuffer::new(display, &lut)
+        .map_err(allocation("BRDF lookup table framebuffer"))?;
+    framebuffer.draw(
+        EmptyVertexAttributes { len: 3 },
+        NoIndices(PrimitiveType::TrianglesList),
+        &program,
+        &glium::uniforms::EmptyUniforms,
+        &Default::default(),
+    )?;
+    Ok(lut)
+}
+
+fn with_importance_sampling(f_shader: &str) -> String {
+    paste_after_version(f_shader, include_str!(r"shaders/importance_sampling.glsl"))
+}
+
+/// Environment uniforms for the main shading pass. Without an environment the
+/// samplers are still bound to `fallback` so no two sampler types share a texture unit.
+#[derive(Copy, Clone)]
+pub struct EnvironmentUniforms<'e> {
+    pub environment: Option<&'e Environment>,
+    /// Rotates view space directions back to world space, where the cubemaps live
+    pub view_to_world: [[f32; 3]; 3],
+    pub fallback: &'e FallbackTextures,
+}
+
+impl<'e> EnvironmentUniforms<'e> {
+    pub fn new(
+        environment: Option<&'e Environment>,
+        view: &Matrix4,
+        fallback: &'e FallbackTextures,
+    ) -> Self {
+        // The inverse of the view rotation is its transpose, so the rows become columns
+        let row = |i: usize| [view[(i, 0)], view[(i, 1)], view[(i, 2)]];
+        Self {
+            environment,
+            view_to_world: [row(0), row(1), row(2)],
+            fallback,
+        }
+    }
+}
+
+impl<'e> Uniforms for EnvironmentUniforms<'e> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let sampler = SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+            ),
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            ..Default::default()
+        };
+
+        let environment = match self.environment {
+            Some(environment) => environment,
+            None => {
+                output("u_has_environment", UniformValue::Bool(false));
+                for name in ["u_irradiance_map", "u_prefiltered_map"].iter() {
+                    output(
+                        name,
+                        UniformValue::Cubemap(&self.fallback.cube, Some(sampler)),
+                    );
+                }
+                output(
+                    "u_brdf_lut",
+                    UniformValue::Texture2d(&self.fallback.white, Some(sampler)),
+                );
+                return;
+            }
+        };
+
+        output("u_has_environment", UniformValue::Bool(true));
+        output("u_view_to_world", UniformValue::Mat3(self.view_to_world));
+        output(
+            "u_environment_intensity",
+            UniformValue::Float(environment.intensity),
+        );
+        output(
+            "u_prefiltered_levels",
+            UniformValue::Float(PREFILTERED_LEVELS as f32),
+        );
+        output(
+            "u_irradiance_map",
+            UniformValue::Cubemap(&environment.irradiance, Some(sampler)),
+        );
+        output(
+            "u_prefiltered_map",
+            UniformValue::Cubemap(
+                &environment.prefiltered,
+                Some(SamplerBehavior {
+                    minify_filter: MinifySamplerFilter::LinearMipmapLinear,
+                    ..sampler
+                }),
+            ),
+        );
+        output(
+            "u_brdf_lut",
+            UniformValue::Texture2d(&environment.brdf_lut, Some(sampler)),
+        );
+    }
+}
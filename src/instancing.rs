@@ -0,0 +1,91 @@
+use crate::error::allocation;
+use crate::{implement_vertex, Display, Error, SceneObject, VertexBuffer};
+use glium::backend::{Context, Facade};
+use glium::vertex::VertexBufferSlice;
+use std::rc::Rc;
+
+/// Per-instance attributes of an instanced draw call
+#[derive(Copy, Clone, Debug)]
+pub struct Instance {
+    pub instance_model: [[f32; 4]; 4],
+    pub instance_color: (f32, f32, f32, f32),
+}
+
+implement_vertex!(Instance, instance_model, instance_color);
+
+impl Instance {
+    pub fn new(object: &SceneObject) -> Self {
+        Self {
+            instance_model: object.get_model_matrix(),
+            instance_color: {
+                let color = object.color.to_linear();
+                (color.r, color.g, color.b, color.a)
+            },
+        }
+    }
+}
+
+/// Objects sharing a mesh (and a material, when asked for) drawn in one call
+pub(crate) struct Batch<'a> {
+    /// The first object of the batch, its mesh and material stand for all of them
+    pub object: &'a SceneObject,
+    pub instances: Vec<Instance>,
+}
+
+/// Groups the objects into batches, keeping the order in which meshes first appear
+pub(crate) fn batches<'a, I>(object_list: I, match_materials: bool) -> Vec<Batch<'a>>
+where
+    I: IntoIterator<Item = &'a Box<SceneObject>>,
+{
+    let mut batches: Vec<Batch> = Vec::new();
+
+    for obj in object_list {
+        let batch = batches.iter_mut().find(|batch| {
+            Rc::ptr_eq(batch.object.drawn_mesh(), obj.drawn_mesh())
+                && batch.object.render_mode == obj.render_mode
+                && (!match_materials || batch.object.material.batches_with(&obj.material))
+        });
+
+        match batch {
+            Some(batch) => batch.instances.push(Instance::new(obj)),
+            None => batches.push(Batch {
+                object: obj,
+                instances: vec![Instance::new(obj)],
+            }),
+        }
+    }
+
+    batches
+}
+
+/// Per-instance vertex buffer reused from frame to frame, grown when a batch doesn't fit
+pub(crate) struct InstanceBuffer {
+    context: Rc<Context>,
+    buffer: VertexBuffer<Instance>,
+}
+
+impl InstanceBuffer {
+    pub fn new(display: &Display) -> Result<Self, Error> {
+        let context = display.get_context().clone();
+        let buffer =
+            VertexBuffer::empty_dynamic(&context, 64).map_err(allocation("instance buffer"))?;
+        Ok(Self { context, buffer })
+    }
+
+    /// Copies the instances to the GPU and returns the part of the buffer holding them
+    pub fn upload(
+        &mut self,
+        instances: &[Instance],
+    ) -> Result<VertexBufferSlice<'_, Instance>, Error> {
+        if instances.len() > self.buffer.len() {
+            self.buffer =
+                VertexBuffer::empty_dynamic(&self.context, instances.len().next_power_of_two())
+                    .map_err(allocation("instance buffer"))?;
+        }
+
+        // The buffer holds at least `instances.len()` elements at this point
+        let slice = self.buffer.slice(0..instances.len()).unwrap();
+        slice.write(instances);
+        Ok(slice)
+    }
+}
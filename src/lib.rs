@@ -1,113 +1,409 @@
+pub mod bezier;
 pub mod camera;
 pub mod common;
+pub mod config;
 pub mod consts;
+pub mod debug;
+pub mod deferred;
+pub mod error;
+pub mod export;
+pub mod gltf_import;
+pub mod grid;
+pub mod ibl;
+pub mod instancing;
+pub mod light;
+pub mod lod;
+pub mod material;
+pub mod mesh;
+pub mod mesh_data;
+pub mod mesh_import;
+pub mod post;
+pub mod render_mode;
+pub mod resources;
+pub mod rgbe;
+pub mod scene;
+pub mod scene_file;
 pub mod scene_object;
+pub mod shadow;
+pub mod simplify;
+pub mod skybox;
+pub mod ssao;
+pub mod subdivide;
 pub mod teapot;
+pub mod tonemap;
 pub mod types;
+pub mod uniforms;
 
+pub use bezier::{tessellate, BezierPatch};
 pub use camera::Camera;
 pub use common::*;
+pub use config::{Culling, Renderer, RendererConfig, WindowConfig, WindowMode};
 pub use consts::*;
+pub use debug::DebugDraw;
+pub use deferred::{DeferredRenderer, GBuffer, RenderPath};
+pub use error::Error;
+pub use export::{export_mesh, export_object, MeshFormat};
 pub use glium::{glutin, Display, DrawParameters, Program, ProgramCreationError, Frame, IndexBuffer, Surface, VertexBuffer};
 pub use glium::{implement_vertex, uniform};
+pub use gltf_import::{import_gltf, import_gltf_slice, ImportError};
 pub use glutin::dpi::LogicalSize;
 pub use glutin::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 pub use glutin::event_loop::{ControlFlow, EventLoop};
 pub use glutin::window::WindowBuilder;
-pub use scene_object::SceneObject;
+pub use grid::{Grid, GridSettings};
+pub use ibl::Environment;
+pub use instancing::Instance;
+pub use light::{Light, LightKind};
+pub use lod::{Lod, LodSettings};
+pub use material::{BlendMode, Material, PbrMaterial, PhongMaterial};
+pub use mesh::Mesh;
+pub use mesh_data::{MeshData, MeshStats, MAX_VERTICES};
+pub use mesh_import::{import_mesh, load_mesh};
+pub use post::{
+    identity_lut, load_cube_lut, Bloom, ChromaticAberration, ColorGrading, PostProcessor,
+    PostSettings, Sharpen, Vignette,
+};
+pub use render_mode::RenderMode;
+pub use resources::{ResourceStats, Resources};
+pub use rgbe::{load_hdr, HdrImage};
+pub use scene::{MeshSource, Node, Scene};
+pub use scene_file::{load_scene, save_scene, SceneDescription, SceneFileError};
+pub use scene_object::{SceneObject, Transform};
+pub use shadow::{ShadowMap, ShadowSettings};
+pub use simplify::{lod_chain, simplify};
+pub use skybox::{equirectangular_to_cubemap, load_cubemap, load_equirectangular, render_sky, Sky};
+pub use ssao::{Ssao, SsaoSettings};
 pub use std::time::{Duration, Instant};
+pub use subdivide::{catmull_clark, loop_subdivide, Corner, PolygonMesh};
+pub use tonemap::{tonemap, HdrTarget, ToneMapping, Tonemapper};
 pub use types::*;
+pub use uniforms::UniformChain;
 
 
+/// Opens a window with the default renderer settings, see `RendererConfig` for the rest
 pub fn setup(
     width: u32,
     height: u32,
     title: &str,
     depth_buffer: u8,
-) -> (Display, EventLoop<()>, Program, DrawParameters) {
-    // Event handling
-    let event_loop = EventLoop::new();
-
-    // Application parameter specifier:
-    let window_builder = WindowBuilder::new()
-        .with_inner_size(LogicalSize::new(width, height))
-        .with_title(title);
-
-    // Open GL context specifier:
-    let context_builder = glutin::ContextBuilder::new()
-        .with_depth_buffer(depth_buffer)
-        .with_vsync(true)
-        .with_multisampling(4);
-
-    // Display object: OpenGL window
-    let display = glium::Display::new(window_builder, context_builder, &event_loop)
-        .expect("Failed to build GLIUM display.");
-
-    // Define the shaders:
-    let program = define_shaders(&display).expect("Failed to compile shaders.");
-
-    // Draw parameters:
-    let draw_params = DrawParameters {
-        backface_culling: glium::draw_parameters::BackfaceCullingMode::CullClockwise,
-        depth: glium::Depth {
-            test: glium::draw_parameters::DepthTest::IfLess,
-            write: true,
-            ..Default::default()
-        },
-        ..Default::default()
-    };
+) -> Result<(Display, EventLoop<()>, Shaders, DrawParameters<'static>), Error> {
+    let renderer = RendererConfig::new(WindowConfig::new(width, height, title))
+        .with_depth_bits(depth_buffer)
+        .build()?;
+
+    Ok((
+        renderer.display,
+        renderer.event_loop,
+        renderer.shaders,
+        renderer.draw_params,
+    ))
+}
+
+/// The shading programs together with the textures they fall back on
+pub struct Shaders {
+    pub phong: Program,
+    pub pbr: Program,
+    /// How objects without a render mode of their own are drawn
+    pub render_mode: RenderMode,
+    pub(crate) modes: render_mode::ModePrograms,
+    pub(crate) fallback: material::FallbackTextures,
+    pub(crate) no_shadows: glium::texture::DepthTexture2d,
+    pub(crate) instances: std::cell::RefCell<instancing::InstanceBuffer>,
+    pub(crate) sky: skybox::SkyPrograms,
+    pub(crate) tonemap: Program,
+}
+
+impl Shaders {
+    pub fn new(display: &Display) -> Result<Self, Error> {
+        Ok(Self {
+            phong: define_shaders(display).map_err(error::shader("Phong"))?,
+            pbr: define_pbr_shaders(display).map_err(error::shader("PBR"))?,
+            render_mode: RenderMode::default(),
+            modes: render_mode::ModePrograms::new(display)?,
+            fallback: material::FallbackTextures::new(display)?,
+            no_shadows: glium::texture::DepthTexture2d::empty(display, 1, 1)
+                .map_err(error::allocation("fallback shadow map"))?,
+            instances: std::cell::RefCell::new(instancing::InstanceBuffer::new(display)?),
+            sky: skybox::SkyPrograms::new(display)?,
+            tonemap: tonemap::define_tonemap_shaders(display)?,
+        })
+    }
+}
+
+/// Pastes the shared lighting and shading functions right after the #version line of a fragment shader
+pub(crate) fn with_lighting(f_shader: &str) -> String {
+    let shaded = paste_after_version(f_shader, include_str!(r"shaders/shading.glsl"));
+    paste_after_version(&shaded, include_str!(r"shaders/lighting.glsl"))
+}
 
-    (display, event_loop, program, draw_params)
+/// Pastes the PBR material uniforms and normal mapping in front of `f_shader`
+pub(crate) fn with_pbr_material(f_shader: &str) -> String {
+    paste_after_version(f_shader, include_str!(r"shaders/pbr_material.glsl"))
+}
+
+/// Inserts shared GLSL code right after the #version line of a shader
+pub(crate) fn paste_after_version(shader: &str, code: &str) -> String {
+    let (version, body) = shader.split_at(shader.find('\n').map_or(0, |i| i + 1));
+    format!("{}\n{}\n{}", version, code, body)
 }
 
 /// Reads the GLSL files as &str and feeds them to glium::Program
 pub fn define_shaders(display: &glium::Display) -> Result<Program, ProgramCreationError> {
     let v_shader = include_str!(r"shaders/vertex_shader.glsl");
-    let f_shader = include_str!(r"shaders/fragment_shader.glsl");
-    Program::from_source(display, &v_shader, &f_shader, None)
+    let f_shader = with_lighting(include_str!(r"shaders/fragment_shader.glsl"));
+    Program::from_source(display, v_shader, &f_shader, None)
+}
+
+/// Reads the metallic/roughness GLSL files as &str and feeds them to glium::Program
+pub fn define_pbr_shaders(display: &glium::Display) -> Result<Program, ProgramCreationError> {
+    let v_shader = include_str!(r"shaders/pbr_vertex_shader.glsl");
+    let f_shader = with_lighting(&with_pbr_material(include_str!(
+        r"shaders/pbr_fragment_shader.glsl"
+    )));
+    Program::from_source(display, v_shader, &f_shader, None)
 }
 
 /// Initalize a glium Frame object with a specified background Color
 pub fn init_frame(display: &Display, color: Color) -> Frame {
     let mut frame = display.draw();
+    let color = color.to_linear();
     frame.clear_color_and_depth((color.r, color.g, color.b, 1.0), 1.0);
     frame
 }
 
-pub fn render(
-    frame: &mut Frame,
+/// The results of earlier passes `render` and `DeferredRenderer::render` can
+/// use, all left out by default
+#[derive(Copy, Clone, Default)]
+pub struct RenderOptions<'a> {
+    /// Shadows of the (first) light, updated for the same camera
+    pub shadow_map: Option<&'a ShadowMap>,
+    /// Image based lighting
+    pub environment: Option<&'a Environment>,
+    /// Ambient occlusion, updated for the same camera
+    pub ssao: Option<&'a Ssao>,
+}
+
+impl<'a> RenderOptions<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_shadows(mut self, shadow_map: &'a ShadowMap) -> Self {
+        self.shadow_map = Some(shadow_map);
+        self
+    }
+
+    pub fn with_environment(mut self, environment: &'a Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    pub fn with_ssao(mut self, ssao: &'a Ssao) -> Self {
+        self.ssao = Some(ssao);
+        self
+    }
+}
+
+/// Draws the opaque objects, then the transparent ones sorted back to front
+pub fn render<S: Surface>(
+    frame: &mut S,
     object_list: &[Box<SceneObject>],
     camera: &Camera,
-    program: &Program,
+    light: &Light,
+    options: RenderOptions,
+    shaders: &Shaders,
     draw_params: &DrawParameters,
-) {
-    // Camera stuff:
-    let perspective = camera.get_perspective_matrix();
-    let view = camera.get_view_matrix();
-
-    // Light stuff:
-    let light = [-1.0, 0.4, 0.9f32];
-
-    // Object dependent stuff:
-    for obj in object_list {
-        let model = obj.get_model_matrix();
-
-        let uniforms = glium::uniform! {
-            model: model,
-            view: view,
-            perspective: perspective,
-            u_light: light
-        };
+) -> Result<(), Error> {
+    lod::select_levels(object_list, camera);
+    let lighting = lighting_uniforms(camera, light, options, shaders);
 
-        frame
-            .draw(
-                (&obj.vertex_bfr, &obj.normal_bfr),
-                &obj.ind_bfr,
-                program,
-                &uniforms,
+    // Objects sharing a mesh and material are drawn in one instanced call:
+    {
+        let opaque = object_list
+            .iter()
+            .filter(|object| !object.material.is_transparent());
+        let mut instance_buffer = shaders.instances.borrow_mut();
+        for batch in instancing::batches(opaque, true) {
+            let instances = instance_buffer.upload(&batch.instances)?;
+            draw_object(
+                frame,
+                batch.object,
+                instances,
+                camera,
+                lighting,
+                shaders,
                 draw_params,
-            )
-            .expect("Failed to draw object");
+            )?;
+        }
     }
+
+    render_transparent(frame, object_list, camera, lighting, shaders, draw_params)
 }
 
+/// The light, shadow, environment and ambient occlusion uniforms of the shading programs
+pub(crate) fn lighting_uniforms<'a>(
+    camera: &Camera,
+    light: &Light,
+    options: RenderOptions<'a>,
+    shaders: &'a Shaders,
+) -> impl glium::uniforms::Uniforms + Copy + 'a {
+    let RenderOptions {
+        shadow_map,
+        environment,
+        ssao,
+    } = options;
+    let view = Matrix4::from(camera.get_view_matrix());
+    UniformChain(
+        UniformChain(
+            UniformChain(
+                light::LightUniforms::new(light, &view),
+                shadow::ShadowUniforms {
+                    shadow_map,
+                    fallback: &shaders.no_shadows,
+                },
+            ),
+            ibl::EnvironmentUniforms::new(environment, &view, &shaders.fallback),
+        ),
+        ssao::SsaoUniforms {
+            ssao,
+            fallback: &shaders.fallback.white,
+        },
+    )
+}
+
+/// Draws the transparent objects from back to front, blended as their material's
+/// `BlendMode` says. They are depth tested against the scene but don't write depth.
+pub(crate) fn render_transparent<S: Surface, U: glium::uniforms::Uniforms + Copy>(
+    frame: &mut S,
+    object_list: &[Box<SceneObject>],
+    camera: &Camera,
+    lighting: U,
+    shaders: &Shaders,
+    draw_params: &DrawParameters,
+) -> Result<(), Error> {
+    let distance = |object: &SceneObject| {
+        let model = object.get_model_matrix();
+        (Vector3::new(model[3][0], model[3][1], model[3][2]) - camera.position()).norm_squared()
+    };
+    let mut transparent: Vec<(Scalar, &SceneObject)> = object_list
+        .iter()
+        .filter(|object| object.material.is_transparent())
+        .map(|object| (distance(object), &**object))
+        .collect();
+    transparent.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    // One draw call per object, instancing would break the order
+    let mut instance_buffer = shaders.instances.borrow_mut();
+    for (_, object) in transparent {
+        let params = DrawParameters {
+            blend: object.material.blend_mode().blend(),
+            depth: glium::Depth {
+                write: false,
+                ..draw_params.depth
+            },
+            ..draw_params.clone()
+        };
+        let instances = instance_buffer.upload(&[Instance::new(object)])?;
+        draw_object(frame, object, instances, camera, lighting, shaders, &params)?;
+    }
+
+    Ok(())
+}
+
+/// Draws instances of an object's mesh in its render mode, or the global one
+fn draw_object<S: Surface, U: glium::uniforms::Uniforms>(
+    frame: &mut S,
+    object: &SceneObject,
+    instances: glium::vertex::VertexBufferSlice<'_, Instance>,
+    camera: &Camera,
+    lighting: U,
+    shaders: &Shaders,
+    draw_params: &DrawParameters,
+) -> Result<(), Error> {
+    let mode = object.render_mode.unwrap_or(shaders.render_mode);
+    if let RenderMode::Shaded | RenderMode::WireframeOverShaded = mode {
+        draw_shaded(
+            frame,
+            object,
+            &instances,
+            camera,
+            lighting,
+            shaders,
+            draw_params,
+        )?;
+    }
+    if mode != RenderMode::Shaded {
+        let programs = &shaders.modes;
+        render_mode::draw_mode(
+            frame,
+            object,
+            &instances,
+            camera,
+            mode,
+            programs,
+            draw_params,
+        )?;
+    }
+    Ok(())
+}
+
+/// Draws instances of an object's mesh, shaded by the object's material
+fn draw_shaded<S: Surface, U: glium::uniforms::Uniforms>(
+    frame: &mut S,
+    object: &SceneObject,
+    instances: &glium::vertex::VertexBufferSlice<'_, Instance>,
+    camera: &Camera,
+    lighting: U,
+    shaders: &Shaders,
+    draw_params: &DrawParameters,
+) -> Result<(), Error> {
+    let mesh = object.drawn_mesh();
+    let instances = instances
+        .per_instance()
+        .map_err(|_| Error::InstancingNotSupported)?;
+
+    let uniforms = glium::uniform! {
+        view: camera.get_view_matrix(),
+        perspective: camera.get_perspective_matrix(),
+    };
+
+    match &object.material {
+        Material::Phong(material) => frame.draw(
+            (
+                &mesh.vertex_bfr,
+                &mesh.normal_bfr,
+                &mesh.color_bfr,
+                instances,
+            ),
+            &mesh.ind_bfr,
+            &shaders.phong,
+            &UniformChain(
+                UniformChain(uniforms, lighting),
+                material::PhongUniforms(material),
+            ),
+            draw_params,
+        ),
+        Material::Pbr(material) => frame.draw(
+            (
+                &mesh.vertex_bfr,
+                &mesh.normal_bfr,
+                &mesh.uv_bfr,
+                &mesh.tangent_bfr,
+                &mesh.color_bfr,
+                instances,
+            ),
+            &mesh.ind_bfr,
+            &shaders.pbr,
+            &UniformChain(
+                UniformChain(uniforms, lighting),
+                material::PbrUniforms {
+                    material,
+                    fallback: &shaders.fallback,
+                },
+            ),
+            draw_params,
+        ),
+    }?;
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use crate::{normalize, Color, Error, Matrix4, Point3, Scalar, Unit, Vector3, WHITE};
+use glium::uniforms::{UniformValue, Uniforms};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LightKind {
+    /// Infinitely far away light, e.g. the sun
+    Directional,
+    /// Cone shaped light with a half angle in radians
+    Spot { angle: Scalar, range: Scalar },
+    /// Light shining in all directions from its position. Does not cast shadows.
+    Point { range: Scalar },
+}
+
+#[derive(Copy, Clone)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: Vector3,
+    pub(crate) direction: Unit<Vector3>,
+    pub color: Color,
+    pub intensity: Scalar,
+    pub casts_shadows: bool,
+}
+
+impl Light {
+    /// A directional light shining along `direction`
+    pub fn directional(direction: Vector3) -> Result<Self, Error> {
+        Ok(Self {
+            kind: LightKind::Directional,
+            position: Vector3::zeros(),
+            direction: normalize(direction)?,
+            color: WHITE,
+            intensity: 1.0,
+            casts_shadows: true,
+        })
+    }
+
+    /// A spot light at `position` shining along `direction` with a cone half angle in radians
+    pub fn spot(
+        position: Vector3,
+        direction: Vector3,
+        angle: Scalar,
+        range: Scalar,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            kind: LightKind::Spot { angle, range },
+            position,
+            direction: normalize(direction)?,
+            color: WHITE,
+            intensity: 1.0,
+            casts_shadows: true,
+        })
+    }
+
+    /// A point light at `position` lighting everything within `range`
+    pub fn point(position: Vector3, range: Scalar) -> Self {
+        Self {
+            kind: LightKind::Point { range },
+            position,
+            direction: -Vector3::y_axis(),
+            color: WHITE,
+            intensity: 1.0,
+            casts_shadows: false,
+        }
+    }
+
+    pub fn direction(&self) -> Vector3 {
+        self.direction.into_inner()
+    }
+
+    /// Keeps the old direction if `direction` has (nearly) zero length
+    pub fn set_direction(&mut self, direction: Vector3) -> Result<(), Error> {
+        self.direction = normalize(direction)?;
+        Ok(())
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: Scalar) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_shadows(mut self, casts_shadows: bool) -> Self {
+        self.casts_shadows = casts_shadows;
+        self
+    }
+}
+
+/// Light uniforms for the main shading pass, moved into view space
+#[derive(Copy, Clone)]
+pub struct LightUniforms {
+    kind: i32,
+    direction: [f32; 3],
+    position: [f32; 3],
+    color: [f32; 3],
+    spot_cutoff: f32,
+    range: f32,
+}
+
+impl LightUniforms {
+    pub fn new(light: &Light, view: &Matrix4) -> Self {
+        let (kind, spot_cutoff, range) = match light.kind {
+            LightKind::Directional => (0, -1.0, 0.0),
+            LightKind::Spot { angle, range } => (1, angle.cos(), range),
+            LightKind::Point { range } => (2, -1.0, range),
+        };
+
+        Self {
+            kind,
+            direction: view.transform_vector(&-light.direction()).into(),
+            position: view
+                .transform_point(&Point3::from(light.position))
+                .coords
+                .into(),
+            color: (light.color.to_linear() * light.intensity).into(),
+            spot_cutoff,
+            range,
+        }
+    }
+}
+
+impl Uniforms for LightUniforms {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        output("u_light_kind", UniformValue::SignedInt(self.kind));
+        output("u_light", UniformValue::Vec3(self.direction));
+        output("u_light_position", UniformValue::Vec3(self.position));
+        output("u_light_color", UniformValue::Vec3(self.color));
+        output("u_spot_cutoff", UniformValue::Float(self.spot_cutoff));
+        output("u_light_range", UniformValue::Float(self.range));
+    }
+}
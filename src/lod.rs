@@ -0,0 +1,183 @@
+use crate::simplify::lod_chain;
+use crate::{Camera, Display, Error, Matrix4, Mesh, MeshData, Scalar, SceneObject, Vector3};
+use crate::{MAX_VERTICES, NORM_EPS};
+use std::rc::Rc;
+
+#[derive(Copy, Clone, Debug)]
+pub struct LodSettings {
+    /// Most meshes in the chain, including the full detail one
+    pub levels: usize,
+    /// Triangles of each level relative to the one before
+    pub ratio: Scalar,
+    /// No level gets simplified below this many triangles
+    pub min_triangles: usize,
+    /// Screen size below which the first simplified level is drawn, as the
+    /// fraction of the viewport height covered by the bounding sphere
+    pub screen_size: Scalar,
+    /// Relative margin around each switching size, so an object sitting right
+    /// at one doesn't flicker between two levels
+    pub hysteresis: Scalar,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            levels: 4,
+            ratio: 0.5,
+            min_triangles: 64,
+            screen_size: 0.5,
+            hysteresis: 0.15,
+        }
+    }
+}
+
+/// Levels of detail of a mesh, from full detail down, and the screen sizes at
+/// which to switch between them. Objects get one with `SceneObject::with_lod`.
+pub struct Lod {
+    pub meshes: Vec<Rc<Mesh>>,
+    /// `thresholds[i]` is the screen size below which level `i + 1` is drawn
+    pub thresholds: Vec<Scalar>,
+    pub hysteresis: Scalar,
+    /// Bounding sphere in model space
+    center: Vector3,
+    radius: Scalar,
+}
+
+impl Lod {
+    /// Simplifies the mesh into a chain of levels and uploads them. Each level
+    /// takes over at `ratio.sqrt()` times the screen size of the one before,
+    /// where it has about as many triangles per pixel.
+    pub fn new(display: &Display, data: &MeshData, settings: LodSettings) -> Result<Self, Error> {
+        let chain = lod_chain(
+            data,
+            settings.levels,
+            settings.ratio,
+            settings.min_triangles,
+        );
+        let center = data
+            .bounds()
+            .map_or_else(Vector3::zeros, |(min, max)| (min + max) / 2.0);
+        let radius = (0..data.vertex_count())
+            .map(|vertex| (data.position(vertex) - center).norm())
+            .fold(0.0, Scalar::max);
+
+        // Meshes too big to upload are left out, the next level stands in for them
+        let mut meshes = Vec::new();
+        let mut thresholds = Vec::new();
+        let mut size = settings.screen_size;
+        for (level, data) in chain.iter().enumerate() {
+            if level > 0 {
+                size *= settings.ratio.sqrt();
+            }
+            if data.vertex_count() > MAX_VERTICES {
+                continue;
+            }
+            if !meshes.is_empty() {
+                thresholds.push(size);
+            }
+            meshes.push(Rc::new(Mesh::from_data(display, data)?));
+        }
+        if meshes.is_empty() {
+            return Err(Error::TooManyVertices(data.vertex_count()));
+        }
+
+        Ok(Self {
+            meshes,
+            thresholds,
+            hysteresis: settings.hysteresis,
+            center,
+            radius,
+        })
+    }
+
+    pub fn levels(&self) -> usize {
+        self.meshes.len()
+    }
+
+    /// Fraction of the viewport height the bounding sphere covers with this model
+    /// matrix, infinite with the camera inside it
+    pub fn screen_size(&self, model: &Matrix4, camera: &Camera) -> Scalar {
+        let center = model.transform_point(&self.center.into()).coords;
+        let scale = (0..3)
+            .map(|i| model.column(i).xyz().norm())
+            .fold(0.0, Scalar::max);
+        let radius = self.radius * scale;
+        let distance = (center - camera.position()).norm();
+        if distance <= radius.max(NORM_EPS) {
+            return Scalar::INFINITY;
+        }
+        radius / (distance * (camera.fov() / 2.0).tan())
+    }
+
+    /// The level to draw at a screen size, moving from `current` only once the
+    /// size is past a threshold by the hysteresis margin
+    pub fn select(&self, size: Scalar, current: usize) -> usize {
+        let last = self.thresholds.len();
+        let mut level = current.min(last);
+        while level < last && size < self.thresholds[level] * (1.0 - self.hysteresis) {
+            level += 1;
+        }
+        while level > 0 && size > self.thresholds[level - 1] * (1.0 + self.hysteresis) {
+            level -= 1;
+        }
+        level
+    }
+}
+
+/// Picks the level of detail of each object with one for the camera. Called by
+/// every pass drawing from the camera, so they all see the same meshes.
+pub(crate) fn select_levels(object_list: &[Box<SceneObject>], camera: &Camera) {
+    for object in object_list {
+        if let Some(lod) = &object.lod {
+            let model = Matrix4::from(object.get_model_matrix());
+            let size = lod.screen_size(&model, camera);
+            object
+                .lod_level
+                .set(lod.select(size, object.lod_level.get()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lod() -> Lod {
+        Lod {
+            meshes: Vec::new(),
+            thresholds: vec![0.5, 0.25],
+            hysteresis: 0.1,
+            center: Vector3::zeros(),
+            radius: 1.0,
+        }
+    }
+
+    #[test]
+    fn select_follows_the_thresholds() {
+        let lod = lod();
+        assert_eq!(lod.select(1.0, 0), 0);
+        assert_eq!(lod.select(0.3, 0), 1);
+        assert_eq!(lod.select(0.1, 0), 2);
+        assert_eq!(lod.select(0.1, 5), 2);
+        assert_eq!(lod.select(Scalar::INFINITY, 2), 0);
+    }
+
+    #[test]
+    fn select_does_not_flip_flop_at_a_threshold() {
+        let lod = lod();
+        for &start in &[0, 1] {
+            let mut level = start;
+            for step in 0..100 {
+                let size = if step % 2 == 0 { 0.48 } else { 0.52 };
+                level = lod.select(size, level);
+                assert_eq!(level, start);
+            }
+        }
+
+        // Past the margin the level changes, and stays when the size comes back a little
+        assert_eq!(lod.select(0.44, 0), 1);
+        assert_eq!(lod.select(0.5, 1), 1);
+        assert_eq!(lod.select(0.56, 1), 0);
+        assert_eq!(lod.select(0.5, 0), 0);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::error::allocation;
+use crate::{Color, Error, Scalar, BLACK, WHITE};
+use glium::draw_parameters::{Blend, BlendingFunction, LinearBlendingFactor};
+use glium::texture::{Cubemap, SrgbTexture2d, Texture2d};
+use glium::uniforms::{SamplerBehavior, SamplerWrapFunction, UniformValue, Uniforms};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+
+/// Selects the shading path an object is drawn with
+#[derive(Clone)]
+pub enum Material {
+    Phong(PhongMaterial),
+    Pbr(PbrMaterial),
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::Phong(PhongMaterial::default())
+    }
+}
+
+impl Material {
+    pub fn blend_mode(&self) -> BlendMode {
+        match self {
+            Material::Phong(material) => material.blend,
+            Material::Pbr(material) => material.blend,
+        }
+    }
+
+    /// Transparent objects are drawn after the opaque ones, sorted back to front
+    pub fn is_transparent(&self) -> bool {
+        self.blend_mode() != BlendMode::Opaque
+    }
+
+    /// Whether objects with the two materials can share an instanced draw call
+    pub(crate) fn batches_with(&self, other: &Material) -> bool {
+        match (self, other) {
+            (Material::Phong(a), Material::Phong(b)) => a == b,
+            (Material::Pbr(a), Material::Pbr(b)) => {
+                a.base_color == b.base_color
+                    && a.blend == b.blend
+                    && a.metallic == b.metallic
+                    && a.roughness == b.roughness
+                    && a.ambient_occlusion == b.ambient_occlusion
+                    && a.emissive == b.emissive
+                    && a.normal_scale == b.normal_scale
+                    && same_texture(&a.base_color_texture, &b.base_color_texture)
+                    && same_texture(&a.metallic_roughness_texture, &b.metallic_roughness_texture)
+                    && same_texture(&a.occlusion_texture, &b.occlusion_texture)
+                    && same_texture(&a.emissive_texture, &b.emissive_texture)
+                    && same_texture(&a.normal_texture, &b.normal_texture)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn same_texture<T>(a: &Option<Rc<T>>, b: &Option<Rc<T>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// How an object's color is combined with what lies behind it. The opacity is
+/// the alpha of the diffuse or base color, times the alpha of the object's color.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlendMode {
+    /// Alpha is ignored
+    #[default]
+    Opaque,
+    /// Mixes the color with the background by its opacity
+    Alpha,
+    /// Adds the color scaled by its opacity, e.g. for glows and fire
+    Additive,
+    /// Adds the color in full and dims the background by the opacity. Reflections
+    /// are not faded out, so e.g. glass keeps its highlights.
+    Premultiplied,
+}
+
+impl BlendMode {
+    pub(crate) fn blend(self) -> Blend {
+        let function = |source, destination| BlendingFunction::Addition {
+            source,
+            destination,
+        };
+        let color = match self {
+            BlendMode::Opaque => return Blend::default(),
+            BlendMode::Alpha => function(
+                LinearBlendingFactor::SourceAlpha,
+                LinearBlendingFactor::OneMinusSourceAlpha,
+            ),
+            BlendMode::Additive => {
+                function(LinearBlendingFactor::SourceAlpha, LinearBlendingFactor::One)
+            }
+            BlendMode::Premultiplied => function(
+                LinearBlendingFactor::One,
+                LinearBlendingFactor::OneMinusSourceAlpha,
+            ),
+        };
+        Blend {
+            color,
+            alpha: function(
+                LinearBlendingFactor::One,
+                LinearBlendingFactor::OneMinusSourceAlpha,
+            ),
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Classic Blinn-Phong material
+#[derive(Copy, Clone, PartialEq)]
+pub struct PhongMaterial {
+    pub ambient: Color,
+    pub diffuse: Color,
+    pub specular: Color,
+    pub shininess: Scalar,
+    pub blend: BlendMode,
+}
+
+impl Default for PhongMaterial {
+    /// The red plastic look the renderer always had
+    fn default() -> Self {
+        Self {
+            ambient: Color::new(0.2, 0.0, 0.0),
+            diffuse: Color::new(0.6, 0.0, 0.0),
+            specular: WHITE,
+            shininess: 16.0,
+            blend: BlendMode::Opaque,
+        }
+    }
+}
+
+/// Metallic/roughness material, shaded with a Cook-Torrance BRDF.
+/// The factors multiply the corresponding texture when one is present.
+#[derive(Clone)]
+pub struct PbrMaterial {
+    pub base_color: Color,
+    pub metallic: Scalar,
+    pub roughness: Scalar,
+    pub ambient_occlusion: Scalar,
+    pub emissive: Color,
+    pub blend: BlendMode,
+    /// Strength of the normal map, 1.0 uses it unchanged
+    pub normal_scale: Scalar,
+    pub base_color_texture: Option<Rc<SrgbTexture2d>>,
+    /// Roughness is read from the green and metallic from the blue channel
+    pub metallic_roughness_texture: Option<Rc<Texture2d>>,
+    /// Ambient occlusion is read from the red channel
+    pub occlusion_texture: Option<Rc<Texture2d>>,
+    pub emissive_texture: Option<Rc<SrgbTexture2d>>,
+    /// Tangent space normal map
+    pub normal_texture: Option<Rc<Texture2d>>,
+}
+
+impl PbrMaterial {
+    pub fn new(base_color: Color, metallic: Scalar, roughness: Scalar) -> Self {
+        Self {
+            base_color,
+            metallic,
+            roughness,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            base_color: WHITE,
+            metallic: 0.0,
+            roughness: 0.5,
+            ambient_occlusion: 1.0,
+            emissive: BLACK,
+            blend: BlendMode::Opaque,
+            normal_scale: 1.0,
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+/// Phong material uniforms for the main shading pass
+#[derive(Copy, Clone)]
+pub struct PhongUniforms<'m>(pub &'m PhongMaterial);
+
+impl<'m> Uniforms for PhongUniforms<'m> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let material = self.0;
+        output(
+            "u_ambient_color",
+            UniformValue::Vec3(material.ambient.to_linear().into()),
+        );
+        output(
+            "u_diffuse_color",
+            UniformValue::Vec3(material.diffuse.to_linear().into()),
+        );
+        output(
+            "u_specular_color",
+            UniformValue::Vec3(material.specular.to_linear().into()),
+        );
+        output("u_shininess", UniformValue::Float(material.shininess));
+        output("u_opacity", UniformValue::Float(material.diffuse.a));
+    }
+}
+
+/// Textures bound in place of the maps a PBR material doesn't have
+pub struct FallbackTextures {
+    /// Neutral for color and emissive maps
+    pub white_srgb: SrgbTexture2d,
+    /// Neutral for occlusion and metallic/roughness maps
+    pub white: Texture2d,
+    /// A flat tangent space normal
+    pub normal: Texture2d,
+    /// Bound to the environment samplers when there is no environment
+    pub cube: Cubemap,
+}
+
+impl FallbackTextures {
+    pub fn new(display: &crate::Display) -> Result<Self, Error> {
+        Ok(Self {
+            white_srgb: SrgbTexture2d::new(display, vec![vec![(255u8, 255u8, 255u8, 255u8)]])
+                .map_err(allocation("fallback texture"))?,
+            white: Texture2d::new(display, vec![vec![(255u8, 255u8, 255u8, 255u8)]])
+                .map_err(allocation("fallback texture"))?,
+            normal: Texture2d::new(display, vec![vec![(128u8, 128u8, 255u8, 255u8)]])
+                .map_err(allocation("fallback texture"))?,
+            cube: Cubemap::empty(display, 1).map_err(allocation("fallback cubemap"))?,
+        })
+    }
+}
+
+/// PBR material uniforms for the main shading pass
+#[derive(Copy, Clone)]
+pub struct PbrUniforms<'m> {
+    pub material: &'m PbrMaterial,
+    pub fallback: &'m FallbackTextures,
+}
+
+impl<'m> Uniforms for PbrUniforms<'m> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let material = self.material;
+        let white = &self.fallback.white;
+
+        output(
+            "u_base_color",
+            UniformValue::Vec3(material.base_color.to_linear().into()),
+        );
+        output("u_opacity", UniformValue::Float(material.base_color.a));
+        output("u_metallic", UniformValue::Float(material.metallic));
+        output("u_roughness", UniformValue::Float(material.roughness));
+        output("u_ao", UniformValue::Float(material.ambient_occlusion));
+        output(
+            "u_emissive",
+            UniformValue::Vec3(material.emissive.to_linear().into()),
+        );
+        output("u_normal_scale", UniformValue::Float(material.normal_scale));
+        output(
+            "u_has_normal_map",
+            UniformValue::Bool(material.normal_texture.is_some()),
+        );
+
+        // Texture coordinates outside [0, 1] tile, as in glTF
+        let sampler = SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Repeat,
+                SamplerWrapFunction::Repeat,
+                SamplerWrapFunction::Repeat,
+            ),
+            ..Default::default()
+        };
+
+        let srgb_maps = [
+            ("u_base_color_map", &material.base_color_texture),
+            ("u_emissive_map", &material.emissive_texture),
+        ];
+        for &(name, texture) in srgb_maps.iter() {
+            let texture = texture.as_deref().unwrap_or(&self.fallback.white_srgb);
+            output(name, UniformValue::SrgbTexture2d(texture, Some(sampler)));
+        }
+
+        let maps = [
+            (
+                "u_metallic_roughness_map",
+                &material.metallic_roughness_texture,
+                white,
+            ),
+            ("u_occlusion_map", &material.occlusion_texture, white),
+            (
+                "u_normal_map",
+                &material.normal_texture,
+                &self.fallback.normal,
+            ),
+        ];
+        for &(name, texture, fallback) in maps.iter() {
+            let texture = texture.as_deref().unwrap_or(fallback);
+            output(name, UniformValue::Texture2d(texture, Some(sampler)));
+        }
+    }
+}
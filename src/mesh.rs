@@ -0,0 +1,124 @@
+use crate::error::allocation;
+use crate::teapot;
+use crate::{
+    Display, Error, IndexBuffer, MeshData, Normal, Tangent, TexCoord, Vertex, VertexBuffer,
+    VertexColor, MAX_VERTICES,
+};
+
+/// Vertex and index buffers of a triangle mesh on the GPU. Objects share a mesh
+/// through an `Rc`, objects sharing one are drawn with a single instanced draw call.
+pub struct Mesh {
+    pub vertex_bfr: VertexBuffer<Vertex>,
+    pub normal_bfr: VertexBuffer<Normal>,
+    pub uv_bfr: VertexBuffer<TexCoord>,
+    pub tangent_bfr: VertexBuffer<Tangent>,
+    pub color_bfr: VertexBuffer<VertexColor>,
+    pub ind_bfr: IndexBuffer<u16>,
+}
+
+impl Mesh {
+    pub fn new(
+        display: &Display,
+        vert: &[Vertex],
+        norm: &[Normal],
+        ind: &[u16],
+    ) -> Result<Self, Error> {
+        // Untextured meshes get zeroed attributes, a zero tangent disables normal mapping
+        let uv = vec![TexCoord::default(); vert.len()];
+        let tangents = vec![Tangent::default(); vert.len()];
+        Self::with_tex_coords(display, vert, norm, &uv, &tangents, ind)
+    }
+
+    /// Like `new`, with the texture coordinates and tangents needed by textured PBR materials
+    pub fn with_tex_coords(
+        display: &Display,
+        vert: &[Vertex],
+        norm: &[Normal],
+        uv: &[TexCoord],
+        tangents: &[Tangent],
+        ind: &[u16],
+    ) -> Result<Self, Error> {
+        let colors = vec![VertexColor::default(); vert.len()];
+        Self::with_colors(display, vert, norm, uv, tangents, &colors, ind)
+    }
+
+    /// Like `with_tex_coords`, with a color per vertex
+    pub fn with_colors(
+        display: &Display,
+        vert: &[Vertex],
+        norm: &[Normal],
+        uv: &[TexCoord],
+        tangents: &[Tangent],
+        colors: &[VertexColor],
+        ind: &[u16],
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            vertex_bfr: VertexBuffer::new(display, vert).map_err(allocation("vertex buffer"))?,
+            normal_bfr: VertexBuffer::new(display, norm)
+                .map_err(allocation("vertex buffer for normals"))?,
+            uv_bfr: VertexBuffer::new(display, uv)
+                .map_err(allocation("vertex buffer for texture coordinates"))?,
+            tangent_bfr: VertexBuffer::new(display, tangents)
+                .map_err(allocation("vertex buffer for tangents"))?,
+            color_bfr: VertexBuffer::new(display, colors)
+                .map_err(allocation("vertex buffer for colors"))?,
+            ind_bfr: IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, ind)
+                .map_err(allocation("index buffer"))?,
+        })
+    }
+
+    /// Uploads geometry built or processed in memory. Fails for more than
+    /// `MAX_VERTICES` vertices, larger meshes have to be `split`.
+    pub fn from_data(display: &Display, data: &MeshData) -> Result<Self, Error> {
+        if data.vertex_count() > MAX_VERTICES {
+            return Err(Error::TooManyVertices(data.vertex_count()));
+        }
+        let indices: Vec<u16> = data.indices.iter().map(|&index| index as u16).collect();
+        if data.colors.is_empty() {
+            return Self::with_tex_coords(
+                display,
+                &data.vertices,
+                &data.normals,
+                &data.tex_coords,
+                &data.tangents,
+                &indices,
+            );
+        }
+        Self::with_colors(
+            display,
+            &data.vertices,
+            &data.normals,
+            &data.tex_coords,
+            &data.tangents,
+            &data.colors,
+            &indices,
+        )
+    }
+
+    /// Reads the geometry back from the GPU, to process or export it
+    pub fn to_data(&self) -> Result<MeshData, Error> {
+        Ok(MeshData {
+            vertices: self.vertex_bfr.read()?,
+            normals: self.normal_bfr.read()?,
+            tex_coords: self.uv_bfr.read()?,
+            tangents: self.tangent_bfr.read()?,
+            colors: self.color_bfr.read()?,
+            indices: self.ind_bfr.read()?.into_iter().map(u32::from).collect(),
+        })
+    }
+
+    /// Size of the vertex and index buffers in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.vertex_bfr.get_size()
+            + self.normal_bfr.get_size()
+            + self.uv_bfr.get_size()
+            + self.tangent_bfr.get_size()
+            + self.color_bfr.get_size()
+            + self.ind_bfr.get_size()
+    }
+
+    /// The Utah teapot tessellated at `teapot::DEFAULT_LEVEL`, fitting the cube from -1 to 1
+    pub fn teapot(display: &Display) -> Result<Self, Error> {
+        Self::from_data(display, &teapot::teapot(teapot::DEFAULT_LEVEL))
+    }
+}
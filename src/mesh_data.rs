@@ -0,0 +1,976 @@
+use crate::{
+    Matrix4, Normal, Point3, Scalar, Tangent, TexCoord, Vector3, Vertex, VertexColor, NORM_EPS, PI,
+    UP,
+};
+use std::collections::HashMap;
+
+/// Most vertices a mesh uploaded with `Mesh::from_data` can have, the range of
+/// its u16 indices. Larger meshes are uploaded in parts made by `split`.
+pub const MAX_VERTICES: usize = u16::MAX as usize + 1;
+
+/// Triangle mesh geometry in memory, for building and processing meshes before
+/// they are uploaded with `Mesh::from_data`. The attribute vectors have one
+/// entry per vertex, every three indices form a triangle. Meshes without vertex
+/// colors leave `colors` empty and are drawn in the material's color.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub normals: Vec<Normal>,
+    pub tex_coords: Vec<TexCoord>,
+    pub tangents: Vec<Tangent>,
+    pub colors: Vec<VertexColor>,
+    pub indices: Vec<u32>,
+}
+
+/// Size and shape of a mesh, see `MeshData::stats`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshStats {
+    pub vertices: usize,
+    pub triangles: usize,
+    /// Corners of the axis aligned bounding box, zero for empty meshes
+    pub min: Vector3,
+    pub max: Vector3,
+    pub area: Scalar,
+    /// Enclosed volume, only meaningful for closed meshes
+    pub volume: Scalar,
+    /// Edges with a single triangle, on the border of a hole or an open surface
+    pub boundary_edges: usize,
+    /// Edges shared by more than two triangles
+    pub non_manifold_edges: usize,
+    /// Edges between two triangles facing opposite sides
+    pub flipped_edges: usize,
+}
+
+impl MeshStats {
+    /// Every edge joins two consistently oriented triangles, so the mesh encloses a volume
+    pub fn is_closed(&self) -> bool {
+        self.boundary_edges == 0 && self.is_manifold()
+    }
+
+    /// No edge is shared by more than two triangles or joins triangles facing opposite sides
+    pub fn is_manifold(&self) -> bool {
+        self.non_manifold_edges == 0 && self.flipped_edges == 0
+    }
+}
+
+impl MeshData {
+    /// Geometry without texture coordinates, they and the tangents are zeroed
+    pub fn new(vertices: Vec<Vertex>, normals: Vec<Normal>, indices: Vec<u32>) -> Self {
+        let count = vertices.len();
+        Self {
+            vertices,
+            normals,
+            tex_coords: vec![TexCoord::default(); count],
+            tangents: vec![Tangent::default(); count],
+            colors: Vec::new(),
+            indices,
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// The vertex indices of every triangle
+    pub fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+    }
+
+    pub fn position(&self, vertex: usize) -> Vector3 {
+        let (x, y, z) = self.vertices[vertex].position;
+        Vector3::new(x, y, z)
+    }
+
+    /// Normal of a triangle following its winding, with twice its area as length
+    pub fn face_normal(&self, [a, b, c]: [usize; 3]) -> Vector3 {
+        let a = self.position(a);
+        (self.position(b) - a).cross(&(self.position(c) - a))
+    }
+
+    /// Bakes a transform into the geometry. Normals and tangents follow, and a
+    /// mirroring transform reverses the winding so the front faces stay in front.
+    pub fn transform(&mut self, matrix: &Matrix4) {
+        let linear = matrix.fixed_slice::<3, 3>(0, 0).into_owned();
+        let normal_matrix = linear
+            .try_inverse()
+            .map_or(linear, |inverse| inverse.transpose());
+        let mirrored = linear.determinant() < 0.0;
+
+        for vertex in self.vertices.iter_mut() {
+            let (x, y, z) = vertex.position;
+            let p = matrix.transform_point(&Point3::new(x, y, z));
+            vertex.position = (p.x, p.y, p.z);
+        }
+        for normal in self.normals.iter_mut() {
+            let (x, y, z) = normal.normal;
+            let n = (normal_matrix * Vector3::new(x, y, z))
+                .try_normalize(NORM_EPS)
+                .unwrap_or_else(Vector3::zeros);
+            normal.normal = (n.x, n.y, n.z);
+        }
+        for tangent in self.tangents.iter_mut() {
+            let (x, y, z, w) = tangent.tangent;
+            let t = (linear * Vector3::new(x, y, z))
+                .try_normalize(NORM_EPS)
+                .unwrap_or_else(Vector3::zeros);
+            let w = if mirrored { -w } else { w };
+            tangent.tangent = (t.x, t.y, t.z, w);
+        }
+        if mirrored {
+            self.flip_winding();
+        }
+    }
+
+    /// Reverses the order of every triangle's vertices, turning it around
+    pub fn flip_winding(&mut self) {
+        for triangle in self.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    /// Points the normals the other way. The bitangents follow, the tangents
+    /// still point along +u.
+    pub fn flip_normals(&mut self) {
+        for normal in self.normals.iter_mut() {
+            let (x, y, z) = normal.normal;
+            normal.normal = (-x, -y, -z);
+        }
+        for tangent in self.tangents.iter_mut() {
+            tangent.tangent.3 = -tangent.tangent.3;
+        }
+    }
+
+    /// Corners of the axis aligned bounding box, `None` without vertices
+    pub fn bounds(&self) -> Option<(Vector3, Vector3)> {
+        if self.vertices.is_empty() {
+            return None;
+        }
+        let first = self.position(0);
+        Some(
+            (0..self.vertex_count()).fold((first, first), |(min, max), i| {
+                let position = self.position(i);
+                (min.inf(&position), max.sup(&position))
+            }),
+        )
+    }
+
+    /// Moves the center of the bounding box to the origin
+    pub fn center(&mut self) {
+        if let Some((min, max)) = self.bounds() {
+            self.transform(&Matrix4::new_translation(&(-(min + max) / 2.0)));
+        }
+    }
+
+    /// Centers the mesh and scales it evenly to fit the cube from -1 to 1,
+    /// touching it on the longest side
+    pub fn normalize(&mut self) {
+        let (min, max) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let half_size = (max - min).max() / 2.0;
+        let scale = if half_size > NORM_EPS {
+            1.0 / half_size
+        } else {
+            1.0
+        };
+        let center = Matrix4::new_translation(&(-(min + max) / 2.0));
+        self.transform(&(Matrix4::new_scaling(scale) * center));
+    }
+
+    /// Appends the geometry of another mesh. If only one of them has normals,
+    /// the other one gets smooth normals. Missing texture coordinates and
+    /// tangents are zeroed, missing vertex colors become white.
+    pub fn append(&mut self, other: &MeshData) {
+        let with_normals;
+        let other = if self.normals.is_empty() == other.normals.is_empty() {
+            other
+        } else if self.normals.is_empty() {
+            self.smooth_normals(PI);
+            other
+        } else {
+            let mut copy = other.clone();
+            copy.smooth_normals(PI);
+            with_normals = copy;
+            &with_normals
+        };
+
+        let (count, offset) = (self.vertex_count(), self.vertex_count() as u32);
+        let total = count + other.vertex_count();
+        self.vertices.extend_from_slice(&other.vertices);
+        self.normals.extend_from_slice(&other.normals);
+        append_attribute(&mut self.tex_coords, &other.tex_coords, count, total);
+        append_attribute(&mut self.tangents, &other.tangents, count, total);
+        append_attribute(&mut self.colors, &other.colors, count, total);
+        self.indices
+            .extend(other.indices.iter().map(|&index| index + offset));
+    }
+
+    /// Combines meshes into one, each with its transform baked in, e.g. to draw
+    /// static objects sharing a material with a single call
+    pub fn merge(parts: &[(&MeshData, Matrix4)]) -> MeshData {
+        let mut merged = MeshData::default();
+        for (data, matrix) in parts {
+            let mut part = (*data).clone();
+            part.transform(matrix);
+            merged.append(&part);
+        }
+        merged
+    }
+
+    /// Merges vertices less than `epsilon` apart, closing cracks left by
+    /// rounding. Nearby positions are first moved onto each other, then
+    /// vertices agreeing in all attributes become one, so UV seams and hard
+    /// edges stay. Welding across them needs the normals recomputed by
+    /// `smooth_normals` afterwards. Triangles collapsing in the process are
+    /// removed. Returns how many vertices are gone.
+    pub fn weld(&mut self, epsilon: Scalar) -> usize {
+        let count = self.vertex_count();
+        let cell = |position: Vector3| position.map(|x| (x / epsilon).floor() as i64);
+
+        // Positions are moved onto the first position near them
+        let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        for i in 0..count {
+            let position = self.position(i);
+            let target = if epsilon > 0.0 {
+                let center = cell(position);
+                let mut target = None;
+                'search: for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        for dz in -1..=1 {
+                            let key = [center.x + dx, center.y + dy, center.z + dz];
+                            for &other in cells.get(&key).into_iter().flatten() {
+                                if (self.position(other) - position).norm() <= epsilon {
+                                    target = Some(other);
+                                    break 'search;
+                                }
+                            }
+                        }
+                    }
+                }
+                if target.is_none() {
+                    cells.entry(center.into()).or_default().push(i);
+                }
+                target
+            } else {
+                None
+            };
+            if let Some(target) = target {
+                self.vertices[i] = self.vertices[target];
+            }
+        }
+
+        let mut shared: HashMap<Vec<u32>, u32> = HashMap::new();
+        let mut remap = Vec::with_capacity(count);
+        let mut data = MeshData::default();
+        for i in 0..count {
+            let key = self.attribute_bits(i);
+            let index = *shared
+                .entry(key)
+                .or_insert_with(|| data.copy_vertex(self, i));
+            remap.push(index);
+        }
+        for triangle in self.triangles() {
+            let [a, b, c] = triangle.map(|vertex| remap[vertex]);
+            if a != b && b != c && c != a {
+                data.indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+        *self = data;
+        count - self.vertex_count()
+    }
+
+    /// Drops the vertices no triangle uses
+    pub fn remove_unused_vertices(&mut self) {
+        let mut remap: Vec<Option<u32>> = vec![None; self.vertex_count()];
+        let mut data = MeshData::default();
+        for &index in &self.indices {
+            let index = match remap[index as usize] {
+                Some(index) => index,
+                None => {
+                    let copied = data.copy_vertex(self, index as usize);
+                    remap[index as usize] = Some(copied);
+                    copied
+                }
+            };
+            data.indices.push(index);
+        }
+        *self = data;
+    }
+
+    /// Splits the mesh into parts `Mesh::from_data` can upload, with at most
+    /// `MAX_VERTICES` vertices each. Vertices shared by triangles in different
+    /// parts are copied into each of them.
+    pub fn split(&self) -> Vec<MeshData> {
+        if self.vertex_count() <= MAX_VERTICES {
+            return vec![self.clone()];
+        }
+
+        let mut parts = Vec::new();
+        let mut part = MeshData::default();
+        let mut remap: HashMap<usize, u32> = HashMap::new();
+        for triangle in self.triangles() {
+            let new = triangle
+                .iter()
+                .filter(|vertex| !remap.contains_key(vertex))
+                .count();
+            if part.vertex_count() + new > MAX_VERTICES {
+                parts.push(std::mem::take(&mut part));
+                remap.clear();
+            }
+            for &vertex in &triangle {
+                let index = match remap.get(&vertex) {
+                    Some(&index) => index,
+                    None => {
+                        let index = part.copy_vertex(self, vertex);
+                        remap.insert(vertex, index);
+                        index
+                    }
+                };
+                part.indices.push(index);
+            }
+        }
+        if !part.indices.is_empty() {
+            parts.push(part);
+        }
+        parts
+    }
+
+    /// Counts, bounds, surface area, volume and how well the triangles are
+    /// connected. Connectivity follows positions, so vertices split along UV
+    /// seams or hard edges don't open the surface.
+    pub fn stats(&self) -> MeshStats {
+        let (min, max) = self
+            .bounds()
+            .unwrap_or((Vector3::zeros(), Vector3::zeros()));
+        let mut area = 0.0;
+        let mut volume = 0.0;
+        // Triangles using each edge in the direction of their winding
+        let mut edges: HashMap<([u32; 3], [u32; 3]), usize> = HashMap::new();
+        for triangle in self.triangles() {
+            area += self.face_normal(triangle).norm() / 2.0;
+            let [a, b, c] = triangle.map(|vertex| self.position(vertex));
+            volume += a.dot(&b.cross(&c)) / 6.0;
+            for corner in 0..3 {
+                let from = self.position_key(triangle[corner]);
+                let to = self.position_key(triangle[(corner + 1) % 3]);
+                *edges.entry((from, to)).or_default() += 1;
+            }
+        }
+
+        let (mut boundary_edges, mut non_manifold_edges, mut flipped_edges) = (0, 0, 0);
+        for (&(from, to), &count) in &edges {
+            // Each undirected edge once, from its smaller end
+            if from > to && edges.contains_key(&(to, from)) {
+                continue;
+            }
+            let reverse = edges.get(&(to, from)).copied().unwrap_or(0);
+            match (count, reverse) {
+                (1, 0) | (0, 1) => boundary_edges += 1,
+                (1, 1) => (),
+                (2, 0) | (0, 2) => flipped_edges += 1,
+                _ => non_manifold_edges += 1,
+            }
+        }
+
+        MeshStats {
+            vertices: self.vertex_count(),
+            triangles: self.triangle_count(),
+            min,
+            max,
+            area,
+            volume: volume.abs(),
+            boundary_edges,
+            non_manifold_edges,
+            flipped_edges,
+        }
+    }
+
+    /// Gives every triangle its own vertices, all with the triangle's normal
+    pub fn flat_normals(&mut self) {
+        let mut corners = Vec::with_capacity(self.indices.len());
+        for triangle in self.triangles() {
+            let normal = self.face_normal(triangle);
+            let normal = normal.try_normalize(NORM_EPS).unwrap_or(UP);
+            corners.extend(triangle.iter().map(|&vertex| (vertex, normal)));
+        }
+        self.rebuild(&corners);
+    }
+
+    /// Smooth vertex normals, averaged over the triangles around each position
+    /// and weighted by the triangles' angles there. Triangles meeting at more
+    /// than `crease_angle` radians keep a hard edge, vertices on such edges are
+    /// split. A crease angle of `PI` smooths everything and splits nothing.
+    pub fn smooth_normals(&mut self, crease_angle: Scalar) {
+        let triangles: Vec<[usize; 3]> = self.triangles().collect();
+        let face_normals: Vec<Vector3> = triangles
+            .iter()
+            .map(|&triangle| {
+                self.face_normal(triangle)
+                    .try_normalize(NORM_EPS)
+                    .unwrap_or_else(Vector3::zeros)
+            })
+            .collect();
+
+        // The corners at each position, vertices split along UV seams still share normals
+        let mut positions: HashMap<[u32; 3], Vec<(usize, Scalar)>> = HashMap::new();
+        for (face, triangle) in triangles.iter().enumerate() {
+            for corner in 0..3 {
+                let angle = self.corner_angle(*triangle, corner);
+                positions
+                    .entry(self.position_key(triangle[corner]))
+                    .or_default()
+                    .push((face, angle));
+            }
+        }
+
+        let min_cos = crease_angle.cos();
+        let mut corners = Vec::with_capacity(self.indices.len());
+        for (face, triangle) in triangles.iter().enumerate() {
+            let face_normal = face_normals[face];
+            for &vertex in triangle {
+                let normal: Vector3 = positions[&self.position_key(vertex)]
+                    .iter()
+                    .filter(|&&(other, _)| {
+                        other == face || face_normals[other].dot(&face_normal) >= min_cos
+                    })
+                    .map(|&(other, angle)| face_normals[other] * angle)
+                    .sum();
+                // Degenerate triangles keep what the vertex had
+                let normal = normal
+                    .try_normalize(NORM_EPS)
+                    .or_else(|| {
+                        let (x, y, z) = self.normals.get(vertex)?.normal;
+                        Vector3::new(x, y, z).try_normalize(NORM_EPS)
+                    })
+                    .unwrap_or(UP);
+                corners.push((vertex, normal));
+            }
+        }
+        self.rebuild(&corners);
+    }
+
+    /// Tangents for normal mapping from the texture coordinates, in the layout
+    /// MikkTSpace uses: a unit tangent along +u in xyz, and in w the sign making
+    /// `cross(normal, tangent) * w` point along +v. The contributions of the
+    /// triangles around a vertex are weighted by their angles. Like MikkTSpace,
+    /// vertices shared by triangles with mirrored UV mappings are split, so each
+    /// side gets its own handedness. Vertices without a usable mapping get a
+    /// zero tangent, which disables normal mapping.
+    pub fn generate_tangents(&mut self) {
+        // Tangent, bitangent and whether the mapping is mirrored, per triangle
+        let frames: Vec<Option<(Vector3, Vector3, bool)>> = self
+            .triangles()
+            .map(|[a, b, c]| {
+                let uv = |i: usize| self.tex_coords.get(i).map_or((0.0, 0.0), |t| t.tex_coords);
+                let (ua, va) = uv(a);
+                let (du1, dv1) = (uv(b).0 - ua, uv(b).1 - va);
+                let (du2, dv2) = (uv(c).0 - ua, uv(c).1 - va);
+                let determinant = du1 * dv2 - du2 * dv1;
+                if determinant.abs() < NORM_EPS {
+                    return None;
+                }
+
+                let e1 = self.position(b) - self.position(a);
+                let e2 = self.position(c) - self.position(a);
+                let tangent = (e1 * dv2 - e2 * dv1) / determinant;
+                let bitangent = (e2 * du1 - e1 * du2) / determinant;
+                Some((tangent, bitangent, determinant < 0.0))
+            })
+            .collect();
+
+        // Vertices keep the side of the first triangle using them, triangles on
+        // the other side of a mirror seam get a copy
+        let source = self.clone();
+        let mut mirrored: Vec<Option<bool>> = vec![None; self.vertex_count()];
+        let mut copies: HashMap<usize, u32> = HashMap::new();
+        for (face, frame) in frames.iter().enumerate() {
+            let flipped = match frame {
+                Some((_, _, flipped)) => *flipped,
+                None => continue,
+            };
+            for corner in face * 3..face * 3 + 3 {
+                let vertex = self.indices[corner] as usize;
+                match mirrored[vertex] {
+                    None => mirrored[vertex] = Some(flipped),
+                    Some(side) if side == flipped => (),
+                    Some(_) => {
+                        let copy = match copies.get(&vertex) {
+                            Some(&copy) => copy,
+                            None => {
+                                let copy = self.copy_vertex(&source, vertex);
+                                copies.insert(vertex, copy);
+                                copy
+                            }
+                        };
+                        self.indices[corner] = copy;
+                    }
+                }
+            }
+        }
+
+        let count = self.vertex_count();
+        let mut tangents = vec![Vector3::zeros(); count];
+        let mut bitangents = vec![Vector3::zeros(); count];
+        for (triangle, frame) in self.triangles().zip(&frames) {
+            if let Some((tangent, bitangent, _)) = frame {
+                for corner in 0..3 {
+                    let angle = self.corner_angle(triangle, corner);
+                    tangents[triangle[corner]] += tangent * angle;
+                    bitangents[triangle[corner]] += bitangent * angle;
+                }
+            }
+        }
+
+        self.tangents = (0..count)
+            .map(|i| {
+                let (x, y, z) = self.normals.get(i).map_or((0.0, 0.0, 0.0), |n| n.normal);
+                let normal = Vector3::new(x, y, z);
+                // Orthogonal to the normal, like the shader expects
+                let tangent = tangents[i] - normal * normal.dot(&tangents[i]);
+                match tangent.try_normalize(NORM_EPS) {
+                    Some(t) => {
+                        let w = if normal.cross(&t).dot(&bitangents[i]) < 0.0 {
+                            -1.0
+                        } else {
+                            1.0
+                        };
+                        Tangent {
+                            tangent: (t.x, t.y, t.z, w),
+                        }
+                    }
+                    None => Tangent::default(),
+                }
+            })
+            .collect();
+    }
+
+    /// Interior angle of a triangle at one of its corners, zero if degenerate
+    fn corner_angle(&self, triangle: [usize; 3], corner: usize) -> Scalar {
+        let at = self.position(triangle[corner]);
+        let to_next = self.position(triangle[(corner + 1) % 3]) - at;
+        let to_prev = self.position(triangle[(corner + 2) % 3]) - at;
+        match (
+            to_next.try_normalize(NORM_EPS),
+            to_prev.try_normalize(NORM_EPS),
+        ) {
+            (Some(a), Some(b)) => a.dot(&b).clamp(-1.0, 1.0).acos(),
+            _ => 0.0,
+        }
+    }
+
+    /// Vertices at exactly the same position share a key
+    pub(crate) fn position_key(&self, vertex: usize) -> [u32; 3] {
+        let (x, y, z) = self.vertices[vertex].position;
+        // Adding zero turns -0.0 into 0.0
+        [
+            (x + 0.0).to_bits(),
+            (y + 0.0).to_bits(),
+            (z + 0.0).to_bits(),
+        ]
+    }
+
+    /// Rebuilds the vertices from one (vertex, normal) pair per triangle corner,
+    /// sharing the vertices whose corners agree on the normal
+    fn rebuild(&mut self, corners: &[(usize, Vector3)]) {
+        let mut shared: HashMap<(usize, [u32; 3]), u32> = HashMap::new();
+        let mut data = MeshData::default();
+        for &(vertex, normal) in corners {
+            let key = (
+                vertex,
+                [normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits()],
+            );
+            let index = *shared.entry(key).or_insert_with(|| {
+                let index = data.copy_vertex(self, vertex);
+                let normal = Normal {
+                    normal: (normal.x, normal.y, normal.z),
+                };
+                match data.normals.get_mut(index as usize) {
+                    Some(copied) => *copied = normal,
+                    None => data.normals.push(normal),
+                }
+                index
+            });
+            data.indices.push(index);
+        }
+        *self = data;
+    }
+
+    /// Appends a copy of a vertex of another mesh, returning its index. Normals
+    /// and colors are copied if `from` has them, so they stay one per vertex.
+    fn copy_vertex(&mut self, from: &MeshData, vertex: usize) -> u32 {
+        let index = self.vertex_count() as u32;
+        self.vertices.push(from.vertices[vertex]);
+        if !from.normals.is_empty() {
+            self.normals
+                .push(from.normals.get(vertex).copied().unwrap_or(Normal {
+                    normal: (0.0, 0.0, 0.0),
+                }));
+        }
+        self.tex_coords
+            .push(from.tex_coords.get(vertex).copied().unwrap_or_default());
+        self.tangents
+            .push(from.tangents.get(vertex).copied().unwrap_or_default());
+        if !from.colors.is_empty() {
+            self.colors
+                .push(from.colors.get(vertex).copied().unwrap_or_default());
+        }
+        index
+    }
+
+    /// All attributes of a vertex as bits, equal for identical vertices
+    fn attribute_bits(&self, vertex: usize) -> Vec<u32> {
+        let (x, y, z) = self.vertices[vertex].position;
+        let mut bits = vec![x.to_bits(), y.to_bits(), z.to_bits()];
+        if let Some(normal) = self.normals.get(vertex) {
+            let (x, y, z) = normal.normal;
+            bits.extend_from_slice(&[x.to_bits(), y.to_bits(), z.to_bits()]);
+        }
+        if let Some(tex_coord) = self.tex_coords.get(vertex) {
+            let (u, v) = tex_coord.tex_coords;
+            bits.extend_from_slice(&[u.to_bits(), v.to_bits()]);
+        }
+        if let Some(tangent) = self.tangents.get(vertex) {
+            let (x, y, z, w) = tangent.tangent;
+            bits.extend_from_slice(&[x.to_bits(), y.to_bits(), z.to_bits(), w.to_bits()]);
+        }
+        if let Some(color) = self.colors.get(vertex) {
+            let (r, g, b, a) = color.color;
+            bits.extend_from_slice(&[r.to_bits(), g.to_bits(), b.to_bits(), a.to_bits()]);
+        }
+        bits
+    }
+}
+
+/// Appends an attribute of another mesh to the one of a mesh with `count`
+/// vertices, `total` with the other's. A side without it gets default values.
+fn append_attribute<T: Copy + Default>(to: &mut Vec<T>, from: &[T], count: usize, total: usize) {
+    if to.is_empty() && !from.is_empty() {
+        to.resize(count, T::default());
+    }
+    to.extend_from_slice(from);
+    if !to.is_empty() {
+        to.resize(total, T::default());
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Cube from -1 to 1 with one vertex per corner, facing outwards.
+    /// Vertex `i` has x, y and z set by bits 0, 1 and 2 of `i`.
+    pub(crate) fn cube() -> MeshData {
+        let vertices = (0..8)
+            .map(|i| {
+                let coordinate = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+                Vertex {
+                    position: (coordinate(1), coordinate(2), coordinate(4)),
+                }
+            })
+            .collect();
+        let faces = [
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+        ];
+        let indices = faces
+            .iter()
+            .flat_map(|&[a, b, c, d]| vec![a, b, c, a, c, d])
+            .collect();
+        let normals = vec![
+            Normal {
+                normal: (0.0, 0.0, 0.0)
+            };
+            8
+        ];
+        MeshData::new(vertices, normals, indices)
+    }
+
+    fn normal(data: &MeshData, vertex: usize) -> Vector3 {
+        let (x, y, z) = data.normals[vertex].normal;
+        Vector3::new(x, y, z)
+    }
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        (a - b).norm() < 1e-5
+    }
+
+    #[test]
+    fn flat_normals_split_every_face() {
+        let mut data = cube();
+        data.flat_normals();
+        assert_eq!(data.vertex_count(), 24);
+        assert_eq!(data.triangle_count(), 12);
+        for triangle in data.triangles() {
+            let face = data.face_normal(triangle).normalize();
+            assert!(face.iter().filter(|x| x.abs() > 0.5).count() == 1);
+            for &vertex in &triangle {
+                assert!(close(normal(&data, vertex), face));
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_normals_keep_edges_sharper_than_the_crease_angle() {
+        let mut data = cube();
+        data.smooth_normals(PI / 3.0);
+        assert_eq!(data.vertex_count(), 24);
+        for triangle in data.triangles() {
+            let face = data.face_normal(triangle).normalize();
+            assert!(close(normal(&data, triangle[0]), face));
+        }
+
+        let mut data = cube();
+        data.smooth_normals(PI);
+        assert_eq!(data.vertex_count(), 8);
+        for vertex in 0..8 {
+            let outwards = data.position(vertex).normalize();
+            assert!(close(normal(&data, vertex), outwards));
+        }
+    }
+
+    /// Two quads side by side in the xy plane facing +z, the texture of the
+    /// right one mirrored along u so both meet at u = 1
+    fn mirrored_quads() -> MeshData {
+        let positions = [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 1.0),
+            (0.0, 1.0),
+            (2.0, 0.0),
+            (2.0, 1.0),
+        ];
+        let mut data = MeshData::new(
+            positions
+                .iter()
+                .map(|&(x, y)| Vertex {
+                    position: (x, y, 0.0),
+                })
+                .collect(),
+            vec![
+                Normal {
+                    normal: (0.0, 0.0, 1.0)
+                };
+                6
+            ],
+            vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2],
+        );
+        data.tex_coords = positions
+            .iter()
+            .map(|&(x, y)| TexCoord {
+                tex_coords: (if x > 1.0 { 2.0 - x } else { x }, y),
+            })
+            .collect();
+        data
+    }
+
+    #[test]
+    fn tangents_follow_the_texture_mapping() {
+        let mut data = mirrored_quads();
+        data.indices.truncate(6);
+        data.generate_tangents();
+        for vertex in 0..4 {
+            assert_eq!(data.tangents[vertex].tangent, (1.0, 0.0, 0.0, 1.0));
+        }
+
+        // Flipping v flips the handedness, not the tangent
+        for tex_coord in &mut data.tex_coords {
+            tex_coord.tex_coords.1 = 1.0 - tex_coord.tex_coords.1;
+        }
+        data.generate_tangents();
+        for vertex in 0..4 {
+            assert_eq!(data.tangents[vertex].tangent, (1.0, 0.0, 0.0, -1.0));
+        }
+    }
+
+    #[test]
+    fn tangents_split_vertices_on_mirror_seams() {
+        let mut data = mirrored_quads();
+        data.generate_tangents();
+
+        // The two vertices on the seam get a copy for the mirrored side
+        assert_eq!(data.vertex_count(), 8);
+        assert_eq!(data.normals.len(), 8);
+        assert_eq!(data.tex_coords.len(), 8);
+        let triangles: Vec<[usize; 3]> = data.triangles().collect();
+        for (face, triangle) in triangles.iter().enumerate() {
+            let expected = if face < 2 {
+                (1.0, 0.0, 0.0, 1.0)
+            } else {
+                (-1.0, 0.0, 0.0, -1.0)
+            };
+            for &vertex in triangle {
+                assert_eq!(data.tangents[vertex].tangent, expected);
+            }
+        }
+    }
+
+    /// The mesh with its own three vertices for every triangle
+    fn triangle_soup(data: &MeshData) -> MeshData {
+        let mut soup = MeshData::default();
+        for &index in &data.indices {
+            let copy = soup.copy_vertex(data, index as usize);
+            soup.indices.push(copy);
+        }
+        soup
+    }
+
+    #[test]
+    fn weld_joins_corners_at_the_same_position() {
+        let mut data = triangle_soup(&cube());
+        assert_eq!(data.vertex_count(), 36);
+        assert_eq!(data.weld(0.0), 28);
+        assert_eq!(data.vertex_count(), 8);
+        assert_eq!(data.triangle_count(), 12);
+        assert!(data.stats().is_closed());
+
+        let mut data = triangle_soup(&cube());
+        for (i, vertex) in data.vertices.iter_mut().enumerate() {
+            vertex.position.0 += i as f32 * 1e-5;
+        }
+        assert_eq!(data.weld(0.0), 0);
+        assert_eq!(data.weld(1e-3), 28);
+
+        // Vertices on hard edges differ in their normals and stay apart
+        let mut data = cube();
+        data.flat_normals();
+        assert_eq!(data.weld(1e-3), 0);
+    }
+
+    /// A flat grid of `n` by `n` unit squares facing +z
+    pub(crate) fn grid(n: usize) -> MeshData {
+        let at = |x: usize, y: usize| (y * (n + 1) + x) as u32;
+        let vertices: Vec<Vertex> = (0..=n)
+            .flat_map(|y| {
+                (0..=n).map(move |x| Vertex {
+                    position: (x as f32, y as f32, 0.0),
+                })
+            })
+            .collect();
+        let mut indices = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                let quad = [at(x, y), at(x + 1, y), at(x + 1, y + 1), at(x, y + 1)];
+                indices.extend_from_slice(&[quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+            }
+        }
+        let normals = vec![
+            Normal {
+                normal: (0.0, 0.0, 1.0)
+            };
+            vertices.len()
+        ];
+        MeshData::new(vertices, normals, indices)
+    }
+
+    #[test]
+    fn split_respects_the_vertex_limit() {
+        assert_eq!(cube().split().len(), 1);
+
+        let data = grid(300);
+        assert!(data.vertex_count() > MAX_VERTICES);
+        let parts = data.split();
+        assert!(parts.len() >= 2);
+        for part in &parts {
+            assert!(part.vertex_count() <= MAX_VERTICES);
+            assert_eq!(part.normals.len(), part.vertex_count());
+            assert_eq!(part.tex_coords.len(), part.vertex_count());
+        }
+        let triangles: usize = parts.iter().map(MeshData::triangle_count).sum();
+        assert_eq!(triangles, data.triangle_count());
+        let area: Scalar = parts.iter().map(|part| part.stats().area).sum();
+        assert!((area - 300.0 * 300.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn merge_bakes_in_transforms_and_fills_in_attributes() {
+        let mut colored = cube();
+        colored.smooth_normals(PI);
+        colored.colors = vec![
+            VertexColor {
+                color: (1.0, 0.0, 0.0, 1.0)
+            };
+            8
+        ];
+        let mut plain = cube();
+        plain.normals.clear();
+
+        let merged = MeshData::merge(&[
+            (
+                &colored,
+                Matrix4::new_translation(&Vector3::new(3.0, 0.0, 0.0)),
+            ),
+            (&plain, Matrix4::new_scaling(0.5)),
+        ]);
+        assert_eq!((merged.vertex_count(), merged.triangle_count()), (16, 24));
+        assert_eq!(merged.normals.len(), 16);
+        assert_eq!(merged.tex_coords.len(), 16);
+        assert_eq!(merged.tangents.len(), 16);
+        assert_eq!(merged.colors.len(), 16);
+        // The part without normals got smooth ones, the part without colors is white
+        for vertex in 0..16 {
+            assert!((normal(&merged, vertex).norm() - 1.0).abs() < 1e-5);
+        }
+        assert_eq!(merged.colors[0], colored.colors[0]);
+        assert_eq!(merged.colors[8], VertexColor::default());
+
+        assert_eq!(
+            merged.bounds(),
+            Some((Vector3::new(-0.5, -1.0, -1.0), Vector3::new(4.0, 1.0, 1.0)))
+        );
+        assert!((merged.stats().volume - 9.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn normalize_fits_the_cube_from_minus_one_to_one() {
+        let mut data = cube();
+        data.transform(
+            &(Matrix4::new_translation(&Vector3::new(5.0, -2.0, 1.0))
+                * Matrix4::new_nonuniform_scaling(&Vector3::new(4.0, 2.0, 1.0))),
+        );
+        data.normalize();
+        let (min, max) = data.bounds().unwrap();
+        assert!(close(min, Vector3::new(-1.0, -0.5, -0.25)));
+        assert!(close(max, Vector3::new(1.0, 0.5, 0.25)));
+    }
+
+    #[test]
+    fn stats_measure_area_and_volume() {
+        let stats = cube().stats();
+        assert_eq!((stats.vertices, stats.triangles), (8, 12));
+        assert_eq!(
+            (stats.min, stats.max),
+            (Vector3::repeat(-1.0), Vector3::repeat(1.0))
+        );
+        assert!((stats.area - 24.0).abs() < 1e-4);
+        assert!((stats.volume - 8.0).abs() < 1e-4);
+        assert!(stats.is_closed());
+
+        // Vertices split along hard edges don't open the surface
+        let mut data = cube();
+        data.flat_normals();
+        assert!(data.stats().is_closed());
+
+        let mut open = cube();
+        open.indices.truncate(30);
+        let stats = open.stats();
+        assert_eq!(stats.boundary_edges, 4);
+        assert!(stats.is_manifold() && !stats.is_closed());
+
+        let mut flipped = cube();
+        flipped.indices[..6].reverse();
+        let stats = flipped.stats();
+        assert_eq!(stats.flipped_edges, 4);
+        assert!(!stats.is_manifold());
+    }
+}
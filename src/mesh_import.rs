@@ -0,0 +1,752 @@
+use crate::export::MeshFormat;
+use crate::{
+    srgb_to_linear, Display, Error, Material, Mesh, MeshData, Normal, PbrMaterial, Scalar,
+    SceneObject, TexCoord, Vertex, VertexColor, PI,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::SplitAsciiWhitespace;
+
+// Like the exporters, the importers mirror geometry along X and reverse the
+// winding, turning right-handed files into the renderer's coordinates.
+
+/// STL has no vertex normals. Triangles meeting at a sharper angle than this
+/// keep a hard edge, flatter ones are smoothed.
+pub const STL_CREASE_ANGLE: Scalar = PI / 6.0;
+
+/// Loads an STL or PLY file into an object, or several if the mesh has to be
+/// `split`. Neither format has materials, so the objects get a white PBR
+/// material that shows vertex colors as they are.
+pub fn import_mesh<P: AsRef<Path>>(display: &Display, path: P) -> Result<Vec<SceneObject>, Error> {
+    let mut objects = Vec::new();
+    for part in load_mesh(path)?.split() {
+        objects.push(imported_object(Rc::new(Mesh::from_data(display, &part)?)));
+    }
+    Ok(objects)
+}
+
+/// An object drawing a mesh loaded with `load_mesh`, with the material `import_mesh` uses
+pub(crate) fn imported_object(mesh: Rc<Mesh>) -> SceneObject {
+    let mut object = SceneObject::from_mesh(mesh);
+    object.set_material(Material::Pbr(PbrMaterial::default()));
+    object
+}
+
+/// Reads an STL or PLY file, telling them apart by the extension
+pub fn load_mesh<P: AsRef<Path>>(path: P) -> Result<MeshData, Error> {
+    let path = path.as_ref();
+    let in_file = |err: Error| match err {
+        Error::Asset(source) => Error::Asset(format!("{}: {}", path.display(), source).into()),
+        err => err,
+    };
+    let read = match MeshFormat::from_path(path) {
+        Some(MeshFormat::Stl { .. }) => read_stl,
+        Some(MeshFormat::Ply { .. }) => read_ply,
+        Some(MeshFormat::Obj) => return Err(in_file(invalid("OBJ files can't be imported"))),
+        None => return Err(in_file(invalid("unknown mesh format"))),
+    };
+    let bytes = fs::read(path).map_err(|err| in_file(Error::Asset(Box::new(err))))?;
+    read(&bytes).map_err(in_file)
+}
+
+/// Reads an ASCII or binary STL file. STL stores every triangle with its own
+/// corners, corners at the same position are welded into one vertex and
+/// triangles collapsing in the process are dropped. Normals are computed with
+/// `STL_CREASE_ANGLE`, the facet normals of the file are ignored.
+pub fn read_stl(bytes: &[u8]) -> Result<MeshData, Error> {
+    // Binary files may start with "solid" as well, their size gives them away
+    let binary_size = bytes
+        .get(80..84)
+        .map(|count| 84 + 50 * u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as u64);
+    let corners = if binary_size == Some(bytes.len() as u64) {
+        read_stl_binary(bytes)
+    } else if bytes.trim_ascii_start().starts_with(b"solid") {
+        read_stl_ascii(bytes)?
+    } else if binary_size.is_some() {
+        return Err(invalid("binary STL with a wrong size, it may be truncated"));
+    } else {
+        return Err(invalid("not an STL file"));
+    };
+
+    let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut data = MeshData::default();
+    for triangle in corners.chunks_exact(3) {
+        let mut indices = [0; 3];
+        for (index, &[x, y, z]) in indices.iter_mut().zip(triangle) {
+            // Adding zero turns -0.0 into 0.0
+            let key = [
+                (x + 0.0).to_bits(),
+                (y + 0.0).to_bits(),
+                (z + 0.0).to_bits(),
+            ];
+            *index = match welded.get(&key) {
+                Some(&index) => index,
+                None => {
+                    let index = data.vertices.len() as u32;
+                    data.vertices.push(Vertex {
+                        position: (-x, y, z),
+                    });
+                    welded.insert(key, index);
+                    index
+                }
+            };
+        }
+        let [a, b, c] = indices;
+        if a != b && b != c && c != a {
+            data.indices.extend_from_slice(&[a, c, b]);
+        }
+    }
+
+    // The normals are all computed, vertices only used by degenerate triangles dropped
+    data = MeshData::new(data.vertices, Vec::new(), data.indices);
+    data.remove_unused_vertices();
+    data.smooth_normals(STL_CREASE_ANGLE);
+    Ok(data)
+}
+
+/// The corners of all triangles, three per triangle
+fn read_stl_binary(bytes: &[u8]) -> Vec<[Scalar; 3]> {
+    let float =
+        |at: usize| Scalar::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    let mut corners = Vec::new();
+    for triangle in (84..bytes.len()).step_by(50) {
+        // The normal comes first, two unused bytes last
+        for corner in 1..4 {
+            let at = triangle + corner * 12;
+            corners.push([float(at), float(at + 4), float(at + 8)]);
+        }
+    }
+    corners
+}
+
+fn read_stl_ascii(bytes: &[u8]) -> Result<Vec<[Scalar; 3]>, Error> {
+    let text = std::str::from_utf8(bytes).map_err(|_| invalid("ASCII STL that isn't text"))?;
+    let mut tokens = text.split_ascii_whitespace();
+    let mut corners = Vec::new();
+    while let Some(token) = tokens.next() {
+        if token == "vertex" {
+            let mut corner = [0.0; 3];
+            for value in corner.iter_mut() {
+                *value = tokens
+                    .next()
+                    .and_then(|token| token.parse().ok())
+                    .ok_or_else(|| invalid("ASCII STL with a broken vertex"))?;
+            }
+            corners.push(corner);
+        }
+    }
+    if corners.len() % 3 != 0 {
+        return Err(invalid("ASCII STL with a facet that isn't a triangle"));
+    }
+    // Also catches binary files starting with "solid" that are too short
+    if !text.contains("endsolid") {
+        return Err(invalid("ASCII STL without endsolid, it may be truncated"));
+    }
+    Ok(corners)
+}
+
+/// Reads an ASCII or binary (little or big endian) PLY file: positions,
+/// normals, texture coordinates as `s`/`t` or `u`/`v`, and vertex colors as
+/// `red`, `green`, `blue` and `alpha`. Integer colors are taken as sRGB,
+/// float colors as linear.
+/// Faces with more than three corners are split into triangle fans. Missing
+/// normals are computed when there are faces, tangents are generated for
+/// texture coordinates.
+pub fn read_ply(bytes: &[u8]) -> Result<MeshData, Error> {
+    let (header, body) = split_header(bytes)?;
+    let mut reader = match header.format {
+        PlyFormat::Ascii => {
+            let text =
+                std::str::from_utf8(body).map_err(|_| invalid("ASCII PLY that isn't text"))?;
+            PlyReader::Ascii(text.split_ascii_whitespace())
+        }
+        PlyFormat::Binary { big_endian } => PlyReader::Binary {
+            bytes: body,
+            big_endian,
+        },
+    };
+
+    let vertex = header.elements.iter().find(|e| e.name == "vertex");
+    let vertex = vertex.ok_or_else(|| invalid("PLY without vertices"))?;
+    let has = |names: &[&str]| {
+        names
+            .iter()
+            .all(|name| vertex.properties.iter().any(|p| p.name() == *name))
+    };
+    let uv_names = if has(&["s", "t"]) {
+        Some(["s", "t"])
+    } else if has(&["u", "v"]) {
+        Some(["u", "v"])
+    } else if has(&["texture_u", "texture_v"]) {
+        Some(["texture_u", "texture_v"])
+    } else {
+        None
+    };
+    let has_normals = has(&["nx", "ny", "nz"]);
+    let has_colors = has(&["red", "green", "blue"]);
+    // Integer colors are sRGB bytes or words, float colors are already linear
+    let srgb_colors = vertex.properties.iter().any(|property| match property {
+        PlyProperty::Scalar { name, ty } => name == "red" && ty.max().is_some(),
+        PlyProperty::List { .. } => false,
+    });
+
+    let mut data = MeshData::default();
+    let mut values: HashMap<&str, Scalar> = HashMap::new();
+    for element in &header.elements {
+        for _ in 0..element.count {
+            values.clear();
+            for property in &element.properties {
+                match property {
+                    PlyProperty::Scalar { name, ty } => {
+                        let value = reader.read(*ty)?;
+                        // Integer colors span their type's range
+                        let value = match (name.as_str(), ty.max()) {
+                            ("red" | "green" | "blue" | "alpha", Some(max)) => value / max,
+                            _ => value,
+                        };
+                        values.insert(name, value as Scalar);
+                    }
+                    PlyProperty::List {
+                        name,
+                        count_ty,
+                        item_ty,
+                    } => {
+                        let count = reader.read(*count_ty)? as usize;
+                        // The count may be garbage, the data runs out before it is reached
+                        let mut items = Vec::with_capacity(count.min(64));
+                        for _ in 0..count {
+                            items.push(reader.read(*item_ty)?);
+                        }
+                        let is_face = element.name == "face"
+                            && (name == "vertex_indices" || name == "vertex_index");
+                        if is_face {
+                            add_polygon(&mut data, &items, vertex.count)?;
+                        }
+                    }
+                }
+            }
+
+            if element.name == "vertex" {
+                let value = |name: &str| values.get(name).copied().unwrap_or(0.0);
+                data.vertices.push(Vertex {
+                    position: (-value("x"), value("y"), value("z")),
+                });
+                data.normals.push(Normal {
+                    normal: (-value("nx"), value("ny"), value("nz")),
+                });
+                let (s, t) = uv_names.map_or((0.0, 0.0), |[s, t]| (value(s), value(t)));
+                data.tex_coords.push(TexCoord {
+                    tex_coords: (s, 1.0 - t),
+                });
+                if has_colors {
+                    let alpha = values.get("alpha").copied().unwrap_or(1.0);
+                    let (mut r, mut g, mut b) = (value("red"), value("green"), value("blue"));
+                    if srgb_colors {
+                        r = srgb_to_linear(r);
+                        g = srgb_to_linear(g);
+                        b = srgb_to_linear(b);
+                    }
+                    data.colors.push(VertexColor {
+                        color: (r, g, b, alpha),
+                    });
+                }
+            }
+        }
+    }
+
+    data.tangents = vec![Default::default(); data.vertex_count()];
+    // Point clouds keep their vertices, computing normals drops all unused ones
+    if !has_normals && !data.indices.is_empty() {
+        data.smooth_normals(PI);
+    }
+    if uv_names.is_some() {
+        data.generate_tangents();
+    }
+    Ok(data)
+}
+
+/// Adds a polygon as a triangle fan, with the winding reversed for the mirroring
+fn add_polygon(data: &mut MeshData, corners: &[f64], vertex_count: usize) -> Result<(), Error> {
+    let mut indices = Vec::with_capacity(corners.len());
+    for &corner in corners {
+        if corner < 0.0 || corner >= vertex_count as f64 {
+            return Err(invalid(format!(
+                "PLY face with a bad vertex index {}",
+                corner
+            )));
+        }
+        indices.push(corner as u32);
+    }
+    for i in 2..indices.len() {
+        data.indices
+            .extend_from_slice(&[indices[0], indices[i], indices[i - 1]]);
+    }
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PlyFormat {
+    Ascii,
+    Binary { big_endian: bool },
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PlyType {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<Self, Error> {
+        Ok(match name {
+            "char" | "int8" => PlyType::Char,
+            "uchar" | "uint8" => PlyType::UChar,
+            "short" | "int16" => PlyType::Short,
+            "ushort" | "uint16" => PlyType::UShort,
+            "int" | "int32" => PlyType::Int,
+            "uint" | "uint32" => PlyType::UInt,
+            "float" | "float32" => PlyType::Float,
+            "double" | "float64" => PlyType::Double,
+            _ => return Err(invalid(format!("PLY with an unknown type {}", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::Char | PlyType::UChar => 1,
+            PlyType::Short | PlyType::UShort => 2,
+            PlyType::Int | PlyType::UInt | PlyType::Float => 4,
+            PlyType::Double => 8,
+        }
+    }
+
+    /// The largest value of integer types
+    fn max(self) -> Option<f64> {
+        match self {
+            PlyType::Char => Some(i8::MAX as f64),
+            PlyType::UChar => Some(u8::MAX as f64),
+            PlyType::Short => Some(i16::MAX as f64),
+            PlyType::UShort => Some(u16::MAX as f64),
+            PlyType::Int => Some(i32::MAX as f64),
+            PlyType::UInt => Some(u32::MAX as f64),
+            PlyType::Float | PlyType::Double => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PlyProperty {
+    Scalar {
+        name: String,
+        ty: PlyType,
+    },
+    List {
+        name: String,
+        count_ty: PlyType,
+        item_ty: PlyType,
+    },
+}
+
+impl PlyProperty {
+    fn name(&self) -> &str {
+        match self {
+            PlyProperty::Scalar { name, .. } | PlyProperty::List { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+struct PlyHeader {
+    format: PlyFormat,
+    elements: Vec<PlyElement>,
+}
+
+/// Parses the header, returning it and the data after it
+fn split_header(bytes: &[u8]) -> Result<(PlyHeader, &[u8]), Error> {
+    let marker = b"end_header";
+    let end = bytes
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .ok_or_else(|| invalid("not a PLY file"))?;
+    let header =
+        std::str::from_utf8(&bytes[..end]).map_err(|_| invalid("PLY header isn't text"))?;
+    // The body starts after the line break ending the marker's line
+    let body = &bytes[end..];
+    let body = match body.iter().position(|&byte| byte == b'\n') {
+        Some(newline) => &body[newline + 1..],
+        None => &[],
+    };
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(invalid("not a PLY file"));
+    }
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    let broken = || invalid("broken PLY header");
+    for line in lines {
+        let words: Vec<&str> = line.split_ascii_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _] => {
+                format = Some(match *name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::Binary { big_endian: false },
+                    "binary_big_endian" => PlyFormat::Binary { big_endian: true },
+                    _ => return Err(invalid(format!("PLY with an unknown format {}", name))),
+                })
+            }
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| broken())?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, item_ty, name] => elements
+                .last_mut()
+                .ok_or_else(broken)?
+                .properties
+                .push(PlyProperty::List {
+                    name: name.to_string(),
+                    count_ty: PlyType::parse(count_ty)?,
+                    item_ty: PlyType::parse(item_ty)?,
+                }),
+            ["property", ty, name] => {
+                elements
+                    .last_mut()
+                    .ok_or_else(broken)?
+                    .properties
+                    .push(PlyProperty::Scalar {
+                        name: name.to_string(),
+                        ty: PlyType::parse(ty)?,
+                    })
+            }
+            [] | ["comment", ..] | ["obj_info", ..] => (),
+            _ => return Err(broken()),
+        }
+    }
+
+    let format = format.ok_or_else(broken)?;
+    Ok((PlyHeader { format, elements }, body))
+}
+
+enum PlyReader<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl PlyReader<'_> {
+    /// The next value, as f64 which holds all PLY types exactly
+    fn read(&mut self, ty: PlyType) -> Result<f64, Error> {
+        let truncated = || invalid("PLY data ends early");
+        match self {
+            PlyReader::Ascii(tokens) => {
+                let token = tokens.next().ok_or_else(truncated)?;
+                token
+                    .parse()
+                    .map_err(|_| invalid(format!("PLY with a bad number {}", token)))
+            }
+            PlyReader::Binary { bytes, big_endian } => {
+                if bytes.len() < ty.size() {
+                    return Err(truncated());
+                }
+                let (value, rest) = bytes.split_at(ty.size());
+                *bytes = rest;
+                // Little endian from here on
+                let mut le = [0u8; 8];
+                le[..value.len()].copy_from_slice(value);
+                if *big_endian {
+                    le[..value.len()].reverse();
+                }
+                let [b0, b1, b2, b3, ..] = le;
+                Ok(match ty {
+                    PlyType::Char => b0 as i8 as f64,
+                    PlyType::UChar => b0 as f64,
+                    PlyType::Short => i16::from_le_bytes([b0, b1]) as f64,
+                    PlyType::UShort => u16::from_le_bytes([b0, b1]) as f64,
+                    PlyType::Int => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    PlyType::UInt => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    PlyType::Float => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    PlyType::Double => f64::from_le_bytes(le),
+                })
+            }
+        }
+    }
+}
+
+fn invalid<S: Into<String>>(message: S) -> Error {
+    Error::Asset(message.into().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector3;
+
+    const QUAD_HEADER: &str = "ply
+format {} 1.0
+comment a unit square with one color per corner
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    const QUAD_POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    const QUAD_COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [128, 128, 128]];
+
+    fn ascii_quad() -> Vec<u8> {
+        let mut text = QUAD_HEADER.replace("{}", "ascii");
+        for ([x, y, z], [r, g, b]) in QUAD_POSITIONS.iter().zip(&QUAD_COLORS) {
+            text += &format!("{} {} {} {} {} {}\n", x, y, z, r, g, b);
+        }
+        text += "4 0 1 2 3\n";
+        text.into_bytes()
+    }
+
+    fn binary_quad(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian {
+            "binary_big_endian"
+        } else {
+            "binary_little_endian"
+        };
+        let mut bytes = QUAD_HEADER.replace("{}", format).into_bytes();
+        for (position, color) in QUAD_POSITIONS.iter().zip(&QUAD_COLORS) {
+            for value in position {
+                if big_endian {
+                    bytes.extend_from_slice(&value.to_be_bytes());
+                } else {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            bytes.extend_from_slice(color);
+        }
+        bytes.push(4);
+        for index in 0..4i32 {
+            if big_endian {
+                bytes.extend_from_slice(&index.to_be_bytes());
+            } else {
+                bytes.extend_from_slice(&index.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// The vertex at a position of the file, after mirroring
+    fn find(data: &MeshData, [x, y, z]: [f32; 3]) -> usize {
+        (0..data.vertex_count())
+            .find(|&vertex| data.vertices[vertex].position == (-x, y, z))
+            .unwrap()
+    }
+
+    fn assert_faces_up(data: &MeshData) {
+        for triangle in data.triangles() {
+            let normal = data.face_normal(triangle).normalize();
+            assert!((normal - Vector3::z()).norm() < 1e-6);
+            for &vertex in &triangle {
+                assert_eq!(data.normals[vertex].normal, (0.0, 0.0, 1.0));
+            }
+        }
+    }
+
+    fn check_quad(data: &MeshData) {
+        assert_eq!(data.vertex_count(), 4);
+        // The quad is split into a fan of two triangles
+        assert_eq!(data.triangle_count(), 2);
+        assert_faces_up(data);
+        let gray = srgb_to_linear(128.0 / 255.0);
+        let expected = [
+            (1.0, 0.0, 0.0, 1.0),
+            (0.0, 1.0, 0.0, 1.0),
+            (0.0, 0.0, 1.0, 1.0),
+            (gray, gray, gray, 1.0),
+        ];
+        for (&position, &color) in QUAD_POSITIONS.iter().zip(&expected) {
+            assert_eq!(data.colors[find(data, position)].color, color);
+        }
+    }
+
+    #[test]
+    fn reads_ascii_ply() {
+        check_quad(&read_ply(&ascii_quad()).unwrap());
+    }
+
+    #[test]
+    fn reads_binary_ply() {
+        check_quad(&read_ply(&binary_quad(false)).unwrap());
+        check_quad(&read_ply(&binary_quad(true)).unwrap());
+    }
+
+    #[test]
+    fn float_colors_are_linear() {
+        let text = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property float red
+property float green
+property float blue
+property float alpha
+element face 1
+property list uchar uint vertex_indices
+end_header
+0 0 0 0.5 0.25 1 0.75
+1 0 0 0.5 0.25 1 0.75
+0 1 0 0.5 0.25 1 0.75
+3 0 1 2
+";
+        let data = read_ply(text.as_bytes()).unwrap();
+        for color in &data.colors {
+            assert_eq!(color.color, (0.5, 0.25, 1.0, 0.75));
+        }
+    }
+
+    #[test]
+    fn reads_point_clouds() {
+        let text = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+end_header
+0 0 0
+1 0 0
+0 1 0
+";
+        let data = read_ply(text.as_bytes()).unwrap();
+        assert_eq!((data.vertex_count(), data.triangle_count()), (3, 0));
+        assert_eq!(data.normals.len(), 3);
+        find(&data, [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn reads_ascii_stl() {
+        let text = "solid triangle
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid triangle
+";
+        let data = read_stl(text.as_bytes()).unwrap();
+        assert_eq!((data.vertex_count(), data.triangle_count()), (3, 1));
+        assert_faces_up(&data);
+        find(&data, [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn stl_drops_degenerate_triangles() {
+        let text = "solid degenerate
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 5 5 5
+      vertex 5 5 5
+      vertex 6 5 5
+    endloop
+  endfacet
+endsolid degenerate
+";
+        let data = read_stl(text.as_bytes()).unwrap();
+        assert_eq!((data.vertex_count(), data.triangle_count()), (3, 1));
+        assert_eq!(data.stats().vertices, 3);
+    }
+
+    fn binary_stl() -> Vec<u8> {
+        // Binary files starting with "solid" must not be taken for ASCII
+        let mut bytes = b"solid but binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        let triangles = [[0, 1, 2], [0, 2, 3]];
+        for triangle in &triangles {
+            bytes.extend_from_slice(&[0; 12]);
+            for &corner in triangle {
+                for value in &QUAD_POSITIONS[corner] {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            bytes.extend_from_slice(&[0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_binary_stl() {
+        let data = read_stl(&binary_stl()).unwrap();
+        // Corners at the same position are welded
+        assert_eq!((data.vertex_count(), data.triangle_count()), (4, 2));
+        assert_faces_up(&data);
+        for &position in &QUAD_POSITIONS {
+            find(&data, position);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert!(read_stl(b"").is_err());
+        assert!(read_stl(b"not a mesh at all").is_err());
+        assert!(read_stl(b"solid x\nfacet normal 0 0 1\nouter loop\nvertex 0 0").is_err());
+        assert!(read_stl(b"solid x\nvertex 0 0 0\nvertex 1 0 0\nendsolid x\n").is_err());
+        assert!(read_stl(b"solid x\nfacet normal 0 0 1\n").is_err());
+        let stl = binary_stl();
+        assert!(read_stl(&stl[..stl.len() - 1]).is_err());
+        assert!(read_stl(&stl[..100]).is_err());
+
+        assert!(read_ply(b"").is_err());
+        assert!(read_ply(b"ply\nformat ascii 1.0\nelement vertex 1\n").is_err());
+        for big_endian in [false, true] {
+            let ply = binary_quad(big_endian);
+            for end in [ply.len() - 1, ply.len() - 17, QUAD_HEADER.len() + 20] {
+                assert!(read_ply(&ply[..end]).is_err());
+            }
+        }
+        let ascii = String::from_utf8(ascii_quad()).unwrap();
+        let broken = [
+            ascii.replace("4 0 1 2 3", "4 0 1 2 4"),
+            ascii.replace("4 0 1 2 3", "4000000000 0 1 2 3"),
+            ascii.replace("0 1 0 128", "0 x 0 128"),
+            ascii.replace("uchar red", "quad red"),
+            ascii.replace("format ascii", "format binary_middle_endian"),
+            ascii.replace("element vertex 4", "element vertex"),
+            ascii.replace("element vertex", "element vertices"),
+        ];
+        for text in &broken {
+            assert!(read_ply(text.as_bytes()).is_err(), "{}", text);
+        }
+    }
+}
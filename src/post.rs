@@ -0,0 +1,543 @@
+use crate::error::{allocation, shader};
+use crate::tonemap::ToneMapping;
+use crate::{uniform, Display, Error, HdrTarget, Program, Scalar, Surface};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::program::ProgramCreationInput;
+use glium::texture::{MipmapsOption, Texture2d, Texture3d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, Uniforms};
+use glium::vertex::EmptyVertexAttributes;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Glow around everything brighter than `threshold`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bloom {
+    pub enabled: bool,
+    pub threshold: Scalar,
+    pub intensity: Scalar,
+    /// Blur passes over the half resolution highlights, each one widens the glow
+    pub iterations: u32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 1.0,
+            intensity: 0.5,
+            iterations: 3,
+        }
+    }
+}
+
+/// Remaps the colors through a 3D lookup table, see `load_cube_lut`
+#[derive(Clone)]
+pub struct ColorGrading {
+    pub enabled: bool,
+    pub lut: Option<Rc<Texture3d>>,
+    /// Blend between the original (0.0) and the graded (1.0) colors
+    pub strength: Scalar,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lut: None,
+            strength: 1.0,
+        }
+    }
+}
+
+/// Color fringes increasing towards the edges of the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChromaticAberration {
+    pub enabled: bool,
+    /// Offset of the red and blue channels at the screen edge, relative to the screen size
+    pub strength: Scalar,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.005,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sharpen {
+    pub enabled: bool,
+    pub strength: Scalar,
+}
+
+impl Default for Sharpen {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.3,
+        }
+    }
+}
+
+/// Darkens the corners of the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vignette {
+    pub enabled: bool,
+    pub strength: Scalar,
+    /// Distance from the center where the darkening starts, 1.0 is the screen edge
+    pub radius: Scalar,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.5,
+            radius: 0.6,
+        }
+    }
+}
+
+/// What happens between the HDR scene and the window. The effects run in the
+/// order of the fields, bloom before and all others after the tone mapping.
+#[derive(Clone, Default)]
+pub struct PostSettings {
+    pub bloom: Bloom,
+    pub tone_mapping: ToneMapping,
+    pub color_grading: ColorGrading,
+    pub chromatic_aberration: ChromaticAberration,
+    pub sharpen: Sharpen,
+    pub vignette: Vignette,
+    /// Fast approximate anti-aliasing, last so it smooths the edges of the final image
+    pub fxaa: bool,
+}
+
+#[derive(Copy, Clone)]
+enum Effect {
+    ColorGrading,
+    ChromaticAberration,
+    Sharpen,
+    Vignette,
+    Fxaa,
+}
+
+struct Programs {
+    tonemap: Program,
+    bright_pass: Program,
+    blur: Program,
+    color_grading: Program,
+    chromatic_aberration: Program,
+    sharpen: Program,
+    vignette: Program,
+    fxaa: Program,
+}
+
+/// Runs the post-processing chain. Effects are full screen passes that read one
+/// of two textures and write the other, the last one draws into the target.
+pub struct PostProcessor {
+    pub settings: PostSettings,
+    programs: Programs,
+    /// Tonemapped, display encoded colors
+    ping_pong: [Texture2d; 2],
+    /// Highlights at half resolution, blurred back and forth for the bloom
+    bloom: [Texture2d; 2],
+}
+
+impl PostProcessor {
+    pub fn new(display: &Display, settings: PostSettings) -> Result<Self, Error> {
+        let programs = Programs {
+            tonemap: crate::tonemap::define_tonemap_shaders(display)?,
+            bright_pass: fullscreen_program(
+                display,
+                include_str!(r"shaders/bright_pass_fragment.glsl"),
+                "bloom bright pass",
+            )?,
+            blur: fullscreen_program(display, include_str!(r"shaders/blur_fragment.glsl"), "blur")?,
+            color_grading: fullscreen_program(
+                display,
+                include_str!(r"shaders/color_grading_fragment.glsl"),
+                "color grading",
+            )?,
+            chromatic_aberration: fullscreen_program(
+                display,
+                include_str!(r"shaders/chromatic_aberration_fragment.glsl"),
+                "chromatic aberration",
+            )?,
+            sharpen: fullscreen_program(
+                display,
+                include_str!(r"shaders/sharpen_fragment.glsl"),
+                "sharpen",
+            )?,
+            vignette: fullscreen_program(
+                display,
+                include_str!(r"shaders/vignette_fragment.glsl"),
+                "vignette",
+            )?,
+            fxaa: fullscreen_program(display, include_str!(r"shaders/fxaa_fragment.glsl"), "FXAA")?,
+        };
+
+        let (ping_pong, bloom) = allocate(display, display.get_framebuffer_dimensions())?;
+        Ok(Self {
+            settings,
+            programs,
+            ping_pong,
+            bloom,
+        })
+    }
+
+    /// Tonemaps `hdr` into `target`, applying the enabled effects on the way
+    pub fn apply<S: Surface>(
+        &mut self,
+        display: &Display,
+        target: &mut S,
+        hdr: &HdrTarget,
+    ) -> Result<(), Error> {
+        if self.ping_pong[0].dimensions() != hdr.dimensions() {
+            let (ping_pong, bloom) = allocate(display, hdr.dimensions())?;
+            self.ping_pong = ping_pong;
+            self.bloom = bloom;
+        }
+
+        let bloom = self.settings.bloom;
+        if bloom.enabled {
+            self.render_bloom(display, hdr)?;
+        }
+
+        // Tone mapping, straight into the target if no effect follows
+        let tone_mapping = self.settings.tone_mapping;
+        let uniforms = uniform! {
+            hdr_color: nearest(hdr.color()),
+            bloom: linear(if bloom.enabled { &self.bloom[0] } else { hdr.color() }),
+            bloom_intensity: if bloom.enabled { bloom.intensity } else { 0.0 },
+            operator: tone_mapping.operator.index(),
+            exposure: tone_mapping.exposure,
+        };
+        let effects = self.effects();
+        if effects.is_empty() {
+            return draw_fullscreen(target, &self.programs.tonemap, &uniforms);
+        }
+        let mut framebuffer = SimpleFrameBuffer::new(display, &self.ping_pong[0])
+            .map_err(allocation("post-processing framebuffer"))?;
+        draw_fullscreen(&mut framebuffer, &self.programs.tonemap, &uniforms)?;
+
+        for (i, &effect) in effects.iter().enumerate() {
+            let source = &self.ping_pong[i % 2];
+            if i + 1 == effects.len() {
+                self.draw_effect(effect, source, target)?;
+            } else {
+                let mut framebuffer = SimpleFrameBuffer::new(display, &self.ping_pong[(i + 1) % 2])
+                    .map_err(allocation("post-processing framebuffer"))?;
+                self.draw_effect(effect, source, &mut framebuffer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The enabled effects after the tone mapping, in order
+    fn effects(&self) -> Vec<Effect> {
+        let settings = &self.settings;
+        let enabled = [
+            (
+                settings.color_grading.enabled && settings.color_grading.lut.is_some(),
+                Effect::ColorGrading,
+            ),
+            (
+                settings.chromatic_aberration.enabled,
+                Effect::ChromaticAberration,
+            ),
+            (settings.sharpen.enabled, Effect::Sharpen),
+            (settings.vignette.enabled, Effect::Vignette),
+            (settings.fxaa, Effect::Fxaa),
+        ];
+        enabled
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|&(_, effect)| effect)
+            .collect()
+    }
+
+    fn draw_effect<S: Surface>(
+        &self,
+        effect: Effect,
+        source: &Texture2d,
+        target: &mut S,
+    ) -> Result<(), Error> {
+        let settings = &self.settings;
+        let programs = &self.programs;
+        match effect {
+            Effect::ColorGrading => {
+                let lut = match &settings.color_grading.lut {
+                    Some(lut) => lut,
+                    None => return Ok(()),
+                };
+                let uniforms = uniform! {
+                    source: nearest(source),
+                    lut: lut
+                        .sampled()
+                        .minify_filter(MinifySamplerFilter::Linear)
+                        .magnify_filter(MagnifySamplerFilter::Linear),
+                    strength: settings.color_grading.strength,
+                };
+                draw_fullscreen(target, &programs.color_grading, &uniforms)
+            }
+            Effect::ChromaticAberration => {
+                let uniforms = uniform! {
+                    source: linear(source),
+                    strength: settings.chromatic_aberration.strength,
+                };
+                draw_fullscreen(target, &programs.chromatic_aberration, &uniforms)
+            }
+            Effect::Sharpen => {
+                let uniforms = uniform! {
+                    source: nearest(source),
+                    strength: settings.sharpen.strength,
+                };
+                draw_fullscreen(target, &programs.sharpen, &uniforms)
+            }
+            Effect::Vignette => {
+                let uniforms = uniform! {
+                    source: nearest(source),
+                    strength: settings.vignette.strength,
+                    radius: settings.vignette.radius,
+                };
+                draw_fullscreen(target, &programs.vignette, &uniforms)
+            }
+            Effect::Fxaa => {
+                let uniforms = uniform! {
+                    source: linear(source),
+                };
+                draw_fullscreen(target, &programs.fxaa, &uniforms)
+            }
+        }
+    }
+
+    /// Extracts the highlights into `bloom[0]` and blurs them there
+    fn render_bloom(&self, display: &Display, hdr: &HdrTarget) -> Result<(), Error> {
+        let framebuffer = |texture| {
+            SimpleFrameBuffer::new(display, texture).map_err(allocation("bloom framebuffer"))
+        };
+
+        let uniforms = uniform! {
+            source: linear(hdr.color()),
+            threshold: self.settings.bloom.threshold,
+        };
+        draw_fullscreen(
+            &mut framebuffer(&self.bloom[0])?,
+            &self.programs.bright_pass,
+            &uniforms,
+        )?;
+
+        for _ in 0..self.settings.bloom.iterations {
+            let passes = [
+                (&self.bloom[0], &self.bloom[1], [1.0f32, 0.0]),
+                (&self.bloom[1], &self.bloom[0], [0.0, 1.0]),
+            ];
+            for &(source, destination, direction) in passes.iter() {
+                let uniforms = uniform! {
+                    source: linear(source),
+                    direction: direction,
+                };
+                draw_fullscreen(
+                    &mut framebuffer(destination)?,
+                    &self.programs.blur,
+                    &uniforms,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+type Buffers = ([Texture2d; 2], [Texture2d; 2]);
+
+fn allocate(display: &Display, (width, height): (u32, u32)) -> Result<Buffers, Error> {
+    let texture = |format, width, height| {
+        Texture2d::empty_with_format(display, format, MipmapsOption::NoMipmap, width, height)
+            .map_err(allocation("post-processing buffer"))
+    };
+    let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+
+    Ok((
+        [
+            texture(UncompressedFloatFormat::U8U8U8U8, width, height)?,
+            texture(UncompressedFloatFormat::U8U8U8U8, width, height)?,
+        ],
+        [
+            texture(
+                UncompressedFloatFormat::F16F16F16F16,
+                half_width,
+                half_height,
+            )?,
+            texture(
+                UncompressedFloatFormat::F16F16F16F16,
+                half_width,
+                half_height,
+            )?,
+        ],
+    ))
+}
+
+fn nearest(texture: &Texture2d) -> Sampler<'_, Texture2d> {
+    texture
+        .sampled()
+        .minify_filter(MinifySamplerFilter::Nearest)
+        .magnify_filter(MagnifySamplerFilter::Nearest)
+}
+
+fn linear(texture: &Texture2d) -> Sampler<'_, Texture2d> {
+    texture
+        .sampled()
+        .minify_filter(MinifySamplerFilter::Linear)
+        .magnify_filter(MagnifySamplerFilter::Linear)
+}
+
+/// Compiles a full screen pass drawn with `fullscreen_vertex.glsl`. The shader
+/// output is written as is, the framebuffer does no sRGB conversion.
+pub(crate) fn fullscreen_program(
+    display: &Display,
+    f_shader: &str,
+    name: &'static str,
+) -> Result<Program, Error> {
+    Program::new(
+        display,
+        ProgramCreationInput::SourceCode {
+            vertex_shader: include_str!(r"shaders/fullscreen_vertex.glsl"),
+            tessellation_control_shader: None,
+            tessellation_evaluation_shader: None,
+            geometry_shader: None,
+            fragment_shader: f_shader,
+            transform_feedback_varyings: None,
+            outputs_srgb: true,
+            uses_point_size: false,
+        },
+    )
+    .map_err(shader(name))
+}
+
+/// Draws a single triangle covering the whole target
+pub(crate) fn draw_fullscreen<S: Surface, U: Uniforms>(
+    target: &mut S,
+    program: &Program,
+    uniforms: &U,
+) -> Result<(), Error> {
+    target.draw(
+        EmptyVertexAttributes { len: 3 },
+        NoIndices(PrimitiveType::TrianglesList),
+        program,
+        uniforms,
+        &Default::default(),
+    )?;
+    Ok(())
+}
+
+/// Reads a 3D LUT in the `.cube` format of Adobe and DaVinci Resolve
+pub fn load_cube_lut<P: AsRef<Path>>(display: &Display, path: P) -> Result<Texture3d, Error> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|err| Error::Asset(Box::new(err)))?;
+    let invalid = |message: String| Error::Asset(format!("{}: {}", path.display(), message).into());
+
+    let mut size = 0;
+    let mut entries = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("LUT_3D_SIZE") {
+            size = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|err| invalid(err.to_string()))?;
+        } else if line.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') {
+            let values = line
+                .split_whitespace()
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| invalid(err.to_string()))?;
+            match values[..] {
+                [r, g, b] => entries.push((r, g, b)),
+                _ => return Err(invalid(format!("expected three values in '{}'", line))),
+            }
+        }
+        // TITLE, DOMAIN_MIN and DOMAIN_MAX are ignored, the domain is taken as [0, 1]
+    }
+
+    if size < 2 || entries.len() != size * size * size {
+        return Err(invalid(format!(
+            "expected LUT_3D_SIZE {} with {} entries, found {}",
+            size,
+            size * size * size,
+            entries.len()
+        )));
+    }
+    lut_texture(display, size, entries)
+}
+
+/// A LUT that leaves the colors unchanged, a starting point for grading in code
+pub fn identity_lut(display: &Display, size: usize) -> Result<Texture3d, Error> {
+    lut_texture(display, size, identity_entries(size)?)
+}
+
+fn identity_entries(size: usize) -> Result<Vec<(f32, f32, f32)>, Error> {
+    if size < 2 {
+        return Err(Error::Asset(
+            format!("a LUT needs a size of at least 2, got {}", size).into(),
+        ));
+    }
+    let step = |i: usize| i as f32 / (size - 1) as f32;
+    let mut entries = Vec::with_capacity(size * size * size);
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                entries.push((step(r), step(g), step(b)));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Red changes fastest in `entries`, then green, then blue
+fn lut_texture(
+    display: &Display,
+    size: usize,
+    entries: Vec<(f32, f32, f32)>,
+) -> Result<Texture3d, Error> {
+    let data: Vec<Vec<Vec<(f32, f32, f32)>>> = entries
+        .chunks(size * size)
+        .map(|slice| slice.chunks(size).map(<[_]>::to_vec).collect())
+        .collect();
+    Texture3d::with_format(
+        display,
+        data,
+        UncompressedFloatFormat::F16F16F16,
+        MipmapsOption::NoMipmap,
+    )
+    .map_err(allocation("color grading LUT"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_entries_span_the_unit_cube() {
+        let entries = identity_entries(3).unwrap();
+        assert_eq!(entries.len(), 27);
+        assert_eq!(entries[0], (0.0, 0.0, 0.0));
+        // Red changes fastest, then green, then blue
+        assert_eq!(entries[1], (0.5, 0.0, 0.0));
+        assert_eq!(entries[3], (0.0, 0.5, 0.0));
+        assert_eq!(entries[9], (0.0, 0.0, 0.5));
+        assert_eq!(entries[26], (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn identity_lut_needs_two_entries_per_axis() {
+        assert!(identity_entries(0).is_err());
+        assert!(identity_entries(1).is_err());
+        assert_eq!(identity_entries(2).unwrap().len(), 8);
+    }
+}
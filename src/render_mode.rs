@@ -0,0 +1,182 @@
+use crate::error::shader;
+use crate::{
+    uniform, Camera, Color, Display, DrawParameters, Error, Instance, Material, Matrix4, Program,
+    Scalar, SceneObject, Surface, DARKGRAY, LIGHTGRAY, TEAL, VIOLET,
+};
+use glium::draw_parameters::{BackfaceCullingMode, Blend, DepthTest};
+use glium::vertex::VertexBufferSlice;
+
+/// World space length of the lines drawn along normals
+const NORMAL_LENGTH: Scalar = 0.05;
+
+/// How objects are drawn, for inspecting meshes. Set globally with
+/// `Shaders::render_mode` or per object with `SceneObject::render_mode`.
+/// Only the forward `render` knows the modes, the deferred path always shades.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// Lit by the object's material
+    #[default]
+    Shaded,
+    /// Only the triangle edges
+    Wireframe,
+    /// Triangle edges on top of the shaded object
+    WireframeOverShaded,
+    /// World space vertex normals as colors, with lines along them
+    VertexNormals,
+    /// World space face normals as flat colors, with lines along them
+    FaceNormals,
+    /// A checkerboard over the texture coordinates
+    UvChecker,
+    /// Distance from the camera, bright is close
+    Depth,
+    /// The material's color without lighting
+    Unlit,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 8] = [
+        RenderMode::Shaded,
+        RenderMode::Wireframe,
+        RenderMode::WireframeOverShaded,
+        RenderMode::VertexNormals,
+        RenderMode::FaceNormals,
+        RenderMode::UvChecker,
+        RenderMode::Depth,
+        RenderMode::Unlit,
+    ];
+
+    /// The mode after this one in `ALL`, wrapping around, for cycling through them
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// The `mode` uniform of `mode_fragment.glsl`
+    fn shader_mode(self) -> i32 {
+        match self {
+            RenderMode::Wireframe | RenderMode::WireframeOverShaded => 0,
+            RenderMode::VertexNormals => 1,
+            RenderMode::FaceNormals => 2,
+            RenderMode::UvChecker => 3,
+            RenderMode::Depth => 4,
+            RenderMode::Shaded | RenderMode::Unlit => 5,
+        }
+    }
+}
+
+pub(crate) struct ModePrograms {
+    fill: Program,
+    normals: Program,
+}
+
+impl ModePrograms {
+    pub fn new(display: &Display) -> Result<Self, Error> {
+        let v_shader = include_str!(r"shaders/mode_vertex.glsl");
+        Ok(Self {
+            fill: Program::from_source(
+                display,
+                v_shader,
+                include_str!(r"shaders/mode_fragment.glsl"),
+                Some(include_str!(r"shaders/mode_geometry.glsl")),
+            )
+            .map_err(shader("render modes"))?,
+            normals: Program::from_source(
+                display,
+                v_shader,
+                include_str!(r"shaders/normals_fragment.glsl"),
+                Some(include_str!(r"shaders/normals_geometry.glsl")),
+            )
+            .map_err(shader("normal lines"))?,
+        })
+    }
+}
+
+/// Draws instances of an object's mesh in one of the inspection modes. For
+/// `WireframeOverShaded` only the edges are drawn, over the already shaded object.
+pub(crate) fn draw_mode<S: Surface>(
+    frame: &mut S,
+    object: &SceneObject,
+    instances: &VertexBufferSlice<'_, Instance>,
+    camera: &Camera,
+    mode: RenderMode,
+    programs: &ModePrograms,
+    draw_params: &DrawParameters,
+) -> Result<(), Error> {
+    let mesh = object.drawn_mesh();
+    let color = match &object.material {
+        Material::Phong(material) => material.diffuse,
+        Material::Pbr(material) => material.base_color,
+    };
+    let wire_color = match mode {
+        RenderMode::WireframeOverShaded => DARKGRAY,
+        _ => LIGHTGRAY,
+    };
+    let view = camera.get_view_matrix();
+    let perspective = camera.get_perspective_matrix();
+
+    let params = match mode {
+        // Edges lie exactly on the shaded surface and are antialiased by blending
+        RenderMode::WireframeOverShaded => DrawParameters {
+            depth: glium::Depth {
+                test: DepthTest::IfLessOrEqual,
+                write: false,
+                ..draw_params.depth
+            },
+            blend: Blend::alpha_blending(),
+            ..draw_params.clone()
+        },
+        // The edges on the back are part of the picture
+        RenderMode::Wireframe => DrawParameters {
+            backface_culling: BackfaceCullingMode::CullingDisabled,
+            blend: Blend::alpha_blending(),
+            ..draw_params.clone()
+        },
+        _ => draw_params.clone(),
+    };
+    let uniforms = uniform! {
+        view: view,
+        perspective: perspective,
+        mode: mode.shader_mode(),
+        u_color: <[f32; 4]>::from(color.to_linear()),
+        wire_color: <[f32; 4]>::from(wire_color.to_linear()),
+        near: camera.near(),
+        far: camera.far(),
+    };
+    let instanced = instances
+        .per_instance()
+        .map_err(|_| Error::InstancingNotSupported)?;
+    frame.draw(
+        (&mesh.vertex_bfr, &mesh.normal_bfr, &mesh.uv_bfr, instanced),
+        &mesh.ind_bfr,
+        &programs.fill,
+        &uniforms,
+        &params,
+    )?;
+
+    if let RenderMode::VertexNormals | RenderMode::FaceNormals = mode {
+        let face = mode == RenderMode::FaceNormals;
+        let line_color: Color = if face { VIOLET } else { TEAL };
+        let view_perspective: [[f32; 4]; 4] =
+            (Matrix4::from(perspective) * Matrix4::from(view)).into();
+        let uniforms = uniform! {
+            view: view,
+            perspective: perspective,
+            view_perspective: view_perspective,
+            normal_length: NORMAL_LENGTH,
+            face: face,
+            line_color: <[f32; 4]>::from(line_color.to_linear()),
+        };
+        let instanced = instances
+            .per_instance()
+            .map_err(|_| Error::InstancingNotSupported)?;
+        frame.draw(
+            (&mesh.vertex_bfr, &mesh.normal_bfr, &mesh.uv_bfr, instanced),
+            &mesh.ind_bfr,
+            &programs.normals,
+            &uniforms,
+            draw_params,
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use crate::{Display, Error, Mesh, Program};
+use glium::texture::{SrgbTexture2d, Texture2d};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// Key of the built-in teapot mesh
+pub const TEAPOT_KEY: &str = "builtin:teapot";
+
+/// Deduplicates GPU resources by key. Handles are `Rc`s: cloning one is cheap and
+/// the GPU memory is freed when the last handle is dropped. The manager itself
+/// only keeps weak references, so it never keeps a resource alive. Errors of the
+/// `load` callbacks are passed through.
+pub struct Resources {
+    display: Display,
+    meshes: HashMap<String, Weak<Mesh>>,
+    mesh_parts: HashMap<String, Vec<Weak<Mesh>>>,
+    textures: HashMap<String, Weak<Texture2d>>,
+    srgb_textures: HashMap<String, Weak<SrgbTexture2d>>,
+    programs: HashMap<String, Weak<Program>>,
+}
+
+/// Live resources and an estimate of the GPU memory they use
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ResourceStats {
+    pub meshes: usize,
+    pub textures: usize,
+    pub programs: usize,
+    /// Size of all vertex and index buffers in bytes
+    pub mesh_bytes: usize,
+    /// Size of all textures including their mipmaps in bytes, assuming 4 bytes per texel
+    pub texture_bytes: usize,
+}
+
+impl ResourceStats {
+    pub fn total_bytes(&self) -> usize {
+        self.mesh_bytes + self.texture_bytes
+    }
+}
+
+impl Resources {
+    pub fn new(display: &Display) -> Self {
+        Self {
+            display: display.clone(),
+            meshes: HashMap::new(),
+            mesh_parts: HashMap::new(),
+            textures: HashMap::new(),
+            srgb_textures: HashMap::new(),
+            programs: HashMap::new(),
+        }
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// Returns the mesh stored under `key`, calling `load` if there is no live one
+    pub fn mesh<E, F>(&mut self, key: &str, load: F) -> Result<Rc<Mesh>, E>
+    where
+        F: FnOnce(&Display) -> Result<Mesh, E>,
+    {
+        get_or_load(&mut self.meshes, &self.display, key, load)
+    }
+
+    /// Like `mesh` for meshes uploaded in several parts, e.g. made by
+    /// `MeshData::split`. The parts are loaded again unless all are alive.
+    pub fn mesh_parts<E, F>(&mut self, key: &str, load: F) -> Result<Vec<Rc<Mesh>>, E>
+    where
+        F: FnOnce(&Display) -> Result<Vec<Mesh>, E>,
+    {
+        if let Some(parts) = self.mesh_parts.get(key) {
+            let live: Vec<_> = parts.iter().filter_map(Weak::upgrade).collect();
+            if live.len() == parts.len() {
+                return Ok(live);
+            }
+        }
+
+        let parts: Vec<_> = load(&self.display)?.into_iter().map(Rc::new).collect();
+        self.mesh_parts
+            .insert(key.to_string(), parts.iter().map(Rc::downgrade).collect());
+        Ok(parts)
+    }
+
+    /// The built-in Utah teapot, uploaded once
+    pub fn teapot(&mut self) -> Result<Rc<Mesh>, Error> {
+        self.mesh(TEAPOT_KEY, Mesh::teapot)
+    }
+
+    /// Returns the linear texture stored under `key`, calling `load` if there is no live one
+    pub fn texture<E, F>(&mut self, key: &str, load: F) -> Result<Rc<Texture2d>, E>
+    where
+        F: FnOnce(&Display) -> Result<Texture2d, E>,
+    {
+        get_or_load(&mut self.textures, &self.display, key, load)
+    }
+
+    /// Returns the sRGB texture stored under `key`, calling `load` if there is no live one
+    pub fn srgb_texture<E, F>(&mut self, key: &str, load: F) -> Result<Rc<SrgbTexture2d>, E>
+    where
+        F: FnOnce(&Display) -> Result<SrgbTexture2d, E>,
+    {
+        get_or_load(&mut self.srgb_textures, &self.display, key, load)
+    }
+
+    /// Returns the program stored under `key`, calling `load` if there is no live one
+    pub fn program<E, F>(&mut self, key: &str, load: F) -> Result<Rc<Program>, E>
+    where
+        F: FnOnce(&Display) -> Result<Program, E>,
+    {
+        get_or_load(&mut self.programs, &self.display, key, load)
+    }
+
+    /// Forgets the keys whose resources were freed and returns how many there were
+    pub fn collect_garbage(&mut self) -> usize {
+        let parts = self.mesh_parts.len();
+        self.mesh_parts
+            .retain(|_, parts| parts.iter().all(|part| part.strong_count() > 0));
+        prune(&mut self.meshes)
+            + (parts - self.mesh_parts.len())
+            + prune(&mut self.textures)
+            + prune(&mut self.srgb_textures)
+            + prune(&mut self.programs)
+    }
+
+    pub fn stats(&self) -> ResourceStats {
+        let meshes: Vec<_> = self
+            .meshes
+            .values()
+            .chain(self.mesh_parts.values().flatten())
+            .filter_map(Weak::upgrade)
+            .collect();
+        let textures: Vec<_> = self.textures.values().filter_map(Weak::upgrade).collect();
+        let srgb_textures: Vec<_> = self
+            .srgb_textures
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect();
+
+        let texture_bytes = textures
+            .iter()
+            .map(|texture| texture_size(texture.dimensions(), texture.get_mipmap_levels()))
+            .chain(
+                srgb_textures
+                    .iter()
+                    .map(|texture| texture_size(texture.dimensions(), texture.get_mipmap_levels())),
+            )
+            .sum();
+
+        ResourceStats {
+            meshes: meshes.len(),
+            textures: textures.len() + srgb_textures.len(),
+            programs: self
+                .programs
+                .values()
+                .filter(|program| program.strong_count() > 0)
+                .count(),
+            mesh_bytes: meshes.iter().map(|mesh| mesh.size_bytes()).sum(),
+            texture_bytes,
+        }
+    }
+}
+
+fn get_or_load<T, E, F: FnOnce(&Display) -> Result<T, E>>(
+    cache: &mut HashMap<String, Weak<T>>,
+    display: &Display,
+    key: &str,
+    load: F,
+) -> Result<Rc<T>, E> {
+    if let Some(resource) = cache.get(key).and_then(Weak::upgrade) {
+        return Ok(resource);
+    }
+
+    let resource = Rc::new(load(display)?);
+    cache.insert(key.to_string(), Rc::downgrade(&resource));
+    Ok(resource)
+}
+
+fn prune<T>(cache: &mut HashMap<String, Weak<T>>) -> usize {
+    let before = cache.len();
+    cache.retain(|_, resource| resource.strong_count() > 0);
+    before - cache.len()
+}
+
+fn texture_size((width, height): (u32, u32), levels: u32) -> usize {
+    (0..levels)
+        .map(|level| ((width >> level).max(1) * (height >> level).max(1)) as usize * 4)
+        .sum()
+}
@@ -0,0 +1,209 @@
+use crate::Error;
+use std::fs;
+use std::path::Path;
+
+/// A Radiance `.hdr` image in linear RGB, the top row comes first
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 3]>,
+}
+
+/// Reads a Radiance `.hdr` (RGBE) file
+pub fn load_hdr<P: AsRef<Path>>(path: P) -> Result<HdrImage, Error> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)
+        .map_err(|err| Error::Asset(format!("{}: {}", path.display(), err).into()))?;
+    decode_hdr(&bytes)
+        .map_err(|message| Error::Asset(format!("{}: {}", path.display(), message).into()))
+}
+
+/// Decodes the contents of a Radiance `.hdr` file, flat or run-length encoded
+pub fn decode_hdr(bytes: &[u8]) -> Result<HdrImage, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    let magic = reader.line()?;
+    if magic != "#?RADIANCE" && magic != "#?RGBE" {
+        return Err("not a Radiance HDR file".to_string());
+    }
+    // Header variables end with an empty line
+    loop {
+        let line = reader.line()?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(format!("unsupported pixel format {}", format));
+            }
+        }
+    }
+
+    let resolution = reader.line()?;
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (
+            height.parse::<u32>().map_err(|err| err.to_string())?,
+            width.parse::<u32>().map_err(|err| err.to_string())?,
+        ),
+        _ => return Err(format!("unsupported image orientation {}", resolution)),
+    };
+    if width == 0 || height == 0 {
+        return Err(format!("empty image of {}x{} pixels", width, height));
+    }
+    let count = (width as usize)
+        .checked_mul(height as usize)
+        .ok_or_else(|| format!("image of {}x{} pixels is too large", width, height))?;
+
+    let mut pixels = Vec::with_capacity(count);
+    let mut scanline = vec![[0u8; 4]; width as usize];
+    for _ in 0..height {
+        reader.scanline(&mut scanline)?;
+        pixels.extend(scanline.iter().map(|&rgbe| to_float(rgbe)));
+    }
+
+    Ok(HdrImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn to_float([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+    if e == 0 {
+        return [0.0; 3];
+    }
+    let scale = 2f32.powi(e as i32 - (128 + 8));
+    [
+        (r as f32 + 0.5) * scale,
+        (g as f32 + 0.5) * scale,
+        (b as f32 + 0.5) * scale,
+    ]
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| "unexpected end of file".to_string())?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn line(&mut self) -> Result<String, String> {
+        let rest = &self.bytes[self.pos..];
+        let end = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or_else(|| "unexpected end of header".to_string())?;
+        self.pos += end + 1;
+        Ok(String::from_utf8_lossy(&rest[..end]).trim().to_string())
+    }
+
+    fn scanline(&mut self, scanline: &mut [[u8; 4]]) -> Result<(), String> {
+        let width = scanline.len();
+        let start = [self.byte()?, self.byte()?, self.byte()?, self.byte()?];
+
+        // Run-length encoded scanlines start with 2, 2 and the width
+        if !(8..0x8000).contains(&width)
+            || start[0] != 2
+            || start[1] != 2
+            || ((start[2] as usize) << 8 | start[3] as usize) != width
+        {
+            scanline[0] = start;
+            for pixel in scanline[1..].iter_mut() {
+                *pixel = [self.byte()?, self.byte()?, self.byte()?, self.byte()?];
+            }
+            return Ok(());
+        }
+
+        // Each channel is encoded separately as runs and literal spans
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = self.byte()? as usize;
+                let (count, run) = if count > 128 {
+                    (count - 128, true)
+                } else {
+                    (count, false)
+                };
+                if count == 0 || x + count > width {
+                    return Err("bad run length in scanline".to_string());
+                }
+                let value = if run { self.byte()? } else { 0 };
+                for pixel in scanline[x..x + count].iter_mut() {
+                    pixel[channel] = if run { value } else { self.byte()? };
+                }
+                x += count;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(resolution: &str) -> Vec<u8> {
+        format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes()
+    }
+
+    #[test]
+    fn decodes_flat_pixels() {
+        let mut bytes = header("-Y 1 +X 2");
+        bytes.extend_from_slice(&[128, 64, 0, 129, 0, 0, 0, 0]);
+        let image = decode_hdr(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(
+            image.pixels[0],
+            [1.0 + 0.5 / 128.0, 0.5 + 0.5 / 128.0, 0.5 / 128.0]
+        );
+        assert_eq!(image.pixels[1], [0.0; 3]);
+    }
+
+    #[test]
+    fn decodes_run_length_encoded_scanlines() {
+        let mut bytes = header("-Y 1 +X 8");
+        bytes.extend_from_slice(&[2, 2, 0, 8]);
+        // A run of eight for red, literals for green, runs of four for blue and the exponent
+        bytes.extend_from_slice(&[128 + 8, 10]);
+        bytes.extend_from_slice(&[8, 0, 1, 2, 3, 4, 5, 6, 7]);
+        bytes.extend_from_slice(&[128 + 4, 20, 128 + 4, 30]);
+        bytes.extend_from_slice(&[128 + 8, 128]);
+        let image = decode_hdr(&bytes).unwrap();
+        assert_eq!(image.pixels.len(), 8);
+        for (x, pixel) in image.pixels.iter().enumerate() {
+            let blue = if x < 4 { 20.0 } else { 30.0 };
+            let expected = [10.5 / 256.0, (x as f32 + 0.5) / 256.0, (blue + 0.5) / 256.0];
+            assert_eq!(*pixel, expected);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(decode_hdr(b"P6\n").is_err());
+        assert!(decode_hdr(&header("-Y 0 +X 4")).is_err());
+        assert!(decode_hdr(&header("-Y 4 +X 0")).is_err());
+        assert!(decode_hdr(&header("+Y 1 +X 1")).is_err());
+
+        let mut truncated = header("-Y 2 +X 1");
+        truncated.extend_from_slice(&[1, 2, 3, 128]);
+        assert!(decode_hdr(&truncated).is_err());
+
+        let mut bad_run = header("-Y 1 +X 8");
+        bad_run.extend_from_slice(&[2, 2, 0, 8, 128 + 9, 0]);
+        assert!(decode_hdr(&bad_run).is_err());
+    }
+
+    #[test]
+    fn load_errors_name_the_file() {
+        let err = load_hdr("missing/sky.hdr").err().unwrap();
+        assert!(err.to_string().contains("missing/sky.hdr"));
+    }
+}
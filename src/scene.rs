@@ -0,0 +1,125 @@
+use crate::scene_object::Transform;
+use crate::{Camera, Light, Matrix4, Point3, SceneObject, Vector3, FORWARD};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Where the objects of a node come from, so scenes can be saved and reloaded
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MeshSource {
+    /// The built-in Utah teapot
+    Teapot,
+    /// One mesh of a glTF file, or its whole default scene when `mesh` is `None`
+    Gltf { path: PathBuf, mesh: Option<usize> },
+    /// An STL or PLY file
+    File { path: PathBuf },
+}
+
+/// A node in the scene hierarchy. Objects, cameras and lights attached to a
+/// node follow its world transform.
+#[derive(Clone, Default)]
+pub struct Node {
+    pub name: Option<String>,
+    /// Transform relative to the parent node
+    pub transform: Transform,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Indices into `Scene::objects`
+    pub objects: Vec<usize>,
+    /// Index into `Scene::cameras`
+    pub camera: Option<usize>,
+    /// Index into `Scene::lights`
+    pub light: Option<usize>,
+    pub mesh: Option<MeshSource>,
+    /// Created while loading the `mesh` of an ancestor, not written when saving
+    pub generated: bool,
+}
+
+/// Objects, cameras and lights together with the node hierarchy placing them
+pub struct Scene {
+    pub objects: Vec<Box<SceneObject>>,
+    pub nodes: Vec<Node>,
+    pub cameras: Vec<Camera>,
+    pub lights: Vec<Light>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self {
+            objects: Vec::new(),
+            nodes: Vec::new(),
+            cameras: Vec::new(),
+            lights: Vec::new(),
+        }
+    }
+
+    /// Adds a node below `parent` and returns its index
+    pub fn add_node(&mut self, mut node: Node, parent: Option<usize>) -> usize {
+        let index = self.nodes.len();
+        node.parent = parent;
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(index);
+        }
+        self.nodes.push(node);
+        index
+    }
+
+    /// Indices of the nodes without a parent
+    pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(move |&i| self.nodes[i].parent.is_none())
+    }
+
+    /// World matrix of a node: the product of all transforms up to the root
+    pub fn world_matrix(&self, node: usize) -> Matrix4 {
+        let mut matrix = self.nodes[node].transform.matrix();
+        let mut parent = self.nodes[node].parent;
+        while let Some(index) = parent {
+            matrix = self.nodes[index].transform.matrix() * matrix;
+            parent = self.nodes[index].parent;
+        }
+        matrix
+    }
+
+    /// Pushes the node transforms down to the attached objects, cameras and lights.
+    /// Call after changing node transforms.
+    pub fn update_transforms(&mut self) {
+        for node in 0..self.nodes.len() {
+            let world = self.world_matrix(node);
+            let position = world.transform_point(&Point3::origin()).coords;
+            // Cameras and lights look down their local -Z axis. A degenerate
+            // transform (zero scale) keeps their previous direction.
+            let direction = world.transform_vector(&FORWARD);
+
+            let Node {
+                objects,
+                camera,
+                light,
+                ..
+            } = &self.nodes[node];
+
+            for &object in objects {
+                self.objects[object].set_parent_matrix(world);
+            }
+            if let Some(camera) = *camera {
+                self.cameras[camera].set_position(position);
+                self.cameras[camera].set_direction(direction).ok();
+            }
+            if let Some(light) = *light {
+                self.lights[light].position = position;
+                self.lights[light].set_direction(direction).ok();
+            }
+        }
+    }
+
+    /// Position of a node in world space
+    pub fn world_position(&self, node: usize) -> Vector3 {
+        self.world_matrix(node)
+            .transform_point(&Point3::origin())
+            .coords
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
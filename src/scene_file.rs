@@ -0,0 +1,955 @@
+use crate::gltf_import::{import_gltf, import_gltf_mesh};
+use crate::mesh_import::{imported_object, load_mesh};
+use crate::scene::{MeshSource, Node, Scene};
+use crate::scene_object::Transform;
+use crate::{
+    BlendMode, Camera, Color, Error, ImportError, Light, LightKind, Material, Mesh, PbrMaterial,
+    PhongMaterial, Resources, SceneObject, UnitQuaternion,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Version written into saved scenes. Files with a newer version are rejected.
+pub const SCENE_FILE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        message: String,
+    },
+    Serialize {
+        message: String,
+    },
+    UnsupportedVersion {
+        found: u32,
+        supported: u32,
+    },
+    /// A mesh file referenced by the scene does not exist
+    MissingAsset {
+        path: PathBuf,
+    },
+    Import {
+        path: PathBuf,
+        source: ImportError,
+    },
+    /// A node refers to a camera or light the file doesn't define
+    BadReference {
+        node: String,
+        what: &'static str,
+        index: usize,
+    },
+    /// An object was built in code and has no mesh source to save
+    UnknownMesh {
+        object: usize,
+    },
+    /// A camera or light can't be built from its description, e.g. a zero direction
+    Invalid {
+        what: String,
+        source: Error,
+    },
+    /// Loading an STL or PLY file or uploading a mesh failed
+    Renderer(Error),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Io { path, source } => {
+                write!(f, "couldn't access {}: {}", path.display(), source)
+            }
+            SceneFileError::Parse { path, message } => {
+                write!(f, "couldn't parse {}: {}", path.display(), message)
+            }
+            SceneFileError::Serialize { message } => {
+                write!(f, "couldn't serialize scene: {}", message)
+            }
+            SceneFileError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "scene file version {} is newer than the supported version {}",
+                found, supported
+            ),
+            SceneFileError::MissingAsset { path } => {
+                write!(f, "missing asset: {} does not exist", path.display())
+            }
+            SceneFileError::Import { path, source } => {
+                write!(f, "couldn't import {}: {}", path.display(), source)
+            }
+            SceneFileError::BadReference { node, what, index } => {
+                write!(
+                    f,
+                    "node '{}' refers to {} {} which is not defined",
+                    node, what, index
+                )
+            }
+            SceneFileError::UnknownMesh { object } => write!(
+                f,
+                "object {} has no mesh source, only objects loaded from a mesh source can be saved",
+                object
+            ),
+            SceneFileError::Invalid { what, source } => write!(f, "invalid {}: {}", what, source),
+            SceneFileError::Renderer(source) => write!(f, "{}", source),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneFileError::Io { source, .. } => Some(source),
+            SceneFileError::Import { source, .. } => Some(source),
+            SceneFileError::Invalid { source, .. } => Some(source),
+            SceneFileError::Renderer(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// The human editable form of a `Scene`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneDescription {
+    pub version: u32,
+    #[serde(default)]
+    pub cameras: Vec<CameraDescription>,
+    #[serde(default)]
+    pub lights: Vec<LightDescription>,
+    #[serde(default)]
+    pub nodes: Vec<NodeDescription>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformDescription {
+    pub position: [f32; 3],
+    /// Quaternion as [x, y, z, w]
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl Default for TransformDescription {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum MaterialDescription {
+    Phong {
+        ambient: [f32; 3],
+        diffuse: [f32; 3],
+        specular: [f32; 3],
+        shininess: f32,
+        /// Alpha of the diffuse color
+        #[serde(default = "one")]
+        opacity: f32,
+        #[serde(default)]
+        blend: BlendMode,
+    },
+    /// Textures only come with the materials of mesh files
+    Pbr {
+        base_color: [f32; 3],
+        metallic: f32,
+        roughness: f32,
+        #[serde(default = "one")]
+        ambient_occlusion: f32,
+        #[serde(default)]
+        emissive: [f32; 3],
+        /// Alpha of the base color
+        #[serde(default = "one")]
+        opacity: f32,
+        #[serde(default)]
+        blend: BlendMode,
+    },
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeDescription {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub transform: TransformDescription,
+    #[serde(default)]
+    pub mesh: Option<MeshSource>,
+    /// Overrides the materials of the mesh
+    #[serde(default)]
+    pub material: Option<MaterialDescription>,
+    /// Index into the camera list
+    #[serde(default)]
+    pub camera: Option<usize>,
+    /// Index into the light list
+    #[serde(default)]
+    pub light: Option<usize>,
+    #[serde(default)]
+    pub children: Vec<NodeDescription>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraDescription {
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    /// Vertical field of view in degrees
+    pub fov: u32,
+    pub near: f32,
+    pub far: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LightDescription {
+    pub kind: LightKind,
+    #[serde(default)]
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    #[serde(default = "white")]
+    pub color: [f32; 3],
+    #[serde(default = "one")]
+    pub intensity: f32,
+    /// Left out, directional and spot lights cast shadows and point lights don't,
+    /// like the `Light` constructors choose
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_flag",
+        deserialize_with = "deserialize_flag"
+    )]
+    pub casts_shadows: Option<bool>,
+}
+
+fn white() -> [f32; 3] {
+    [1.0; 3]
+}
+
+/// Writes a set optional flag as a plain bool, RON would wrap it in `Some`
+fn serialize_flag<S: Serializer>(flag: &Option<bool>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bool(flag.unwrap_or_default())
+}
+
+fn deserialize_flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    bool::deserialize(deserializer).map(Some)
+}
+
+/// Loads a scene saved as RON, or as JSON when the file name ends in `.json`.
+/// Mesh files are resolved relative to the scene file, meshes already held by
+/// `resources` are shared instead of uploaded again.
+pub fn load_scene<P: AsRef<Path>>(
+    resources: &mut Resources,
+    path: P,
+) -> Result<Scene, SceneFileError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|source| SceneFileError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let description: SceneDescription = if is_json(path) {
+        serde_json::from_str(&text).map_err(|err| err.to_string())
+    } else {
+        ron::de::from_str(&text).map_err(|err| err.to_string())
+    }
+    .map_err(|message| SceneFileError::Parse {
+        path: path.to_path_buf(),
+        message,
+    })?;
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    description.instantiate(resources, base_dir)
+}
+
+/// Saves a scene as RON, or as JSON when the file name ends in `.json`
+pub fn save_scene<P: AsRef<Path>>(scene: &Scene, path: P) -> Result<(), SceneFileError> {
+    let path = path.as_ref();
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let description = SceneDescription::from_scene(scene, base_dir)?;
+
+    let text = if is_json(path) {
+        serde_json::to_string_pretty(&description).map_err(|err| err.to_string())
+    } else {
+        ron::ser::to_string_pretty(&description, ron::ser::PrettyConfig::new())
+            .map_err(|err| err.to_string())
+    }
+    .map_err(|message| SceneFileError::Serialize { message })?;
+
+    fs::write(path, text).map_err(|source| SceneFileError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+pub(crate) fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+impl SceneDescription {
+    /// Describes the scene with mesh paths relative to `base_dir`, the directory
+    /// of the file it will be saved to
+    pub fn from_scene(scene: &Scene, base_dir: &Path) -> Result<Self, SceneFileError> {
+        // Every object has to be reachable through a node with a mesh source
+        let mut saved = vec![false; scene.objects.len()];
+        for node in scene.nodes.iter() {
+            if node.mesh.is_some() || node.generated {
+                for &object in &node.objects {
+                    saved[object] = true;
+                }
+            }
+        }
+        if let Some(object) = saved.iter().position(|&saved| !saved) {
+            return Err(SceneFileError::UnknownMesh { object });
+        }
+
+        Ok(Self {
+            version: SCENE_FILE_VERSION,
+            cameras: scene.cameras.iter().map(CameraDescription::from).collect(),
+            lights: scene.lights.iter().map(LightDescription::from).collect(),
+            nodes: scene
+                .roots()
+                .filter(|&root| !scene.nodes[root].generated)
+                .map(|root| NodeDescription::from_node(scene, root, base_dir))
+                .collect(),
+        })
+    }
+
+    /// Builds the scene, loading mesh files relative to `base_dir`
+    pub fn instantiate(
+        &self,
+        resources: &mut Resources,
+        base_dir: &Path,
+    ) -> Result<Scene, SceneFileError> {
+        if self.version > SCENE_FILE_VERSION {
+            return Err(SceneFileError::UnsupportedVersion {
+                found: self.version,
+                supported: SCENE_FILE_VERSION,
+            });
+        }
+
+        let mut scene = Scene::new();
+        for (i, camera) in self.cameras.iter().enumerate() {
+            let camera = Camera::try_from(camera).map_err(|source| SceneFileError::Invalid {
+                what: format!("camera {}", i),
+                source,
+            })?;
+            scene.cameras.push(camera);
+        }
+        for (i, light) in self.lights.iter().enumerate() {
+            let light = Light::try_from(light).map_err(|source| SceneFileError::Invalid {
+                what: format!("light {}", i),
+                source,
+            })?;
+            scene.lights.push(light);
+        }
+
+        let mut loader = Loader {
+            resources,
+            base_dir,
+            gltf_meshes: HashMap::new(),
+        };
+        for node in &self.nodes {
+            node.instantiate(&mut loader, &mut scene, None)?;
+        }
+
+        scene.update_transforms();
+        Ok(scene)
+    }
+}
+
+impl NodeDescription {
+    fn from_node(scene: &Scene, index: usize, base_dir: &Path) -> Self {
+        let node = &scene.nodes[index];
+        let transform = &node.transform;
+        let rotation = transform.rotation.quaternion().coords;
+
+        // Materials of glTF files are loaded with the file, the other meshes have none
+        let material = match node.mesh {
+            Some(MeshSource::Teapot) | Some(MeshSource::File { .. }) => node
+                .objects
+                .first()
+                .map(|&object| MaterialDescription::from(&scene.objects[object].material)),
+            _ => None,
+        };
+
+        Self {
+            name: node.name.clone(),
+            transform: TransformDescription {
+                position: transform.position.into(),
+                rotation: rotation.into(),
+                scale: transform.scale.into(),
+            },
+            mesh: node
+                .mesh
+                .as_ref()
+                .map(|mesh| map_path(mesh, |path| relative_path(path, base_dir))),
+            material,
+            camera: node.camera,
+            light: node.light,
+            children: node
+                .children
+                .iter()
+                .filter(|&&child| !scene.nodes[child].generated)
+                .map(|&child| NodeDescription::from_node(scene, child, base_dir))
+                .collect(),
+        }
+    }
+
+    fn instantiate(
+        &self,
+        loader: &mut Loader,
+        scene: &mut Scene,
+        parent: Option<usize>,
+    ) -> Result<usize, SceneFileError> {
+        let name = self.name.clone().unwrap_or_default();
+        if let Some(camera) = self.camera.filter(|&i| i >= scene.cameras.len()) {
+            return Err(SceneFileError::BadReference {
+                node: name,
+                what: "camera",
+                index: camera,
+            });
+        }
+        if let Some(light) = self.light.filter(|&i| i >= scene.lights.len()) {
+            return Err(SceneFileError::BadReference {
+                node: name,
+                what: "light",
+                index: light,
+            });
+        }
+
+        let [x, y, z, w] = self.transform.rotation;
+        let node = Node {
+            name: self.name.clone(),
+            transform: Transform::new(
+                self.transform.position.into(),
+                UnitQuaternion::from_quaternion(nalgebra::Quaternion::new(w, x, y, z)),
+                self.transform.scale.into(),
+            ),
+            camera: self.camera,
+            light: self.light,
+            // Nodes keep the resolved path, so the scene can be saved anywhere
+            mesh: self
+                .mesh
+                .as_ref()
+                .map(|mesh| map_path(mesh, |path| loader.base_dir.join(path))),
+            ..Default::default()
+        };
+        let index = scene.add_node(node, parent);
+
+        match &self.mesh {
+            Some(MeshSource::Teapot) => {
+                let mesh = loader
+                    .resources
+                    .teapot()
+                    .map_err(SceneFileError::Renderer)?;
+                attach(scene, index, vec![SceneObject::from_mesh(mesh)]);
+            }
+            Some(MeshSource::Gltf { path, mesh }) => {
+                let path = loader.base_dir.join(path);
+                if !path.exists() {
+                    return Err(SceneFileError::MissingAsset { path });
+                }
+                let import_error = |source| SceneFileError::Import {
+                    path: path.clone(),
+                    source,
+                };
+                match mesh {
+                    Some(mesh) => {
+                        let key = (path.clone(), *mesh);
+                        let objects = match loader.gltf_meshes.get(&key).cloned() {
+                            Some(objects) => objects,
+                            None => {
+                                let display = loader.resources.display();
+                                let objects = import_gltf_mesh(display, &path, *mesh)
+                                    .map_err(import_error)?;
+                                loader.gltf_meshes.insert(key, objects.clone());
+                                objects
+                            }
+                        };
+                        attach(scene, index, objects);
+                    }
+                    None => {
+                        let display = loader.resources.display();
+                        let asset = import_gltf(display, &path).map_err(import_error)?;
+                        graft(scene, index, asset);
+                    }
+                }
+            }
+            Some(MeshSource::File { path }) => {
+                let path = loader.base_dir.join(path);
+                if !path.exists() {
+                    return Err(SceneFileError::MissingAsset { path });
+                }
+                let key = path.to_string_lossy().into_owned();
+                let meshes = loader
+                    .resources
+                    .mesh_parts(&key, |display| {
+                        let parts = load_mesh(&path)?.split();
+                        parts
+                            .iter()
+                            .map(|part| Mesh::from_data(display, part))
+                            .collect()
+                    })
+                    .map_err(SceneFileError::Renderer)?;
+                attach(
+                    scene,
+                    index,
+                    meshes.into_iter().map(imported_object).collect(),
+                );
+            }
+            None => (),
+        }
+
+        if let Some(material) = &self.material {
+            let material = Material::from(material);
+            for &object in &scene.nodes[index].objects {
+                scene.objects[object].set_material(material.clone());
+            }
+        }
+
+        for child in &self.children {
+            child.instantiate(loader, scene, Some(index))?;
+        }
+
+        Ok(index)
+    }
+}
+
+/// State shared by the nodes of one scene file while it loads
+struct Loader<'a> {
+    resources: &'a mut Resources,
+    base_dir: &'a Path,
+    /// Objects of the glTF meshes loaded so far, cloned to share their GPU meshes
+    gltf_meshes: HashMap<(PathBuf, usize), Vec<SceneObject>>,
+}
+
+fn map_path(source: &MeshSource, f: impl Fn(&Path) -> PathBuf) -> MeshSource {
+    match source {
+        MeshSource::Teapot => MeshSource::Teapot,
+        MeshSource::Gltf { path, mesh } => MeshSource::Gltf {
+            path: f(path),
+            mesh: *mesh,
+        },
+        MeshSource::File { path } => MeshSource::File { path: f(path) },
+    }
+}
+
+/// `path` as seen from the directory `base`, both taken relative to the working
+/// directory. Paths on another drive than `base` stay absolute.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let (path, base) = match std::env::current_dir() {
+        Ok(cwd) => (normalize(&cwd.join(path)), normalize(&cwd.join(base))),
+        Err(_) => (normalize(path), normalize(base)),
+    };
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 && path.has_root() {
+        return path;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    for component in path.components().skip(common) {
+        relative.push(component);
+    }
+    relative
+}
+
+/// Drops `.` components and folds `..` into the directory before it, without
+/// looking at the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn attach(scene: &mut Scene, node: usize, objects: Vec<SceneObject>) {
+    for object in objects {
+        scene.nodes[node].objects.push(scene.objects.len());
+        scene.objects.push(Box::new(object));
+    }
+}
+
+/// Hangs the node tree of a loaded mesh file below `node`. The cameras and
+/// lights of the file are dropped, the scene file defines its own.
+fn graft(scene: &mut Scene, node: usize, asset: Scene) {
+    let node_offset = scene.nodes.len();
+    let object_offset = scene.objects.len();
+
+    for asset_node in asset.nodes {
+        let parent = asset_node
+            .parent
+            .map_or(node, |parent| parent + node_offset);
+        let child = Node {
+            name: asset_node.name,
+            transform: asset_node.transform,
+            children: Vec::new(),
+            objects: asset_node
+                .objects
+                .iter()
+                .map(|i| i + object_offset)
+                .collect(),
+            generated: true,
+            ..Default::default()
+        };
+        scene.add_node(child, Some(parent));
+    }
+    scene.objects.extend(asset.objects);
+}
+
+impl From<&Camera> for CameraDescription {
+    fn from(camera: &Camera) -> Self {
+        Self {
+            position: camera.position().into(),
+            direction: camera.direction().into(),
+            fov: camera.fov_degrees(),
+            near: camera.near(),
+            far: camera.far(),
+        }
+    }
+}
+
+impl TryFrom<&CameraDescription> for Camera {
+    type Error = Error;
+
+    /// The aspect ratio is square until the first frame updates the resolution
+    fn try_from(description: &CameraDescription) -> Result<Self, Error> {
+        let mut camera = Camera::new(
+            description.position.into(),
+            description.direction.into(),
+            (1, 1),
+            description.fov,
+        )?;
+        camera.set_clipping_planes(description.near, description.far);
+        Ok(camera)
+    }
+}
+
+impl From<&Light> for LightDescription {
+    fn from(light: &Light) -> Self {
+        Self {
+            kind: light.kind,
+            position: light.position.into(),
+            direction: light.direction().into(),
+            color: light.color.into(),
+            intensity: light.intensity,
+            casts_shadows: Some(light.casts_shadows),
+        }
+    }
+}
+
+impl TryFrom<&LightDescription> for Light {
+    type Error = Error;
+
+    fn try_from(description: &LightDescription) -> Result<Self, Error> {
+        let mut light = match description.kind {
+            LightKind::Directional => Light::directional(description.direction.into())?,
+            LightKind::Spot { angle, range } => Light::spot(
+                description.position.into(),
+                description.direction.into(),
+                angle,
+                range,
+            )?,
+            LightKind::Point { range } => Light::point(description.position.into(), range),
+        };
+        light.color = description.color.into();
+        light.intensity = description.intensity;
+        if let Some(casts_shadows) = description.casts_shadows {
+            light.casts_shadows = casts_shadows;
+        }
+        Ok(light)
+    }
+}
+
+impl From<&Material> for MaterialDescription {
+    fn from(material: &Material) -> Self {
+        match material {
+            Material::Phong(phong) => MaterialDescription::Phong {
+                ambient: phong.ambient.into(),
+                diffuse: phong.diffuse.into(),
+                specular: phong.specular.into(),
+                shininess: phong.shininess,
+                opacity: phong.diffuse.a,
+                blend: phong.blend,
+            },
+            Material::Pbr(pbr) => MaterialDescription::Pbr {
+                base_color: pbr.base_color.into(),
+                metallic: pbr.metallic,
+                roughness: pbr.roughness,
+                ambient_occlusion: pbr.ambient_occlusion,
+                emissive: pbr.emissive.into(),
+                opacity: pbr.base_color.a,
+                blend: pbr.blend,
+            },
+        }
+    }
+}
+
+impl From<&MaterialDescription> for Material {
+    fn from(description: &MaterialDescription) -> Self {
+        match *description {
+            MaterialDescription::Phong {
+                ambient,
+                diffuse,
+                specular,
+                shininess,
+                opacity,
+                blend,
+            } => Material::Phong(PhongMaterial {
+                ambient: ambient.into(),
+                diffuse: Color::from(diffuse).with_alpha(opacity),
+                specular: specular.into(),
+                shininess,
+                blend,
+            }),
+            MaterialDescription::Pbr {
+                base_color,
+                metallic,
+                roughness,
+                ambient_occlusion,
+                emissive,
+                opacity,
+                blend,
+            } => Material::Pbr(PbrMaterial {
+                ambient_occlusion,
+                emissive: emissive.into(),
+                blend,
+                ..PbrMaterial::new(
+                    Color::from(base_color).with_alpha(opacity),
+                    metallic,
+                    roughness,
+                )
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn description() -> SceneDescription {
+        SceneDescription {
+            version: SCENE_FILE_VERSION,
+            cameras: vec![CameraDescription {
+                position: [0.0, 1.0, 5.0],
+                direction: [0.0, 0.0, -1.0],
+                fov: 60,
+                near: 0.1,
+                far: 100.0,
+            }],
+            lights: vec![LightDescription {
+                kind: LightKind::Spot {
+                    angle: 0.5,
+                    range: 20.0,
+                },
+                position: [1.0, 4.0, 0.0],
+                direction: [0.0, -1.0, 0.0],
+                color: [1.0, 0.5, 0.25],
+                intensity: 2.0,
+                casts_shadows: Some(true),
+            }],
+            nodes: vec![NodeDescription {
+                name: Some("root".to_string()),
+                transform: TransformDescription {
+                    position: [1.0, 2.0, 3.0],
+                    rotation: [0.0, 0.0, 0.5f32.sin(), 0.5f32.cos()],
+                    scale: [2.0; 3],
+                },
+                mesh: Some(MeshSource::Teapot),
+                material: Some(MaterialDescription::Pbr {
+                    base_color: [0.8, 0.1, 0.1],
+                    metallic: 0.5,
+                    roughness: 0.25,
+                    ambient_occlusion: 1.0,
+                    emissive: [0.0; 3],
+                    opacity: 0.5,
+                    blend: BlendMode::Alpha,
+                }),
+                camera: Some(0),
+                light: Some(0),
+                children: vec![
+                    NodeDescription {
+                        name: None,
+                        transform: TransformDescription::default(),
+                        mesh: Some(MeshSource::Gltf {
+                            path: PathBuf::from("meshes/car.gltf"),
+                            mesh: Some(2),
+                        }),
+                        material: None,
+                        camera: None,
+                        light: None,
+                        children: Vec::new(),
+                    },
+                    NodeDescription {
+                        name: Some("ply".to_string()),
+                        transform: TransformDescription::default(),
+                        mesh: Some(MeshSource::File {
+                            path: PathBuf::from("../meshes/bunny.ply"),
+                        }),
+                        material: Some(MaterialDescription::Phong {
+                            ambient: [0.1; 3],
+                            diffuse: [0.6; 3],
+                            specular: [0.3; 3],
+                            shininess: 32.0,
+                            opacity: 1.0,
+                            blend: BlendMode::Opaque,
+                        }),
+                        camera: None,
+                        light: None,
+                        children: Vec::new(),
+                    },
+                ],
+            }],
+        }
+    }
+
+    fn to_ron(description: &SceneDescription) -> String {
+        ron::ser::to_string_pretty(description, ron::ser::PrettyConfig::new()).unwrap()
+    }
+
+    #[test]
+    fn description_round_trips_through_ron() {
+        let text = to_ron(&description());
+        let parsed: SceneDescription = ron::de::from_str(&text).unwrap();
+        assert_eq!(to_ron(&parsed), text);
+        assert_eq!(
+            parsed.nodes[0].children[1].mesh,
+            description().nodes[0].children[1].mesh
+        );
+    }
+
+    #[test]
+    fn description_round_trips_through_json() {
+        let text = serde_json::to_string_pretty(&description()).unwrap();
+        let parsed: SceneDescription = serde_json::from_str(&text).unwrap();
+        assert_eq!(serde_json::to_string_pretty(&parsed).unwrap(), text);
+        assert_eq!(to_ron(&parsed), to_ron(&description()));
+    }
+
+    #[test]
+    fn missing_fields_get_defaults() {
+        let text = "(version: 1, nodes: [(mesh: Some(Teapot))])";
+        let parsed: SceneDescription = ron::de::from_str(text).unwrap();
+        assert!(parsed.cameras.is_empty());
+        let node = &parsed.nodes[0];
+        assert_eq!(node.transform.rotation, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(node.transform.scale, [1.0; 3]);
+        assert!(node.children.is_empty());
+    }
+
+    #[test]
+    fn lights_cast_shadows_by_kind_unless_set() {
+        let text = "(
+            version: 1,
+            lights: [
+                (kind: Directional, direction: (0.0, -1.0, 0.0)),
+                (kind: Spot(angle: 0.5, range: 10.0), direction: (0.0, -1.0, 0.0)),
+                (kind: Point(range: 10.0), direction: (0.0, -1.0, 0.0)),
+                (kind: Directional, direction: (0.0, -1.0, 0.0), casts_shadows: false),
+                (kind: Point(range: 10.0), direction: (0.0, -1.0, 0.0), casts_shadows: true),
+            ],
+        )";
+        let parsed: SceneDescription = ron::de::from_str(text).unwrap();
+        let shadows: Vec<bool> = parsed
+            .lights
+            .iter()
+            .map(|light| Light::try_from(light).unwrap().casts_shadows)
+            .collect();
+        assert_eq!(shadows, [true, true, false, false, true]);
+    }
+
+    #[test]
+    fn saved_paths_are_relative_to_the_scene_file() {
+        let path = Path::new("assets/meshes/bunny.ply");
+        assert_eq!(
+            relative_path(path, Path::new("assets/scenes")),
+            Path::new("../meshes/bunny.ply")
+        );
+        assert_eq!(
+            relative_path(path, Path::new("assets")),
+            Path::new("meshes/bunny.ply")
+        );
+        assert_eq!(
+            relative_path(path, Path::new("")),
+            Path::new("assets/meshes/bunny.ply")
+        );
+        assert_eq!(
+            relative_path(Path::new("./a/../b.stl"), Path::new("c/./d")),
+            Path::new("../../b.stl")
+        );
+
+        // Loading joins the saved path to the scene directory, which must find the file again
+        let base = Path::new("out/scenes");
+        let saved = relative_path(path, base);
+        assert_eq!(normalize(&base.join(saved)), path);
+    }
+
+    #[test]
+    fn saving_rebases_mesh_paths() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(
+            Node {
+                mesh: Some(MeshSource::File {
+                    path: PathBuf::from("assets/bunny.ply"),
+                }),
+                ..Default::default()
+            },
+            None,
+        );
+        scene.add_node(
+            Node {
+                mesh: Some(MeshSource::Gltf {
+                    path: PathBuf::from("assets/car.gltf"),
+                    mesh: None,
+                }),
+                ..Default::default()
+            },
+            Some(root),
+        );
+
+        let description = SceneDescription::from_scene(&scene, Path::new("scenes")).unwrap();
+        let node = &description.nodes[0];
+        assert_eq!(
+            node.mesh,
+            Some(MeshSource::File {
+                path: PathBuf::from("../assets/bunny.ply")
+            })
+        );
+        assert_eq!(
+            node.children[0].mesh,
+            Some(MeshSource::Gltf {
+                path: PathBuf::from("../assets/car.gltf"),
+                mesh: None
+            })
+        );
+    }
+}
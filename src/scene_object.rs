@@ -1,30 +1,119 @@
-use crate::teapot::TEAPOT;
-use crate::{Display, IndexBuffer, Normal, Scalar, Vector3, Vertex, VertexBuffer};
+use crate::{
+    Color, Display, Error, Lod, Material, Matrix4, Mesh, Normal, RenderMode, Scalar, Tangent,
+    TexCoord, UnitQuaternion, Vector3, Vertex, WHITE,
+};
+use std::cell::Cell;
+use std::rc::Rc;
 
+/// Translation, rotation and (non-uniform) scale, applied in scale-rotate-translate order
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Transform {
-    position: Vector3,
-    pub(crate) scale: Scalar,
+    pub position: Vector3,
+    pub rotation: UnitQuaternion,
+    pub scale: Vector3,
 }
 
+impl Transform {
+    pub fn new(position: Vector3, rotation: UnitQuaternion, scale: Vector3) -> Self {
+        Self {
+            position,
+            rotation,
+            scale,
+        }
+    }
+
+    /// Builds the model matrix: translation * rotation * scale
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::new_translation(&self.position)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new(
+            Vector3::zeros(),
+            UnitQuaternion::identity(),
+            Vector3::repeat(1.0),
+        )
+    }
+}
+
+/// A mesh placed in the scene. Cloning is cheap: clones share the GPU mesh and
+/// are drawn together with one instanced draw call.
+#[derive(Clone)]
 pub struct SceneObject {
-    pub vertex_bfr: VertexBuffer<Vertex>,
-    pub normal_bfr: VertexBuffer<Normal>,
-    pub ind_bfr: IndexBuffer<u16>,
+    pub mesh: Rc<Mesh>,
+    pub material: Material,
+    /// Tint multiplied into the material color, uploaded per instance
+    pub color: Color,
+    /// Overrides the global `Shaders::render_mode` when set
+    pub render_mode: Option<RenderMode>,
+    /// Simplified meshes drawn in place of `mesh` when the object is small on screen
+    pub lod: Option<Rc<Lod>>,
+    /// Level picked for the camera of the last frame
+    pub(crate) lod_level: Cell<usize>,
     pub(crate) transform: Transform,
+    /// World matrix of the scene node the object hangs from
+    pub(crate) parent: Matrix4,
 }
 
 impl SceneObject {
-    pub fn new(display: &Display, vert: &[Vertex], norm: &[Normal], ind: &[u16]) -> Self {
+    pub fn new(
+        display: &Display,
+        vert: &[Vertex],
+        norm: &[Normal],
+        ind: &[u16],
+    ) -> Result<Self, Error> {
+        let mesh = Mesh::new(display, vert, norm, ind)?;
+        Ok(Self::from_mesh(Rc::new(mesh)))
+    }
+
+    /// Like `new`, with the texture coordinates and tangents needed by textured PBR materials
+    pub fn with_tex_coords(
+        display: &Display,
+        vert: &[Vertex],
+        norm: &[Normal],
+        uv: &[TexCoord],
+        tangents: &[Tangent],
+        ind: &[u16],
+    ) -> Result<Self, Error> {
+        let mesh = Mesh::with_tex_coords(display, vert, norm, uv, tangents, ind)?;
+        Ok(Self::from_mesh(Rc::new(mesh)))
+    }
+
+    /// An object drawing an already uploaded mesh
+    pub fn from_mesh(mesh: Rc<Mesh>) -> Self {
         Self {
-            vertex_bfr: VertexBuffer::new(display, vert).expect("Couldn't allocate Vertex Buffer."),
-            normal_bfr: VertexBuffer::new(display, norm)
-                .expect("Couldn't allocate Vertex Buffer for normals."),
-            ind_bfr: IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, ind)
-                .expect("Couldn't allocate Index buffer"),
-            transform: Transform {
-                position: Vector3::zeros(),
-                scale: 1.0,
-            },
+            mesh,
+            material: Material::default(),
+            color: WHITE,
+            render_mode: None,
+            lod: None,
+            lod_level: Cell::new(0),
+            transform: Transform::default(),
+            parent: Matrix4::identity(),
+        }
+    }
+
+    /// An object drawing the levels of detail, the full detail mesh up close
+    pub fn with_lod(lod: Rc<Lod>) -> Self {
+        let mut object = Self::from_mesh(lod.meshes[0].clone());
+        object.lod = Some(lod);
+        object
+    }
+
+    /// The level of detail drawn in the last frame, 0 for full detail
+    pub fn lod_level(&self) -> usize {
+        self.lod_level.get()
+    }
+
+    /// The mesh to draw: the current level of detail, or `mesh` without any
+    pub fn drawn_mesh(&self) -> &Rc<Mesh> {
+        match &self.lod {
+            Some(lod) => &lod.meshes[self.lod_level.get().min(lod.meshes.len() - 1)],
+            None => &self.mesh,
         }
     }
 
@@ -32,12 +121,32 @@ impl SceneObject {
         self.transform = transform;
     }
 
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn set_parent_matrix(&mut self, parent: Matrix4) {
+        self.parent = parent;
+    }
+
     pub fn set_position(&mut self, position: Vector3) {
         self.transform.position = position;
     }
 
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    pub fn set_rotation(&mut self, rotation: UnitQuaternion) {
+        self.transform.rotation = rotation;
+    }
+
     pub fn set_scale(&mut self, scale: Scalar) {
-        self.transform.scale = scale;
+        self.transform.scale = Vector3::repeat(scale);
     }
 
     pub fn scale_up(&mut self) {
@@ -48,18 +157,12 @@ impl SceneObject {
         self.transform.scale /= 1.01;
     }
 
-    pub fn teapot(display: &Display) -> Self {
-        let mut teapot = SceneObject::new(display, &TEAPOT.0, &TEAPOT.1, &TEAPOT.2);
-        teapot.set_scale(0.01);
-        teapot
+    /// The Utah teapot, fitting the cube from -1 to 1
+    pub fn teapot(display: &Display) -> Result<Self, Error> {
+        Ok(SceneObject::from_mesh(Rc::new(Mesh::teapot(display)?)))
     }
 
     pub fn get_model_matrix(&self) -> [[f32; 4]; 4] {
-        [
-            [self.transform.scale, 0.0, 0.0, 0.0],
-            [0.0, self.transform.scale, 0.0, 0.0],
-            [0.0, 0.0, self.transform.scale, 0.0],
-            [0.0, 0.0, 0.0, 1.0f32],
-        ]
+        (self.parent * self.transform.matrix()).into()
     }
 }
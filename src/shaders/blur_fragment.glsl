@@ -0,0 +1,24 @@
+#version 140
+
+in vec2 v_position;
+
+uniform sampler2D source;
+// Unit step along the blur axis: (1, 0) or (0, 1)
+uniform vec2 direction;
+
+out vec4 color;
+
+const float WEIGHTS[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+// One axis of a separable 9 tap gaussian blur
+void main() {
+    vec2 uv = v_position * 0.5 + 0.5;
+    vec2 offset = direction / vec2(textureSize(source, 0));
+
+    vec3 result = texture(source, uv).rgb * WEIGHTS[0];
+    for (int i = 1; i < 5; ++i) {
+        result += texture(source, uv + offset * float(i)).rgb * WEIGHTS[i];
+        result += texture(source, uv - offset * float(i)).rgb * WEIGHTS[i];
+    }
+    color = vec4(result, 1.0);
+}
@@ -0,0 +1,45 @@
+#version 140
+
+const uint SAMPLE_COUNT = 512u;
+
+in vec2 v_position;
+
+out vec4 color;
+
+// Smith's method with the Schlick-GGX approximation for image based lighting
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+    float k = roughness * roughness / 2.0;
+    float g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+// Scale and bias to F0 of the split sum approximation, indexed by n_dot_v and roughness
+void main() {
+    vec2 uv = v_position * 0.5 + 0.5;
+    float n_dot_v = max(uv.x, 1e-4);
+    float roughness = uv.y;
+
+    vec3 V = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    vec3 N = vec3(0.0, 0.0, 1.0);
+
+    float scale = 0.0;
+    float bias = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; ++i) {
+        vec3 H = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), N, roughness);
+        vec3 L = normalize(2.0 * dot(V, H) * H - V);
+
+        float n_dot_l = max(L.z, 0.0);
+        float n_dot_h = max(H.z, 0.0);
+        float v_dot_h = max(dot(V, H), 0.0);
+        if (n_dot_l > 0.0) {
+            float G = geometry_smith(n_dot_v, n_dot_l, roughness);
+            float G_visible = G * v_dot_h / (n_dot_h * n_dot_v);
+            float fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fresnel) * G_visible;
+            bias += fresnel * G_visible;
+        }
+    }
+
+    color = vec4(vec2(scale, bias) / float(SAMPLE_COUNT), 0.0, 1.0);
+}
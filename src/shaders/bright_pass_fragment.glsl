@@ -0,0 +1,15 @@
+#version 140
+
+in vec2 v_position;
+
+uniform sampler2D source;
+uniform float threshold;
+
+out vec4 color;
+
+// Keeps the part of the radiance above the threshold
+void main() {
+    vec3 hdr = texture(source, v_position * 0.5 + 0.5).rgb;
+    float brightness = max(max(hdr.r, hdr.g), hdr.b);
+    color = vec4(hdr * max(brightness - threshold, 0.0) / max(brightness, 1e-4), 1.0);
+}
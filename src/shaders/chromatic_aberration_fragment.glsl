@@ -0,0 +1,20 @@
+#version 140
+
+in vec2 v_position;
+
+uniform sampler2D source;
+uniform float strength;
+
+out vec4 color;
+
+// Red and blue are scaled apart from the image center, like a cheap lens does
+void main() {
+    vec2 uv = v_position * 0.5 + 0.5;
+    vec2 offset = (uv - 0.5) * strength;
+    color = vec4(
+        texture(source, uv + offset).r,
+        texture(source, uv).g,
+        texture(source, uv - offset).b,
+        1.0
+    );
+}
@@ -0,0 +1,18 @@
+#version 140
+
+in vec2 v_position;
+
+uniform sampler2D source;
+uniform sampler3D lut;
+uniform float strength;
+
+out vec4 color;
+
+// Looks the display encoded color up in a 3D LUT
+void main() {
+    vec3 original = texture(source, v_position * 0.5 + 0.5).rgb;
+    // Sample the texel centers, so 0 and 1 hit the first and last entries
+    float size = float(textureSize(lut, 0).x);
+    vec3 graded = texture(lut, original * (size - 1.0) / size + 0.5 / size).rgb;
+    color = vec4(mix(original, graded, strength), 1.0);
+}
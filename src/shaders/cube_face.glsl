@@ -0,0 +1,17 @@
+// Shared by the programs rendering into cubemap faces, pasted after the #version line.
+
+in vec2 v_position;
+
+// 0..5 for the +X, -X, +Y, -Y, +Z and -Z faces
+uniform int face;
+
+// Direction through a point of the current face, as in the OpenGL specification
+vec3 face_direction() {
+    vec2 st = v_position;
+    if (face == 0) return vec3(1.0, -st.y, -st.x);
+    if (face == 1) return vec3(-1.0, -st.y, st.x);
+    if (face == 2) return vec3(st.x, 1.0, st.y);
+    if (face == 3) return vec3(st.x, -1.0, -st.y);
+    if (face == 4) return vec3(st.x, -st.y, 1.0);
+    return vec3(-st.x, -st.y, -1.0);
+}
@@ -0,0 +1,13 @@
+#version 140
+
+in vec3 position;
+in vec4 color;
+
+out vec4 v_color;
+
+uniform mat4 view_perspective;
+
+void main() {
+    v_color = color;
+    gl_Position = view_perspective * vec4(position, 1.0);
+}
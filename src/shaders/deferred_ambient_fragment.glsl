@@ -0,0 +1,26 @@
+#version 140
+
+out vec4 color;
+
+// Ambient and emitted light, also copies the G-buffer depth into the target
+void main() {
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    float depth = texelFetch(g_depth, texel, 0).r;
+    if (depth >= 1.0) {
+        discard;
+    }
+
+    vec4 albedo = texelFetch(g_albedo, texel, 0);
+    vec3 N = normalize(texelFetch(g_normal, texel, 0).xyz);
+    vec4 material = texelFetch(g_material, texel, 0);
+    vec4 emission = texelFetch(g_emission, texel, 0);
+    vec3 V = normalize(-view_position(depth));
+
+    if (is_pbr(albedo)) {
+        float ao = material.b * ambient_occlusion();
+        color = vec4(pbr_ambient(N, V, albedo.rgb, material.r, material.g, ao) + emission.rgb, 1.0);
+    } else {
+        color = vec4(phong_ambient(N, emission.rgb), 1.0);
+    }
+    gl_FragDepth = depth;
+}
@@ -0,0 +1,38 @@
+#version 140
+
+// Rotates and moves view space positions back into world space, for the shadow lookup
+uniform mat4 inverse_view;
+
+out vec4 color;
+
+// The light bound to the u_light uniforms, added onto the target
+void main() {
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    float depth = texelFetch(g_depth, texel, 0).r;
+    if (depth >= 1.0) {
+        discard;
+    }
+
+    vec3 position = view_position(depth);
+    float attenuation;
+    vec3 L = light_vector(position, attenuation);
+    if (attenuation <= 0.0) {
+        discard;
+    }
+
+    vec4 albedo = texelFetch(g_albedo, texel, 0);
+    vec3 N = normalize(texelFetch(g_normal, texel, 0).xyz);
+    vec4 material = texelFetch(g_material, texel, 0);
+    vec3 V = normalize(-position);
+
+    float n_dot_l = max(dot(N, L), 0.0);
+    vec3 world_position = (inverse_view * vec4(position, 1.0)).xyz;
+    vec3 radiance = u_light_color * attenuation * shadow_factor(world_position, position.z, n_dot_l);
+
+    if (is_pbr(albedo)) {
+        color = vec4(pbr_direct(N, V, L, radiance, albedo.rgb, material.r, material.g), 1.0);
+    } else {
+        float shininess = texelFetch(g_emission, texel, 0).a;
+        color = vec4(phong_direct(N, V, L, radiance, albedo.rgb, material.rgb, shininess), 1.0);
+    }
+}
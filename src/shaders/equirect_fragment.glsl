@@ -0,0 +1,17 @@
+#version 140
+
+const float PI = 3.14159265359;
+
+uniform sampler2D equirectangular;
+
+out vec4 color;
+
+void main() {
+    vec3 direction = normalize(face_direction());
+    // The image is stored top row first
+    vec2 uv = vec2(
+        atan(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        0.5 - asin(direction.y) / PI
+    );
+    color = vec4(texture(equirectangular, uv).rgb, 1.0);
+}
@@ -2,26 +2,33 @@
 
 in vec3 v_normal;
 in vec3 v_position;
+in vec3 v_world_position;
+in float v_view_depth;
+in vec3 v_color;
+in float v_alpha;
 
 out vec4 color;
 
-uniform vec3 u_light;
-
-const vec3 ambient_color = vec3(0.2, 0.0, 0.0);
-const vec3 diffuse_color = vec3(0.6, 0.0, 0.0);
-const vec3 specular_color = vec3(1.0, 1.0, 1.0);
+uniform vec3 u_ambient_color;
+uniform vec3 u_diffuse_color;
+uniform vec3 u_specular_color;
+uniform float u_shininess;
+uniform float u_opacity;
 
 void main() {
 
     vec3 N = normalize(v_normal);
-    vec3 L = normalize(u_light);
     vec3 C = normalize(-v_position);
 
+    float attenuation;
+    vec3 L = light_vector(v_position, attenuation);
+
     float diffuse = max(dot(N, L), 0.0);
 
-    vec3 half_direction = normalize(L + C);
+    float lit = attenuation * shadow_factor(v_world_position, v_view_depth, diffuse);
 
-    float specular = pow(max(dot(half_direction, N), 0.0), 16.0);
+    vec3 ambient = phong_ambient(N, u_ambient_color * v_color);
+    vec3 direct = phong_direct(N, C, L, u_light_color * lit, u_diffuse_color * v_color, u_specular_color, u_shininess);
 
-    color = vec4(ambient_color + diffuse * diffuse_color + specular * specular_color, 1.0);
-}
\ No newline at end of file
+    color = vec4(ambient + direct, u_opacity * v_alpha);
+}
@@ -0,0 +1,9 @@
+#version 140
+
+// Fullscreen triangle for passes over a whole framebuffer or cubemap face
+out vec2 v_position;
+
+void main() {
+    v_position = vec2(gl_VertexID == 1 ? 3.0 : -1.0, gl_VertexID == 2 ? 3.0 : -1.0);
+    gl_Position = vec4(v_position, 0.0, 1.0);
+}
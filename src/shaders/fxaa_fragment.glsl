@@ -0,0 +1,52 @@
+#version 140
+
+in vec2 v_position;
+
+uniform sampler2D source;
+
+out vec4 color;
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float SPAN_MAX = 8.0;
+
+float luma(vec3 rgb) {
+    return dot(rgb, vec3(0.299, 0.587, 0.114));
+}
+
+// Fast approximate anti-aliasing: blurs along the edges found in the luma
+void main() {
+    vec2 uv = v_position * 0.5 + 0.5;
+    vec2 texel = 1.0 / vec2(textureSize(source, 0));
+
+    float luma_nw = luma(texture(source, uv + vec2(-1.0, -1.0) * texel).rgb);
+    float luma_ne = luma(texture(source, uv + vec2(1.0, -1.0) * texel).rgb);
+    float luma_sw = luma(texture(source, uv + vec2(-1.0, 1.0) * texel).rgb);
+    float luma_se = luma(texture(source, uv + vec2(1.0, 1.0) * texel).rgb);
+    float luma_m = luma(texture(source, uv).rgb);
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // The edge runs perpendicular to the luma gradient
+    vec2 direction = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texel;
+
+    vec3 rgb_a = 0.5 * (
+        texture(source, uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(source, uv + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 rgb_b = rgb_a * 0.5 + 0.25 * (
+        texture(source, uv + direction * -0.5).rgb +
+        texture(source, uv + direction * 0.5).rgb
+    );
+
+    // Fall back to the narrower blur if the wide one picked up another edge
+    float luma_b = luma(rgb_b);
+    color = vec4((luma_b < luma_min || luma_b > luma_max) ? rgb_a : rgb_b, 1.0);
+}
@@ -0,0 +1,20 @@
+// G-buffer access for the deferred lighting passes, pasted after the #version line.
+
+uniform sampler2D g_albedo;
+uniform sampler2D g_normal;
+uniform sampler2D g_material;
+uniform sampler2D g_emission;
+uniform sampler2D g_depth;
+uniform mat4 inverse_perspective;
+
+// Alpha of the albedo, 0.0 for Phong and 1.0 for PBR surfaces
+bool is_pbr(vec4 albedo) {
+    return albedo.a > 0.5;
+}
+
+// View space position of this pixel, read back from the depth buffer
+vec3 view_position(float depth) {
+    vec2 ndc = gl_FragCoord.xy / vec2(textureSize(g_depth, 0)) * 2.0 - 1.0;
+    vec4 position = inverse_perspective * vec4(ndc, depth * 2.0 - 1.0, 1.0);
+    return position.xyz / position.w;
+}
@@ -0,0 +1,24 @@
+#version 140
+
+in vec3 v_normal;
+in vec4 v_tangent;
+in vec2 v_tex_coords;
+in vec3 v_color;
+
+// The G-buffer layout is described on GBuffer in deferred.rs
+out vec4 albedo;
+out vec4 normal;
+out vec4 material;
+out vec4 emission;
+
+void main() {
+    vec4 metallic_roughness = texture(u_metallic_roughness_map, v_tex_coords);
+    float metallic = clamp(u_metallic * metallic_roughness.b, 0.0, 1.0);
+    float roughness = clamp(u_roughness * metallic_roughness.g, 0.04, 1.0);
+    float ao = u_ao * texture(u_occlusion_map, v_tex_coords).r;
+
+    albedo = vec4(u_base_color * v_color * texture(u_base_color_map, v_tex_coords).rgb, 1.0);
+    normal = vec4(surface_normal(v_normal, v_tangent, v_tex_coords), 0.0);
+    material = vec4(metallic, roughness, ao, 0.0);
+    emission = vec4(u_emissive * texture(u_emissive_map, v_tex_coords).rgb, 0.0);
+}
@@ -0,0 +1,22 @@
+#version 140
+
+in vec3 v_normal;
+in vec3 v_color;
+
+uniform vec3 u_ambient_color;
+uniform vec3 u_diffuse_color;
+uniform vec3 u_specular_color;
+uniform float u_shininess;
+
+// The G-buffer layout is described on GBuffer in deferred.rs
+out vec4 albedo;
+out vec4 normal;
+out vec4 material;
+out vec4 emission;
+
+void main() {
+    albedo = vec4(u_diffuse_color * v_color, 0.0);
+    normal = vec4(normalize(v_normal), 0.0);
+    material = vec4(u_specular_color, 0.0);
+    emission = vec4(u_ambient_color * v_color, u_shininess);
+}
@@ -0,0 +1,63 @@
+#version 140
+
+// Infinite grid on a horizontal plane. Every pixel's view ray is intersected
+// with the plane, the depth of the hit keeps the grid behind the scene.
+in vec2 v_position;
+
+uniform mat4 view_perspective;
+uniform mat4 inverse_view_perspective;
+uniform vec3 camera_position;
+uniform float height;
+uniform float spacing;
+uniform float major_every;
+uniform float fade_distance;
+uniform vec4 minor_color;
+uniform vec4 major_color;
+uniform vec4 x_axis_color;
+uniform vec4 z_axis_color;
+
+out vec4 color;
+
+vec3 unproject(float depth) {
+    vec4 position = inverse_view_perspective * vec4(v_position, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+// Coverage of lines every `cell` units, about one pixel wide at any distance
+float lines(vec2 coord, float cell) {
+    vec2 scaled = coord / cell;
+    vec2 width = fwidth(scaled);
+    vec2 distance = abs(fract(scaled - 0.5) - 0.5) / width;
+    return 1.0 - min(min(distance.x, distance.y), 1.0);
+}
+
+// Coverage of a single line where `coord` is zero
+float axis(float coord) {
+    return 1.0 - min(abs(coord) / fwidth(coord), 1.0);
+}
+
+void main() {
+    vec3 near = unproject(-1.0);
+    vec3 far = unproject(1.0);
+    float t = (height - near.y) / (far.y - near.y);
+    if (t <= 0.0 || t > 1.0) {
+        discard;
+    }
+    vec3 position = mix(near, far, t);
+
+    vec4 clip = view_perspective * vec4(position, 1.0);
+    gl_FragDepth = clip.z / clip.w * 0.5 + 0.5;
+
+    vec2 coord = position.xz;
+    vec4 grid = minor_color * lines(coord, spacing);
+    grid = mix(grid, major_color, lines(coord, spacing * major_every));
+    // The x axis runs where z is zero and the other way round
+    grid = mix(grid, x_axis_color, axis(coord.y));
+    grid = mix(grid, z_axis_color, axis(coord.x));
+
+    float fade = 1.0 - smoothstep(0.0, fade_distance, length(position.xz - camera_position.xz));
+    color = vec4(grid.rgb, grid.a * fade);
+    if (color.a <= 0.0) {
+        discard;
+    }
+}
@@ -0,0 +1,30 @@
+#version 140
+
+const float PI = 3.14159265359;
+const float SAMPLE_DELTA = 0.025;
+
+uniform samplerCube environment;
+
+out vec4 color;
+
+// Cosine weighted average of the environment over the hemisphere around the normal
+void main() {
+    vec3 N = normalize(face_direction());
+    vec3 up = abs(N.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, N));
+    up = cross(N, right);
+
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+            vec3 tangent = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 direction = tangent.x * right + tangent.y * up + tangent.z * N;
+            irradiance += texture(environment, direction).rgb * cos(theta) * sin(theta);
+            samples += 1.0;
+        }
+    }
+
+    // The 1/PI of the Lambertian BRDF cancels, shaders only multiply by the albedo
+    color = vec4(PI * irradiance / samples, 1.0);
+}
@@ -0,0 +1,128 @@
+// Shared lighting and shadow functions, pasted after the #version line of the shading programs.
+
+// Light in view space. u_light points towards the light.
+uniform int u_light_kind;
+uniform vec3 u_light;
+uniform vec3 u_light_position;
+uniform vec3 u_light_color;
+uniform float u_spot_cutoff;
+uniform float u_light_range;
+
+// Shadow mapping
+uniform bool u_shadows_enabled;
+uniform int u_cascade_count;
+uniform vec4 u_cascade_splits;
+uniform float u_shadow_bias;
+uniform float u_shadow_slope_bias;
+uniform int u_pcf_radius;
+uniform mat4 u_light_space0;
+uniform mat4 u_light_space1;
+uniform mat4 u_light_space2;
+uniform mat4 u_light_space3;
+uniform sampler2DShadow u_shadow_map0;
+uniform sampler2DShadow u_shadow_map1;
+uniform sampler2DShadow u_shadow_map2;
+uniform sampler2DShadow u_shadow_map3;
+
+// Image based lighting, the maps are looked up with world space directions
+uniform bool u_has_environment;
+uniform mat3 u_view_to_world;
+uniform float u_environment_intensity;
+uniform float u_prefiltered_levels;
+uniform samplerCube u_irradiance_map;
+uniform samplerCube u_prefiltered_map;
+uniform sampler2D u_brdf_lut;
+
+// Screen space ambient occlusion, one texel per pixel of the frame
+uniform bool u_ssao_enabled;
+uniform sampler2D u_ssao_map;
+
+const int LIGHT_SPOT = 1;
+const int LIGHT_POINT = 2;
+
+// Percentage closer filtering over a (2r + 1)^2 texel kernel
+float sample_shadow(sampler2DShadow shadow_map, mat4 light_space, vec3 world_position, float bias) {
+    vec4 light_position = light_space * vec4(world_position, 1.0);
+    vec3 coords = light_position.xyz / light_position.w * 0.5 + 0.5;
+
+    if (coords.z > 1.0 || any(lessThan(coords.xy, vec2(0.0))) || any(greaterThan(coords.xy, vec2(1.0)))) {
+        return 1.0;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0));
+    float lit = 0.0;
+    for (int x = -u_pcf_radius; x <= u_pcf_radius; ++x) {
+        for (int y = -u_pcf_radius; y <= u_pcf_radius; ++y) {
+            lit += texture(shadow_map, vec3(coords.xy + vec2(x, y) * texel, coords.z - bias));
+        }
+    }
+
+    float kernel = float(2 * u_pcf_radius + 1);
+    return lit / (kernel * kernel);
+}
+
+// Fades point and spot lights out towards their range, nothing beyond it is lit
+float range_falloff(float distance) {
+    float x = min(distance / u_light_range, 1.0);
+    float window = 1.0 - x * x * x * x;
+    return window * window;
+}
+
+// Direction towards the light from a view space position, with the spot cone and range falloff
+vec3 light_vector(vec3 position, out float attenuation) {
+    vec3 L = normalize(u_light);
+    attenuation = 1.0;
+    if (u_light_kind == LIGHT_POINT) {
+        L = normalize(u_light_position - position);
+        attenuation = range_falloff(length(u_light_position - position));
+    } else if (u_light_kind == LIGHT_SPOT) {
+        vec3 to_light = normalize(u_light_position - position);
+        attenuation = smoothstep(u_spot_cutoff, mix(u_spot_cutoff, 1.0, 0.1), dot(to_light, L))
+            * range_falloff(length(u_light_position - position));
+        L = to_light;
+    }
+    return L;
+}
+
+// 1.0 is fully lit, 0.0 is fully in shadow
+float shadow_factor(vec3 world_position, float view_depth, float n_dot_l) {
+    if (!u_shadows_enabled) {
+        return 1.0;
+    }
+
+    // The last split lies at the maximum shadow distance, nothing beyond is shadowed
+    if (view_depth >= u_cascade_splits[u_cascade_count - 1]) {
+        return 1.0;
+    }
+
+    float bias = max(u_shadow_slope_bias * (1.0 - n_dot_l), u_shadow_bias);
+
+    if (u_cascade_count <= 1 || view_depth < u_cascade_splits.x) {
+        return sample_shadow(u_shadow_map0, u_light_space0, world_position, bias);
+    } else if (u_cascade_count <= 2 || view_depth < u_cascade_splits.y) {
+        return sample_shadow(u_shadow_map1, u_light_space1, world_position, bias);
+    } else if (u_cascade_count <= 3 || view_depth < u_cascade_splits.z) {
+        return sample_shadow(u_shadow_map2, u_light_space2, world_position, bias);
+    }
+    return sample_shadow(u_shadow_map3, u_light_space3, world_position, bias);
+}
+
+// Diffuse light from the environment arriving at a surface with the view space normal N
+vec3 environment_irradiance(vec3 N) {
+    return texture(u_irradiance_map, u_view_to_world * N).rgb * u_environment_intensity;
+}
+
+// Environment reflected along the view space direction R, blurred by the roughness
+vec3 environment_reflection(vec3 R, float roughness) {
+    vec3 direction = u_view_to_world * R;
+    float level = roughness * u_prefiltered_levels;
+    return textureLod(u_prefiltered_map, direction, level).rgb * u_environment_intensity;
+}
+
+// Share of the ambient light reaching this pixel, 1.0 is unoccluded
+float ambient_occlusion() {
+    if (!u_ssao_enabled) {
+        return 1.0;
+    }
+    return texelFetch(u_ssao_map, ivec2(gl_FragCoord.xy), 0).r;
+}
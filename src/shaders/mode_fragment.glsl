@@ -0,0 +1,57 @@
+#version 150
+
+in vec3 v_normal;
+in vec3 v_face_normal;
+in vec2 v_tex_coords;
+in float v_view_depth;
+in vec4 v_color;
+in vec3 v_barycentric;
+
+// See `RenderMode::shader_mode`
+uniform int mode;
+uniform vec4 u_color;
+uniform vec4 wire_color;
+uniform float near;
+uniform float far;
+
+out vec4 color;
+
+const int WIREFRAME = 0;
+const int VERTEX_NORMALS = 1;
+const int FACE_NORMALS = 2;
+const int UV_CHECKER = 3;
+const int DEPTH = 4;
+const int UNLIT = 5;
+
+// Coverage of the triangle's edges, about one pixel wide
+float edge() {
+    vec3 width = fwidth(v_barycentric);
+    vec3 distance = smoothstep(vec3(0.0), width * 1.5, v_barycentric);
+    return 1.0 - min(min(distance.x, distance.y), distance.z);
+}
+
+void main() {
+    if (mode == WIREFRAME) {
+        float coverage = edge();
+        if (coverage <= 0.0) {
+            discard;
+        }
+        color = vec4(wire_color.rgb, wire_color.a * coverage);
+    } else if (mode == VERTEX_NORMALS) {
+        color = vec4(normalize(v_normal) * 0.5 + 0.5, 1.0);
+    } else if (mode == FACE_NORMALS) {
+        color = vec4(v_face_normal * 0.5 + 0.5, 1.0);
+    } else if (mode == UV_CHECKER) {
+        vec2 cell = floor(v_tex_coords * 8.0);
+        float checker = mod(cell.x + cell.y, 2.0);
+        // Tinted by the coordinates, so flipped or stretched UVs stand out
+        vec3 tint = vec3(fract(v_tex_coords), 1.0);
+        color = vec4(mix(0.2, 0.9, checker) * tint, 1.0);
+    } else if (mode == DEPTH) {
+        // Logarithmic, a linear ramp leaves everything close to the camera black
+        float depth = log(max(v_view_depth, near) / near) / log(far / near);
+        color = vec4(vec3(1.0 - clamp(depth, 0.0, 1.0)), 1.0);
+    } else {
+        color = u_color * v_color;
+    }
+}
@@ -0,0 +1,43 @@
+#version 150
+
+// Passes the triangles through, adding barycentric coordinates for the
+// wireframe and the face normal for flat shading
+layout(triangles) in;
+layout(triangle_strip, max_vertices = 3) out;
+
+in vec3 g_normal[];
+in vec3 g_world_position[];
+in vec2 g_tex_coords[];
+in float g_view_depth[];
+in vec4 g_color[];
+
+out vec3 v_normal;
+out vec3 v_face_normal;
+out vec2 v_tex_coords;
+out float v_view_depth;
+out vec4 v_color;
+out vec3 v_barycentric;
+
+// World space normal of the triangle, turned to the side of its vertex normals
+vec3 face_normal() {
+    vec3 normal = normalize(cross(
+        g_world_position[1] - g_world_position[0],
+        g_world_position[2] - g_world_position[0]
+    ));
+    return dot(normal, g_normal[0] + g_normal[1] + g_normal[2]) < 0.0 ? -normal : normal;
+}
+
+void main() {
+    vec3 face = face_normal();
+    for (int i = 0; i < 3; i++) {
+        gl_Position = gl_in[i].gl_Position;
+        v_normal = g_normal[i];
+        v_face_normal = face;
+        v_tex_coords = g_tex_coords[i];
+        v_view_depth = g_view_depth[i];
+        v_color = g_color[i];
+        v_barycentric = vec3(i == 0, i == 1, i == 2);
+        EmitVertex();
+    }
+    EndPrimitive();
+}
@@ -0,0 +1,31 @@
+#version 150
+
+// Shared by the render mode programs, the geometry shaders build on its outputs
+in vec3 position;
+in vec3 normal;
+in vec2 tex_coords;
+in mat4 instance_model;
+in vec4 instance_color;
+
+out vec3 g_normal;
+out vec3 g_world_position;
+out vec2 g_tex_coords;
+out float g_view_depth;
+out vec4 g_color;
+
+uniform mat4 view;
+uniform mat4 perspective;
+
+void main() {
+    mat3 normal_matrix = transpose(inverse(mat3(instance_model)));
+    vec4 world_position = instance_model * vec4(position, 1.0);
+    vec4 view_position = view * world_position;
+
+    g_normal = normalize(normal_matrix * normal);
+    g_world_position = world_position.xyz;
+    g_tex_coords = tex_coords;
+    g_view_depth = view_position.z;
+    g_color = instance_color;
+
+    gl_Position = perspective * view_position;
+}
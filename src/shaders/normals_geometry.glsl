@@ -0,0 +1,42 @@
+#version 150
+
+// Short lines along the vertex normals, or along the face normal from the
+// center of each triangle
+layout(triangles) in;
+layout(line_strip, max_vertices = 6) out;
+
+in vec3 g_normal[];
+in vec3 g_world_position[];
+
+uniform mat4 view_perspective;
+uniform float normal_length;
+uniform bool face;
+uniform vec4 line_color;
+
+out vec4 v_color;
+
+void line(vec3 from, vec3 direction) {
+    v_color = line_color;
+    gl_Position = view_perspective * vec4(from, 1.0);
+    EmitVertex();
+    gl_Position = view_perspective * vec4(from + direction * normal_length, 1.0);
+    EmitVertex();
+    EndPrimitive();
+}
+
+void main() {
+    if (face) {
+        vec3 normal = normalize(cross(
+            g_world_position[1] - g_world_position[0],
+            g_world_position[2] - g_world_position[0]
+        ));
+        if (dot(normal, g_normal[0] + g_normal[1] + g_normal[2]) < 0.0) {
+            normal = -normal;
+        }
+        line((g_world_position[0] + g_world_position[1] + g_world_position[2]) / 3.0, normal);
+    } else {
+        for (int i = 0; i < 3; i++) {
+            line(g_world_position[i], g_normal[i]);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+#version 140
+
+in vec3 v_normal;
+in vec4 v_tangent;
+in vec2 v_tex_coords;
+in vec3 v_position;
+in vec3 v_world_position;
+in float v_view_depth;
+in vec3 v_color;
+in float v_alpha;
+
+out vec4 color;
+
+void main() {
+    vec4 base_color = texture(u_base_color_map, v_tex_coords);
+    vec3 albedo = u_base_color * v_color * base_color.rgb;
+    float opacity = u_opacity * base_color.a * v_alpha;
+    vec4 metallic_roughness = texture(u_metallic_roughness_map, v_tex_coords);
+    float metallic = clamp(u_metallic * metallic_roughness.b, 0.0, 1.0);
+    float roughness = clamp(u_roughness * metallic_roughness.g, 0.04, 1.0);
+    // Baked occlusion from the material combined with the screen space one
+    float ao = u_ao * texture(u_occlusion_map, v_tex_coords).r * ambient_occlusion();
+    vec3 emissive = u_emissive * texture(u_emissive_map, v_tex_coords).rgb;
+
+    vec3 N = surface_normal(v_normal, v_tangent, v_tex_coords);
+    vec3 V = normalize(-v_position);
+
+    float attenuation;
+    vec3 L = light_vector(v_position, attenuation);
+    float n_dot_l = max(dot(N, L), 0.0);
+
+    float lit = attenuation * shadow_factor(v_world_position, v_view_depth, n_dot_l);
+    vec3 direct = pbr_direct(N, V, L, u_light_color * lit, albedo, metallic, roughness);
+    vec3 ambient = pbr_ambient(N, V, albedo, metallic, roughness, ao);
+
+    // Linear light, encoded for the display by the framebuffer or the tonemapping pass
+    color = vec4(ambient + direct + emissive, opacity);
+}
@@ -0,0 +1,31 @@
+// Metallic/roughness material inputs, pasted into the programs drawing PBR objects.
+
+uniform vec3 u_base_color;
+// Alpha of the base color
+uniform float u_opacity;
+uniform float u_metallic;
+uniform float u_roughness;
+uniform float u_ao;
+uniform vec3 u_emissive;
+uniform float u_normal_scale;
+uniform bool u_has_normal_map;
+
+uniform sampler2D u_base_color_map;
+uniform sampler2D u_metallic_roughness_map;
+uniform sampler2D u_occlusion_map;
+uniform sampler2D u_emissive_map;
+uniform sampler2D u_normal_map;
+
+// The interpolated normal, perturbed by the normal map if there is one
+vec3 surface_normal(vec3 normal, vec4 tangent, vec2 tex_coords) {
+    vec3 N = normalize(normal);
+    if (!u_has_normal_map || dot(tangent.xyz, tangent.xyz) < 1e-8) {
+        return N;
+    }
+
+    vec3 T = normalize(tangent.xyz - N * dot(N, tangent.xyz));
+    vec3 B = cross(N, T) * tangent.w;
+    vec3 n = texture(u_normal_map, tex_coords).xyz * 2.0 - 1.0;
+    n.xy *= u_normal_scale;
+    return normalize(mat3(T, B, N) * n);
+}
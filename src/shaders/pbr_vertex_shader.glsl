@@ -0,0 +1,38 @@
+#version 150
+
+in vec3 position;
+in vec3 normal;
+in vec2 tex_coords;
+in vec4 tangent;
+in vec4 color;
+in mat4 instance_model;
+in vec4 instance_color;
+
+out vec3 v_normal;
+out vec4 v_tangent;
+out vec2 v_tex_coords;
+out vec3 v_position;
+out vec3 v_world_position;
+out float v_view_depth;
+out vec3 v_color;
+out float v_alpha;
+
+uniform mat4 view;
+uniform mat4 perspective;
+
+void main() {
+    mat4 modelview = view * instance_model;
+    mat3 normal_matrix = transpose(inverse(mat3(modelview)));
+    vec4 view_position = modelview * vec4(position, 1.0);
+
+    v_normal = normal_matrix * normal;
+    v_tangent = vec4(mat3(modelview) * tangent.xyz, tangent.w);
+    v_tex_coords = tex_coords;
+    v_position = view_position.xyz;
+    v_world_position = (instance_model * vec4(position, 1.0)).xyz;
+    v_view_depth = view_position.z;
+    v_color = instance_color.rgb * color.rgb;
+    v_alpha = instance_color.a * color.a;
+
+    gl_Position = perspective * view_position;
+}
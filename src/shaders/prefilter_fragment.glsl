@@ -0,0 +1,28 @@
+#version 140
+
+const uint SAMPLE_COUNT = 512u;
+
+uniform samplerCube environment;
+uniform float roughness;
+
+out vec4 color;
+
+// Environment convolved with the GGX lobe, assuming the view along the normal
+void main() {
+    vec3 N = normalize(face_direction());
+    vec3 V = N;
+
+    vec3 prefiltered = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; ++i) {
+        vec3 H = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), N, roughness);
+        vec3 L = normalize(2.0 * dot(V, H) * H - V);
+        float n_dot_l = dot(N, L);
+        if (n_dot_l > 0.0) {
+            prefiltered += texture(environment, L).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+
+    color = vec4(prefiltered / weight, 1.0);
+}
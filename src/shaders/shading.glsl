@@ -0,0 +1,82 @@
+// Surface shading shared by the forward and deferred renderers, pasted after lighting.glsl.
+// Directions are in view space and point away from the surface.
+
+const float PI = 3.14159265359;
+
+// Constant ambient term for PBR surfaces in scenes without an environment
+const vec3 ambient_light = vec3(0.03);
+
+// Trowbridge-Reitz GGX normal distribution
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Smith's method with the Schlick-GGX approximation for direct lighting
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Fresnel averaged over the microfacets, rough surfaces reflect less at grazing angles
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Cook-Torrance reflection of the radiance arriving from L
+vec3 pbr_direct(vec3 N, vec3 V, vec3 L, vec3 radiance, vec3 albedo, float metallic, float roughness) {
+    vec3 H = normalize(V + L);
+    float n_dot_l = max(dot(N, L), 0.0);
+    float n_dot_v = max(dot(N, V), 1e-4);
+    float n_dot_h = max(dot(N, H), 0.0);
+
+    // Dielectrics reflect about 4%, metals tint the reflection with their albedo
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+    vec3 F = fresnel_schlick(max(dot(H, V), 0.0), f0);
+    float D = distribution_ggx(n_dot_h, roughness);
+    float G = geometry_smith(n_dot_v, n_dot_l, roughness);
+
+    vec3 specular = D * G * F / (4.0 * n_dot_v * max(n_dot_l, 1e-4));
+    vec3 k_diffuse = (vec3(1.0) - F) * (1.0 - metallic);
+    return (k_diffuse * albedo / PI + specular) * radiance * n_dot_l;
+}
+
+// Ambient light of a PBR surface, reflecting the environment if there is one
+vec3 pbr_ambient(vec3 N, vec3 V, vec3 albedo, float metallic, float roughness, float ao) {
+    if (!u_has_environment) {
+        return ambient_light * albedo * ao;
+    }
+
+    // Split sum approximation of the environment's specular reflection
+    float n_dot_v = max(dot(N, V), 1e-4);
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+    vec3 F = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    vec2 brdf = texture(u_brdf_lut, vec2(n_dot_v, roughness)).rg;
+    vec3 reflection = environment_reflection(reflect(-V, N), roughness);
+    vec3 k_ambient = (vec3(1.0) - F) * (1.0 - metallic);
+    return (k_ambient * environment_irradiance(N) * albedo + reflection * (F * brdf.x + brdf.y)) * ao;
+}
+
+// Blinn-Phong reflection of the radiance arriving from L
+vec3 phong_direct(vec3 N, vec3 V, vec3 L, vec3 radiance, vec3 diffuse_color, vec3 specular_color, float shininess) {
+    float diffuse = max(dot(N, L), 0.0);
+    float specular = pow(max(dot(normalize(L + V), N), 0.0), shininess);
+    return radiance * (diffuse * diffuse_color + specular * specular_color);
+}
+
+// The environment, if any, tints the ambient light
+vec3 phong_ambient(vec3 N, vec3 ambient_color) {
+    vec3 ambient = ambient_color * ambient_occlusion();
+    if (u_has_environment) {
+        ambient *= environment_irradiance(N);
+    }
+    return ambient;
+}
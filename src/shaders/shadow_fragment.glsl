@@ -0,0 +1,5 @@
+#version 140
+
+// Depth only pass: the depth buffer is written by the fixed function pipeline
+void main() {
+}
@@ -0,0 +1,10 @@
+#version 140
+
+in vec3 position;
+in mat4 instance_model;
+
+uniform mat4 light_space;
+
+void main() {
+    gl_Position = light_space * instance_model * vec4(position, 1.0);
+}
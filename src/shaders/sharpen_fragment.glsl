@@ -0,0 +1,22 @@
+#version 140
+
+in vec2 v_position;
+
+uniform sampler2D source;
+uniform float strength;
+
+out vec4 color;
+
+// Unsharp mask with the four direct neighbours
+void main() {
+    vec2 uv = v_position * 0.5 + 0.5;
+    vec2 texel = 1.0 / vec2(textureSize(source, 0));
+
+    vec3 center = texture(source, uv).rgb;
+    vec3 neighbours = texture(source, uv + vec2(texel.x, 0.0)).rgb
+        + texture(source, uv - vec2(texel.x, 0.0)).rgb
+        + texture(source, uv + vec2(0.0, texel.y)).rgb
+        + texture(source, uv - vec2(0.0, texel.y)).rgb;
+
+    color = vec4(clamp(center + (4.0 * center - neighbours) * strength, 0.0, 1.0), 1.0);
+}
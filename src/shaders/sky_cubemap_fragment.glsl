@@ -0,0 +1,12 @@
+#version 140
+
+in vec3 v_direction;
+
+uniform samplerCube environment;
+uniform float intensity;
+
+out vec4 color;
+
+void main() {
+    color = vec4(texture(environment, v_direction).rgb * intensity, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 140
+
+in vec3 v_direction;
+
+uniform vec3 zenith;
+uniform vec3 horizon;
+uniform vec3 ground;
+
+out vec4 color;
+
+void main() {
+    float height = normalize(v_direction).y;
+    vec3 sky = height > 0.0
+        ? mix(horizon, zenith, sqrt(height))
+        : mix(horizon, ground, sqrt(-height));
+    color = vec4(sky, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 140
+
+// Fullscreen triangle without vertex buffers. It sits on the far plane, so the
+// sky only shows where no geometry was drawn.
+uniform mat4 inverse_view_perspective;
+
+out vec3 v_direction;
+
+void main() {
+    vec2 position = vec2(gl_VertexID == 1 ? 3.0 : -1.0, gl_VertexID == 2 ? 3.0 : -1.0);
+    gl_Position = vec4(position, 1.0, 1.0);
+    v_direction = (inverse_view_perspective * vec4(position, 1.0, 1.0)).xyz;
+}
@@ -0,0 +1,21 @@
+#version 140
+
+in vec2 v_position;
+
+uniform sampler2D source;
+
+out vec4 color;
+
+// Box blur over the 4x4 tile of the noise texture, which hides the rotation pattern
+void main() {
+    vec2 uv = v_position * 0.5 + 0.5;
+    vec2 texel = 1.0 / vec2(textureSize(source, 0));
+
+    float result = 0.0;
+    for (int x = -2; x < 2; ++x) {
+        for (int y = -2; y < 2; ++y) {
+            result += texture(source, uv + vec2(x, y) * texel).r;
+        }
+    }
+    color = vec4(vec3(result / 16.0), 1.0);
+}
@@ -0,0 +1,60 @@
+#version 140
+
+in vec2 v_position;
+
+// View space normals and depth of the prepass, depth 0 where nothing was drawn
+uniform sampler2D normal_depth;
+// Random rotations around the normal, tiled over the screen
+uniform sampler2D noise;
+// Sample offsets in a unit hemisphere around +z, one per texel
+uniform sampler2D kernel;
+uniform int kernel_size;
+uniform mat4 perspective;
+uniform float radius;
+uniform float bias;
+uniform float intensity;
+
+out vec4 color;
+
+// View space position of the pixel at uv, undoing the perspective divide
+vec3 view_position(vec2 uv, float depth) {
+    vec2 ndc = uv * 2.0 - 1.0;
+    return vec3(ndc * depth / vec2(perspective[0][0], perspective[1][1]), depth);
+}
+
+void main() {
+    vec2 uv = v_position * 0.5 + 0.5;
+    vec4 center = texture(normal_depth, uv);
+    if (center.w <= 0.0) {
+        color = vec4(1.0);
+        return;
+    }
+
+    vec3 position = view_position(uv, center.w);
+    vec3 normal = normalize(center.xyz);
+
+    // Orient the kernel along the normal, rotated by the noise to trade banding for noise
+    vec2 noise_scale = vec2(textureSize(normal_depth, 0)) / vec2(textureSize(noise, 0));
+    vec3 random = vec3(texture(noise, uv * noise_scale).xy, 0.0);
+    vec3 tangent = normalize(random - normal * dot(random, normal));
+    mat3 tbn = mat3(tangent, cross(normal, tangent), normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < kernel_size; ++i) {
+        vec3 sample_position = position + tbn * texelFetch(kernel, ivec2(i, 0), 0).xyz * radius;
+        vec4 projected = perspective * vec4(sample_position, 1.0);
+        vec2 sample_uv = projected.xy / projected.w * 0.5 + 0.5;
+
+        float sample_depth = texture(normal_depth, sample_uv).w;
+        if (sample_depth <= 0.0) {
+            continue;
+        }
+
+        // Geometry far in front of the sample, like a silhouette edge, does not occlude it
+        float range = smoothstep(0.0, 1.0, radius / abs(position.z - sample_depth));
+        occlusion += (sample_depth <= sample_position.z - bias ? 1.0 : 0.0) * range;
+    }
+
+    float visibility = 1.0 - occlusion / float(kernel_size);
+    color = vec4(vec3(pow(visibility, intensity)), 1.0);
+}
@@ -0,0 +1,11 @@
+#version 140
+
+in vec3 v_normal;
+in float v_view_depth;
+
+// View space normal and the distance along the view direction
+out vec4 normal_depth;
+
+void main() {
+    normal_depth = vec4(normalize(v_normal), v_view_depth);
+}
@@ -0,0 +1,21 @@
+#version 150
+
+in vec3 position;
+in vec3 normal;
+in mat4 instance_model;
+
+out vec3 v_normal;
+out float v_view_depth;
+
+uniform mat4 view;
+uniform mat4 perspective;
+
+void main() {
+    mat4 modelview = view * instance_model;
+    vec4 view_position = modelview * vec4(position, 1.0);
+
+    v_normal = transpose(inverse(mat3(modelview))) * normal;
+    v_view_depth = view_position.z;
+
+    gl_Position = perspective * view_position;
+}
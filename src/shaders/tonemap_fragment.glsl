@@ -0,0 +1,44 @@
+#version 140
+
+const int OPERATOR_CLAMP = 0;
+const int OPERATOR_REINHARD = 1;
+const int OPERATOR_ACES = 2;
+
+in vec2 v_position;
+
+uniform sampler2D hdr_color;
+// Blurred highlights added on top, see the bloom post effect
+uniform sampler2D bloom;
+uniform float bloom_intensity;
+uniform int operator;
+uniform float exposure;
+
+out vec4 color;
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+vec3 linear_to_srgb(vec3 linear) {
+    vec3 low = linear * 12.92;
+    vec3 high = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(high, low, vec3(lessThanEqual(linear, vec3(0.0031308))));
+}
+
+void main() {
+    vec2 uv = v_position * 0.5 + 0.5;
+    vec3 hdr = texture(hdr_color, uv).rgb + texture(bloom, uv).rgb * bloom_intensity;
+    hdr *= exposure;
+
+    vec3 mapped;
+    if (operator == OPERATOR_REINHARD) {
+        mapped = hdr / (hdr + vec3(1.0));
+    } else if (operator == OPERATOR_ACES) {
+        mapped = aces(hdr);
+    } else {
+        mapped = clamp(hdr, 0.0, 1.0);
+    }
+
+    color = vec4(linear_to_srgb(mapped), 1.0);
+}
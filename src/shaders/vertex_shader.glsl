@@ -2,17 +2,30 @@
 
 in vec3 position;
 in vec3 normal;
+in vec4 color;
+in mat4 instance_model;
+in vec4 instance_color;
 
 out vec3 v_normal;
 out vec3 v_position;
+out vec3 v_world_position;
+out float v_view_depth;
+out vec3 v_color;
+out float v_alpha;
 
-uniform mat4 model;
 uniform mat4 view;
 uniform mat4 perspective;
 
 void main() {
-    mat4 modelview = view * model;
+    mat4 modelview = view * instance_model;
+    vec4 view_position = modelview * vec4(position, 1.0);
+
     v_normal = transpose(inverse(mat3(modelview))) * normal;
-    gl_Position = perspective * modelview * vec4(position, 1.0);
-    v_position = gl_Position.xyz / gl_Position.w;
-}
\ No newline at end of file
+    v_position = view_position.xyz;
+    v_world_position = (instance_model * vec4(position, 1.0)).xyz;
+    v_view_depth = view_position.z;
+    v_color = instance_color.rgb * color.rgb;
+    v_alpha = instance_color.a * color.a;
+
+    gl_Position = perspective * view_position;
+}
@@ -0,0 +1,16 @@
+#version 140
+
+in vec2 v_position;
+
+uniform sampler2D source;
+uniform float strength;
+// Distance from the center, in half screen sizes, where the darkening starts
+uniform float radius;
+
+out vec4 color;
+
+void main() {
+    vec2 uv = v_position * 0.5 + 0.5;
+    float falloff = smoothstep(radius, radius + 0.8, length(v_position));
+    color = vec4(texture(source, uv).rgb * (1.0 - falloff * strength), 1.0);
+}
@@ -0,0 +1,335 @@
+use crate::error::{allocation, shader};
+use crate::instancing::{self, InstanceBuffer};
+use crate::lod;
+use crate::{
+    look_to, orthographic, perspective, uniform, Camera, Display, DrawParameters, Error, Light,
+    LightKind, Matrix4, Point3, Program, Scalar, SceneObject, Surface, Vector3, PI,
+};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{DepthFormat, DepthTexture2d, MipmapsOption};
+use glium::uniforms::{
+    DepthTextureComparison, MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior,
+    SamplerWrapFunction, UniformValue, Uniforms,
+};
+
+/// The fragment shader has one sampler per cascade, so this is a hard limit
+pub const MAX_CASCADES: usize = 4;
+
+/// Widest field of view of a spot light's shadow map, in radians. A perspective
+/// projection breaks down towards 180 degrees, wider cones are only shadowed
+/// in their middle.
+const MAX_SPOT_FOV: Scalar = 170.0 * PI / 180.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowSettings {
+    /// Width and height of every shadow map in texels
+    pub resolution: u32,
+    /// Constant depth bias against shadow acne
+    pub bias: Scalar,
+    /// Extra bias applied to surfaces at grazing angles to the light
+    pub slope_bias: Scalar,
+    /// PCF kernel radius in texels: 0 is a single hard sample, 1 a 3x3 kernel, ...
+    pub pcf_radius: i32,
+    /// Number of cascades used for directional lights
+    pub cascades: usize,
+    /// Blend between logarithmic (1.0) and uniform (0.0) cascade splits
+    pub split_lambda: Scalar,
+    /// Shadows are not rendered beyond this distance from the camera
+    pub max_distance: Scalar,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 2048,
+            bias: 0.0005,
+            slope_bias: 0.005,
+            pcf_radius: 1,
+            cascades: 3,
+            split_lambda: 0.75,
+            max_distance: 50.0,
+        }
+    }
+}
+
+pub struct ShadowMap {
+    settings: ShadowSettings,
+    program: Program,
+    textures: Vec<DepthTexture2d>,
+    light_space: [[[f32; 4]; 4]; MAX_CASCADES],
+    splits: [f32; MAX_CASCADES],
+    cascade_count: usize,
+    instances: InstanceBuffer,
+}
+
+impl ShadowMap {
+    pub fn new(display: &Display, settings: ShadowSettings) -> Result<Self, Error> {
+        let program = define_shadow_shaders(display).map_err(shader("shadow"))?;
+        let textures = (0..MAX_CASCADES)
+            .map(|_| {
+                DepthTexture2d::empty_with_format(
+                    display,
+                    DepthFormat::F32,
+                    MipmapsOption::NoMipmap,
+                    settings.resolution,
+                    settings.resolution,
+                )
+                .map_err(allocation("shadow map"))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            settings,
+            program,
+            textures,
+            light_space: [Matrix4::identity().into(); MAX_CASCADES],
+            splits: [0.0; MAX_CASCADES],
+            cascade_count: 0,
+            instances: InstanceBuffer::new(display)?,
+        })
+    }
+
+    pub fn settings(&self) -> &ShadowSettings {
+        &self.settings
+    }
+
+    /// Changes the settings, reallocating the shadow maps if the resolution changed
+    pub fn set_settings(
+        &mut self,
+        display: &Display,
+        settings: ShadowSettings,
+    ) -> Result<(), Error> {
+        if settings.resolution != self.settings.resolution {
+            *self = Self::new(display, settings)?;
+        } else {
+            self.settings = settings;
+        }
+        Ok(())
+    }
+
+    /// Depth-only pass rendering the objects from the point of view of the light.
+    /// Directional lights get one map per cascade fitted to the camera frustum.
+    pub fn update(
+        &mut self,
+        display: &Display,
+        object_list: &[Box<SceneObject>],
+        camera: &Camera,
+        light: &Light,
+    ) -> Result<(), Error> {
+        if !light.casts_shadows {
+            self.cascade_count = 0;
+            return Ok(());
+        }
+
+        lod::select_levels(object_list, camera);
+        match light.kind {
+            LightKind::Directional => self.fit_cascades(camera, light),
+            LightKind::Spot { angle, range } => {
+                let view = look_to(light.position, light.direction);
+                self.light_space[0] = (spot_projection(angle, range) * view).into();
+                self.splits[0] = camera.far().min(self.settings.max_distance);
+                self.cascade_count = 1;
+            }
+            LightKind::Point { .. } => {
+                self.cascade_count = 0;
+                return Ok(());
+            }
+        }
+
+        let draw_params = DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // Casters only need their mesh to match to be drawn together
+        let batches = instancing::batches(object_list, false);
+
+        for cascade in 0..self.cascade_count {
+            let mut target = SimpleFrameBuffer::depth_only(display, &self.textures[cascade])
+                .map_err(allocation("shadow framebuffer"))?;
+            target.clear_depth(1.0);
+
+            for batch in &batches {
+                let instance_slice = self.instances.upload(&batch.instances)?;
+                let instances = instance_slice
+                    .per_instance()
+                    .map_err(|_| Error::InstancingNotSupported)?;
+                let uniforms = uniform! {
+                    light_space: self.light_space[cascade],
+                };
+
+                target.draw(
+                    (&batch.object.drawn_mesh().vertex_bfr, instances),
+                    &batch.object.drawn_mesh().ind_bfr,
+                    &self.program,
+                    &uniforms,
+                    &draw_params,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Splits the camera frustum and fits an orthographic light projection around each slice
+    fn fit_cascades(&mut self, camera: &Camera, light: &Light) {
+        let count = self.settings.cascades.clamp(1, MAX_CASCADES);
+        let near = camera.near();
+        let far = camera.far().min(self.settings.max_distance);
+        let lambda = self.settings.split_lambda;
+
+        // Only the orientation matters, the ortho box is placed in light space
+        let view = look_to(Vector3::zeros(), light.direction);
+
+        let mut previous = near;
+        for i in 0..count {
+            let p = (i + 1) as Scalar / count as Scalar;
+            let split =
+                lambda * near * (far / near).powf(p) + (1.0 - lambda) * (near + (far - near) * p);
+
+            let corners = camera.frustum_corners(previous, split);
+            let center = corners.iter().fold(Vector3::zeros(), |acc, c| acc + c) / 8.0;
+            let radius = corners
+                .iter()
+                .map(|c| (c - center).norm())
+                .fold(0.0, Scalar::max);
+
+            // Keep the box size and position texel aligned to stop shadow edges from shimmering
+            let radius = (radius * 16.0).ceil() / 16.0;
+            let texel = 2.0 * radius / self.settings.resolution as Scalar;
+            let center = view.transform_point(&Point3::from(center));
+            let (x, y) = (
+                (center.x / texel).floor() * texel,
+                (center.y / texel).floor() * texel,
+            );
+
+            // Extend the box towards the light so casters outside the frustum still cast
+            let projection = orthographic(
+                x - radius,
+                x + radius,
+                y - radius,
+                y + radius,
+                center.z - radius - self.settings.max_distance,
+                center.z + radius,
+            );
+
+            self.light_space[i] = (projection * view).into();
+            self.splits[i] = split;
+            previous = split;
+        }
+
+        self.cascade_count = count;
+    }
+}
+
+/// Shadow map uniforms for the main shading pass. Without a shadow map the
+/// samplers are still bound to `fallback` so no two sampler types share a texture unit.
+#[derive(Copy, Clone)]
+pub struct ShadowUniforms<'s> {
+    pub shadow_map: Option<&'s ShadowMap>,
+    pub fallback: &'s DepthTexture2d,
+}
+
+const MAPS: [&str; MAX_CASCADES] = [
+    "u_shadow_map0",
+    "u_shadow_map1",
+    "u_shadow_map2",
+    "u_shadow_map3",
+];
+
+const MATRICES: [&str; MAX_CASCADES] = [
+    "u_light_space0",
+    "u_light_space1",
+    "u_light_space2",
+    "u_light_space3",
+];
+
+impl<'s> Uniforms for ShadowUniforms<'s> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let sampler = SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+            ),
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            depth_texture_comparison: Some(DepthTextureComparison::LessOrEqual),
+            ..Default::default()
+        };
+
+        let shadow_map = match self.shadow_map {
+            Some(shadow_map) if shadow_map.cascade_count > 0 => shadow_map,
+            _ => {
+                output("u_shadows_enabled", UniformValue::Bool(false));
+                for name in MAPS.iter() {
+                    output(
+                        name,
+                        UniformValue::DepthTexture2d(self.fallback, Some(sampler)),
+                    );
+                }
+                return;
+            }
+        };
+
+        let settings = &shadow_map.settings;
+        output("u_shadows_enabled", UniformValue::Bool(true));
+        output(
+            "u_cascade_count",
+            UniformValue::SignedInt(shadow_map.cascade_count as i32),
+        );
+        output("u_cascade_splits", UniformValue::Vec4(shadow_map.splits));
+        output("u_shadow_bias", UniformValue::Float(settings.bias));
+        output(
+            "u_shadow_slope_bias",
+            UniformValue::Float(settings.slope_bias),
+        );
+        output("u_pcf_radius", UniformValue::SignedInt(settings.pcf_radius));
+
+        let cascades = MAPS.iter().zip(MATRICES.iter());
+        for ((map, matrix), (texture, light_space)) in cascades.zip(
+            shadow_map
+                .textures
+                .iter()
+                .zip(shadow_map.light_space.iter()),
+        ) {
+            output(matrix, UniformValue::Mat4(*light_space));
+            output(map, UniformValue::DepthTexture2d(texture, Some(sampler)));
+        }
+    }
+}
+
+/// Reads the depth-only GLSL files as &str and feeds them to glium::Program
+/// Projection of a spot light's shadow map, covering the cone up to `MAX_SPOT_FOV`
+fn spot_projection(angle: Scalar, range: Scalar) -> Matrix4 {
+    perspective((2.0 * angle).min(MAX_SPOT_FOV), 1.0, 0.05, range)
+}
+
+pub fn define_shadow_shaders(display: &Display) -> Result<Program, glium::ProgramCreationError> {
+    let v_shader = include_str!(r"shaders/shadow_vertex.glsl");
+    let f_shader = include_str!(r"shaders/shadow_fragment.glsl");
+    Program::from_source(display, v_shader, f_shader, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_spot_lights_keep_a_valid_projection() {
+        // Inside every cone below, a little to the right of its axis
+        let point = Point3::new(1.0, 0.0, 5.0);
+        for &angle in &[0.5, PI / 2.0, 2.0, PI] {
+            let projection = spot_projection(angle, 10.0);
+            assert!(projection.iter().all(|x| x.is_finite()));
+            // The point stays inside the map, on the same side
+            let projected = projection.transform_point(&point);
+            assert!(projected.x > 0.0 && projected.x < 1.0);
+            assert!(projected.z.abs() < 1.0);
+        }
+    }
+}
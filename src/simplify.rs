@@ -0,0 +1,415 @@
+//! Mesh decimation by quadric error metrics (Garland and Heckbert, 1997).
+//! Edges are collapsed cheapest first, where the cost of moving a point is
+//! its squared distance to the planes of the triangles it came from.
+
+use crate::{MeshData, Scalar};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+type Quadric = nalgebra::Matrix4<f64>;
+type Point = nalgebra::Vector3<f64>;
+
+/// Weight of the planes holding open borders in place, relative to the triangles' planes
+const BOUNDARY_WEIGHT: f64 = 100.0;
+
+/// Smallest cosine between a triangle's normal before and after a collapse.
+/// Collapses turning a triangle further are rejected, they fold the surface over.
+const MIN_NORMAL_COS: f64 = 0.3;
+
+/// Simplifies a mesh down to about `target_triangles` triangles, or as far as
+/// possible without folding the surface over. Vertices at the same position
+/// move together, so UV seams and hard edges don't tear open. The other
+/// attributes of the remaining vertices are kept as they were.
+pub fn simplify(data: &MeshData, target_triangles: usize) -> MeshData {
+    let mut simplifier = Simplifier::new(data);
+    simplifier.run(target_triangles);
+    simplifier.finish()
+}
+
+/// Successively simplified copies of a mesh for levels of detail, starting
+/// with the mesh itself. Each level has about `ratio` times the triangles of
+/// the one before. Stops after `levels` meshes, before one would have fewer
+/// than `min_triangles` triangles, or when the mesh can't be simplified further.
+pub fn lod_chain(
+    data: &MeshData,
+    levels: usize,
+    ratio: Scalar,
+    min_triangles: usize,
+) -> Vec<MeshData> {
+    let mut chain = vec![data.clone()];
+    while chain.len() < levels {
+        let previous = &chain[chain.len() - 1];
+        let target = (previous.triangle_count() as Scalar * ratio) as usize;
+        if target < min_triangles {
+            break;
+        }
+        let next = simplify(previous, target);
+        // Collapses were rejected before getting anywhere close
+        if next.triangle_count() as Scalar > previous.triangle_count() as Scalar * ratio.sqrt() {
+            break;
+        }
+        chain.push(next);
+    }
+    chain
+}
+
+/// The vertices sharing a position, moved and merged as one
+struct Node {
+    position: Point,
+    quadric: Quadric,
+    vertices: Vec<usize>,
+    /// Triangles using the node, including ones already removed
+    triangles: Vec<usize>,
+    /// Bumped by every collapse into the node, invalidating older candidates
+    version: u32,
+    alive: bool,
+}
+
+/// A possible collapse of the edge between two nodes into one point
+struct Candidate {
+    cost: f64,
+    nodes: [usize; 2],
+    versions: [u32; 2],
+    position: Point,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    /// Reversed, so the cheapest collapse comes first out of the max-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+struct Simplifier<'a> {
+    data: &'a MeshData,
+    nodes: Vec<Node>,
+    node_of: Vec<usize>,
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    alive_count: usize,
+    candidates: BinaryHeap<Candidate>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(data: &'a MeshData) -> Self {
+        let mut nodes = Vec::new();
+        let mut node_of = Vec::with_capacity(data.vertex_count());
+        let mut by_position: HashMap<[u32; 3], usize> = HashMap::new();
+        for vertex in 0..data.vertex_count() {
+            let node = *by_position
+                .entry(data.position_key(vertex))
+                .or_insert_with(|| {
+                    nodes.push(Node {
+                        position: data.position(vertex).cast(),
+                        quadric: Quadric::zeros(),
+                        vertices: Vec::new(),
+                        triangles: Vec::new(),
+                        version: 0,
+                        alive: true,
+                    });
+                    nodes.len() - 1
+                });
+            nodes[node].vertices.push(vertex);
+            node_of.push(node);
+        }
+
+        let triangles: Vec<[usize; 3]> = data.triangles().collect();
+        let mut simplifier = Self {
+            data,
+            nodes,
+            node_of,
+            alive: vec![true; triangles.len()],
+            alive_count: triangles.len(),
+            triangles,
+            candidates: BinaryHeap::new(),
+        };
+
+        // Planes of the triangles, weighted by area
+        let mut edges: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        for t in 0..simplifier.triangles.len() {
+            let corners = simplifier.corner_nodes(t);
+            let [a, b, c] = corners;
+            if a == b || b == c || c == a {
+                simplifier.alive[t] = false;
+                simplifier.alive_count -= 1;
+                continue;
+            }
+            let normal = simplifier.normal(t, None);
+            let area = normal.norm() / 2.0;
+            if let Some(normal) = normal.try_normalize(1e-12) {
+                let plane = plane_quadric(&normal, &simplifier.nodes[a].position) * area;
+                for &node in &corners {
+                    simplifier.nodes[node].quadric += plane;
+                }
+            }
+            for corner in 0..3 {
+                let node = corners[corner];
+                simplifier.nodes[node].triangles.push(t);
+                let (from, to) = (node, corners[(corner + 1) % 3]);
+                edges
+                    .entry((from.min(to), from.max(to)))
+                    .or_insert((0, t))
+                    .0 += 1;
+            }
+        }
+
+        // Planes through open borders, perpendicular to their triangle
+        for (&(a, b), &(count, t)) in &edges {
+            if count == 1 {
+                let (pa, pb) = (simplifier.nodes[a].position, simplifier.nodes[b].position);
+                let edge = pb - pa;
+                let normal = simplifier.normal(t, None);
+                if let Some(normal) = edge.cross(&normal).try_normalize(1e-12) {
+                    let plane =
+                        plane_quadric(&normal, &pa) * (BOUNDARY_WEIGHT * edge.norm_squared());
+                    simplifier.nodes[a].quadric += plane;
+                    simplifier.nodes[b].quadric += plane;
+                }
+            }
+        }
+
+        for &(a, b) in edges.keys() {
+            simplifier.push_candidate(a, b);
+        }
+        simplifier
+    }
+
+    fn run(&mut self, target_triangles: usize) {
+        while self.alive_count > target_triangles {
+            let candidate = match self.candidates.pop() {
+                Some(candidate) => candidate,
+                None => break,
+            };
+            let [a, b] = candidate.nodes;
+            let current = |node: usize, version: u32| {
+                self.nodes[node].alive && self.nodes[node].version == version
+            };
+            if !current(a, candidate.versions[0]) || !current(b, candidate.versions[1]) {
+                continue;
+            }
+            if self.folds_over(a, b, &candidate.position)
+                || self.folds_over(b, a, &candidate.position)
+            {
+                continue;
+            }
+            self.collapse(a, b, candidate.position);
+        }
+    }
+
+    /// Moves `b` into `a` at `position`, removing the triangles between them
+    fn collapse(&mut self, a: usize, b: usize, position: Point) {
+        let quadric = self.nodes[a].quadric + self.nodes[b].quadric;
+        let moved = std::mem::take(&mut self.nodes[b].triangles);
+        let vertices = std::mem::take(&mut self.nodes[b].vertices);
+        self.nodes[b].alive = false;
+        for &vertex in &vertices {
+            self.node_of[vertex] = a;
+        }
+
+        let node = &mut self.nodes[a];
+        node.position = position;
+        node.quadric = quadric;
+        node.version += 1;
+        node.vertices.extend(vertices);
+        node.triangles.extend(moved);
+        node.triangles.sort_unstable();
+        node.triangles.dedup();
+
+        let mut triangles = std::mem::take(&mut self.nodes[a].triangles);
+        triangles.retain(|&t| {
+            if !self.alive[t] {
+                return false;
+            }
+            let [x, y, z] = self.corner_nodes(t);
+            if x == y || y == z || z == x {
+                self.alive[t] = false;
+                self.alive_count -= 1;
+                return false;
+            }
+            true
+        });
+
+        let mut neighbours: Vec<usize> = triangles
+            .iter()
+            .flat_map(|&t| self.corner_nodes(t))
+            .filter(|&node| node != a)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        self.nodes[a].triangles = triangles;
+        for neighbour in neighbours {
+            self.push_candidate(a, neighbour);
+        }
+    }
+
+    /// Whether moving `node` to `position` turns one of its triangles not shared with `other` over
+    fn folds_over(&self, node: usize, other: usize, position: &Point) -> bool {
+        self.nodes[node].triangles.iter().any(|&t| {
+            if !self.alive[t] || self.corner_nodes(t).contains(&other) {
+                return false;
+            }
+            let before = self.normal(t, None);
+            let after = self.normal(t, Some((node, position)));
+            match (before.try_normalize(1e-12), after.try_normalize(1e-12)) {
+                (Some(before), Some(after)) => before.dot(&after) < MIN_NORMAL_COS,
+                _ => true,
+            }
+        })
+    }
+
+    fn push_candidate(&mut self, a: usize, b: usize) {
+        let quadric = self.nodes[a].quadric + self.nodes[b].quadric;
+        let (pa, pb) = (self.nodes[a].position, self.nodes[b].position);
+        let midpoint = (pa + pb) / 2.0;
+
+        // The point of least error, unless it's ill-defined or far off, e.g. on flat areas
+        let mut choices = vec![pa, pb, midpoint];
+        let mut solve = quadric;
+        solve.set_row(3, &nalgebra::RowVector4::new(0.0, 0.0, 0.0, 1.0));
+        if let Some(inverse) = solve.try_inverse() {
+            let optimum = inverse.column(3).xyz();
+            if (optimum - midpoint).norm() <= (pb - pa).norm() {
+                choices.push(optimum);
+            }
+        }
+        let (cost, position) = choices
+            .into_iter()
+            .map(|p| (error(&quadric, &p), p))
+            .min_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(Ordering::Equal))
+            .unwrap_or((0.0, midpoint));
+
+        self.candidates.push(Candidate {
+            cost,
+            nodes: [a, b],
+            versions: [self.nodes[a].version, self.nodes[b].version],
+            position,
+        });
+    }
+
+    fn corner_nodes(&self, triangle: usize) -> [usize; 3] {
+        self.triangles[triangle].map(|vertex| self.node_of[vertex])
+    }
+
+    /// Normal of a triangle with twice its area as length, optionally with one node moved
+    fn normal(&self, triangle: usize, moved: Option<(usize, &Point)>) -> Point {
+        let [a, b, c] = self.corner_nodes(triangle).map(|node| match moved {
+            Some((moved, position)) if moved == node => *position,
+            _ => self.nodes[node].position,
+        });
+        (b - a).cross(&(c - a))
+    }
+
+    /// The remaining triangles, with the vertices at their nodes' positions
+    fn finish(self) -> MeshData {
+        let mut result = self.data.clone();
+        for (vertex, &node) in self.node_of.iter().enumerate() {
+            let p = self.nodes[node].position;
+            result.vertices[vertex].position = (p.x as Scalar, p.y as Scalar, p.z as Scalar);
+        }
+        result.indices = self
+            .triangles
+            .iter()
+            .zip(&self.alive)
+            .filter(|(_, &alive)| alive)
+            .flat_map(|(triangle, _)| triangle.iter().map(|&vertex| vertex as u32))
+            .collect();
+        result.remove_unused_vertices();
+        result
+    }
+}
+
+/// The quadric measuring the squared distance to the plane through `point` facing `normal`
+fn plane_quadric(normal: &Point, point: &Point) -> Quadric {
+    let plane = nalgebra::Vector4::new(normal.x, normal.y, normal.z, -normal.dot(point));
+    plane * plane.transpose()
+}
+
+fn error(quadric: &Quadric, point: &Point) -> f64 {
+    let p = point.push(1.0);
+    (p.transpose() * quadric * p)[0].max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh_data::tests::grid;
+
+    /// The grid bent into a bump, so collapses have a cost
+    fn bumpy_grid(n: usize) -> MeshData {
+        let mut data = grid(n);
+        for vertex in &mut data.vertices {
+            let (x, y, _) = vertex.position;
+            let (u, v) = (x / n as Scalar - 0.5, y / n as Scalar - 0.5);
+            vertex.position.2 = (1.0 - 4.0 * (u * u + v * v)).max(0.0) * n as Scalar / 4.0;
+        }
+        data
+    }
+
+    #[test]
+    fn simplify_reaches_the_target() {
+        let data = bumpy_grid(20);
+        assert_eq!(data.triangle_count(), 800);
+        for &target in &[400, 200, 100] {
+            let simplified = simplify(&data, target);
+            let triangles = simplified.triangle_count();
+            assert!(
+                triangles <= target && triangles + 2 >= target,
+                "{}",
+                triangles
+            );
+            assert!(simplified.stats().is_manifold());
+        }
+    }
+
+    #[test]
+    fn simplify_keeps_the_border_in_place() {
+        let data = grid(20);
+        let simplified = simplify(&data, 100);
+        assert!(simplified.triangle_count() <= 100);
+        // A flat square keeps its area and corners as long as its border stays
+        let (before, after) = (data.stats(), simplified.stats());
+        assert_eq!((after.min, after.max), (before.min, before.max));
+        assert!((after.area - before.area).abs() < 1e-3);
+        for triangle in simplified.triangles() {
+            assert!(simplified.face_normal(triangle).z > 0.0);
+        }
+    }
+
+    #[test]
+    fn lod_chain_decreases_monotonically() {
+        let data = bumpy_grid(32);
+        let chain = lod_chain(&data, 5, 0.5, 100);
+        assert!(chain.len() >= 3);
+        assert!(chain.len() <= 5);
+        assert_eq!(chain[0].triangle_count(), data.triangle_count());
+        for pair in chain.windows(2) {
+            let (previous, next) = (pair[0].triangle_count(), pair[1].triangle_count());
+            assert!(next < previous);
+            assert!(
+                next <= previous / 2 && next >= 100,
+                "{} after {}",
+                next,
+                previous
+            );
+        }
+        assert_eq!(lod_chain(&data, 1, 0.5, 100).len(), 1);
+        assert_eq!(lod_chain(&data, 5, 0.5, 10_000).len(), 1);
+    }
+}
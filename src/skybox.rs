@@ -0,0 +1,284 @@
+use crate::error::{allocation, shader};
+use crate::rgbe::load_hdr;
+use crate::{paste_after_version, srgb_to_linear, Surface, DARKGRAY, LIGHTGRAY, SKYBLUE};
+use crate::{uniform, Camera, Color, Display, Error, Matrix4, Program, Scalar, Shaders};
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::{
+    CubeLayer, Cubemap, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat,
+};
+use glium::uniforms::{MagnifySamplerFilter, SamplerWrapFunction, Uniforms};
+use glium::vertex::EmptyVertexAttributes;
+use glium::{BlitTarget, DrawParameters};
+use std::path::Path;
+use std::rc::Rc;
+
+/// The cubemap faces in the order OpenGL numbers them
+pub const CUBE_FACES: [CubeLayer; 6] = [
+    CubeLayer::PositiveX,
+    CubeLayer::NegativeX,
+    CubeLayer::PositiveY,
+    CubeLayer::NegativeY,
+    CubeLayer::PositiveZ,
+    CubeLayer::NegativeZ,
+];
+
+/// Background drawn wherever no geometry covers the frame
+#[derive(Clone)]
+pub enum Sky {
+    /// Vertical gradient from the ground over the horizon up to the zenith
+    Gradient {
+        zenith: Color,
+        horizon: Color,
+        ground: Color,
+    },
+    /// An environment cubemap in linear color, scaled by `intensity`
+    Cubemap {
+        cubemap: Rc<Cubemap>,
+        intensity: Scalar,
+    },
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Sky::Gradient {
+            zenith: SKYBLUE,
+            horizon: LIGHTGRAY,
+            ground: DARKGRAY,
+        }
+    }
+}
+
+impl Sky {
+    pub fn cubemap(cubemap: Rc<Cubemap>) -> Self {
+        Sky::Cubemap {
+            cubemap,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// The programs drawing the two kinds of sky
+pub(crate) struct SkyPrograms {
+    gradient: Program,
+    cubemap: Program,
+}
+
+impl SkyPrograms {
+    pub(crate) fn new(display: &Display) -> Result<Self, Error> {
+        let v_shader = include_str!(r"shaders/sky_vertex.glsl");
+        let gradient_shader = include_str!(r"shaders/sky_gradient_fragment.glsl");
+        let cubemap_shader = include_str!(r"shaders/sky_cubemap_fragment.glsl");
+        Ok(Self {
+            gradient: Program::from_source(display, v_shader, gradient_shader, None)
+                .map_err(shader("gradient sky"))?,
+            cubemap: Program::from_source(display, v_shader, cubemap_shader, None)
+                .map_err(shader("skybox"))?,
+        })
+    }
+}
+
+/// Draws the sky behind everything already in the frame. Call it after the opaque
+/// geometry: the sky lies on the far plane and only fills pixels nothing was drawn to.
+pub fn render_sky<S: Surface>(
+    frame: &mut S,
+    camera: &Camera,
+    sky: &Sky,
+    shaders: &Shaders,
+) -> Result<(), Error> {
+    // Only the rotation of the view matters, the sky is infinitely far away
+    let mut view = Matrix4::from(camera.get_view_matrix());
+    for row in 0..3 {
+        view[(row, 3)] = 0.0;
+    }
+    let view_perspective = Matrix4::from(camera.get_perspective_matrix()) * view;
+    let inverse_view_perspective: [[f32; 4]; 4] = view_perspective
+        .try_inverse()
+        .unwrap_or_else(Matrix4::identity)
+        .into();
+
+    let params = DrawParameters {
+        depth: glium::Depth {
+            test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+            write: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let triangle = EmptyVertexAttributes { len: 3 };
+    let indices = NoIndices(PrimitiveType::TrianglesList);
+
+    match sky {
+        Sky::Gradient {
+            zenith,
+            horizon,
+            ground,
+        } => {
+            let uniforms = uniform! {
+                inverse_view_perspective: inverse_view_perspective,
+                zenith: <[f32; 3]>::from(zenith.to_linear()),
+                horizon: <[f32; 3]>::from(horizon.to_linear()),
+                ground: <[f32; 3]>::from(ground.to_linear()),
+            };
+            frame.draw(triangle, indices, &shaders.sky.gradient, &uniforms, &params)?;
+        }
+        Sky::Cubemap { cubemap, intensity } => {
+            let uniforms = uniform! {
+                inverse_view_perspective: inverse_view_perspective,
+                environment: cubemap.sampled().magnify_filter(MagnifySamplerFilter::Linear),
+                intensity: *intensity,
+            };
+            frame.draw(triangle, indices, &shaders.sky.cubemap, &uniforms, &params)?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds a cubemap from six square images given in the order of `CUBE_FACES`.
+/// The images are taken as sRGB and stored linear.
+pub fn load_cubemap<P: AsRef<Path>>(display: &Display, faces: [P; 6]) -> Result<Cubemap, Error> {
+    let mut textures = Vec::with_capacity(6);
+    for path in faces.iter() {
+        let image = image::open(path.as_ref())
+            .map_err(|err| Error::Asset(Box::new(err)))?
+            .to_rgb8();
+        let (width, height) = image.dimensions();
+        if width != height || textures.first().is_some_and(|&(size, _)| size != width) {
+            return Err(Error::Asset(
+                format!(
+                    "{}: cubemap faces have to be square and of equal size",
+                    path.as_ref().display()
+                )
+                .into(),
+            ));
+        }
+
+        let linear: Vec<f32> = image
+            .into_raw()
+            .into_iter()
+            .map(|value| srgb_to_linear(value as f32 / 255.0))
+            .collect();
+        let texture = Texture2d::with_format(
+            display,
+            RawImage2d::from_raw_rgb(linear, (width, height)),
+            UncompressedFloatFormat::F16F16F16,
+            MipmapsOption::NoMipmap,
+        )
+        .map_err(allocation("cubemap face"))?;
+        textures.push((width, texture));
+    }
+
+    let size = textures[0].0;
+    let cubemap = Cubemap::empty_with_format(
+        display,
+        UncompressedFloatFormat::F16F16F16,
+        MipmapsOption::NoMipmap,
+        size,
+    )
+    .map_err(allocation("cubemap"))?;
+
+    let target = BlitTarget {
+        left: 0,
+        bottom: 0,
+        width: size as i32,
+        height: size as i32,
+    };
+    for ((_, texture), &face) in textures.iter().zip(CUBE_FACES.iter()) {
+        let framebuffer = SimpleFrameBuffer::new(display, cubemap.main_level().image(face))
+            .map_err(allocation("cubemap framebuffer"))?;
+        texture.as_surface().blit_whole_color_to(
+            &framebuffer,
+            &target,
+            MagnifySamplerFilter::Linear,
+        );
+    }
+    Ok(cubemap)
+}
+
+/// Loads an equirectangular Radiance `.hdr` panorama and projects it onto a cubemap
+/// with faces of `size` pixels
+pub fn load_equirectangular<P: AsRef<Path>>(
+    display: &Display,
+    path: P,
+    size: u32,
+) -> Result<Cubemap, Error> {
+    let image = load_hdr(path)?;
+    let data: Vec<f32> = image.pixels.iter().flatten().copied().collect();
+    let panorama = Texture2d::with_format(
+        display,
+        RawImage2d::from_raw_rgb(data, (image.width, image.height)),
+        UncompressedFloatFormat::F32F32F32,
+        MipmapsOption::NoMipmap,
+    )
+    .map_err(allocation("panorama texture"))?;
+
+    equirectangular_to_cubemap(display, &panorama, size)
+}
+
+/// Projects an equirectangular panorama onto a cubemap on the GPU
+pub fn equirectangular_to_cubemap(
+    display: &Display,
+    panorama: &Texture2d,
+    size: u32,
+) -> Result<Cubemap, Error> {
+    let program = cube_face_program(
+        display,
+        include_str!(r"shaders/equirect_fragment.glsl"),
+        "equirectangular projection",
+    )?;
+
+    let cubemap = Cubemap::empty_with_format(
+        display,
+        UncompressedFloatFormat::F16F16F16,
+        MipmapsOption::NoMipmap,
+        size,
+    )
+    .map_err(allocation("cubemap"))?;
+
+    render_cube_faces(display, &cubemap, 0, &program, |face| {
+        uniform! {
+            equirectangular: panorama
+                .sampled()
+                .wrap_function(SamplerWrapFunction::Repeat)
+                .magnify_filter(MagnifySamplerFilter::Linear),
+            face: face,
+        }
+    })?;
+    Ok(cubemap)
+}
+
+/// Compiles a program drawing into cubemap faces, `f_shader` can call `face_direction()`
+pub(crate) fn cube_face_program(
+    display: &Display,
+    f_shader: &str,
+    name: &'static str,
+) -> Result<Program, Error> {
+    let v_shader = include_str!(r"shaders/fullscreen_vertex.glsl");
+    let f_shader = paste_after_version(f_shader, include_str!(r"shaders/cube_face.glsl"));
+    Program::from_source(display, v_shader, &f_shader, None).map_err(shader(name))
+}
+
+/// Draws `program` over each face of a mipmap level, `uniforms` gets the face index
+pub(crate) fn render_cube_faces<U: Uniforms, F: Fn(i32) -> U>(
+    display: &Display,
+    cubemap: &Cubemap,
+    level: u32,
+    program: &Program,
+    uniforms: F,
+) -> Result<(), Error> {
+    let mipmap = cubemap
+        .mipmap(level)
+        .ok_or_else(|| Error::Asset(format!("cubemap has no mipmap level {}", level).into()))?;
+    for (face, &layer) in CUBE_FACES.iter().enumerate() {
+        let mut framebuffer = SimpleFrameBuffer::new(display, mipmap.image(layer))
+            .map_err(allocation("cubemap framebuffer"))?;
+        framebuffer.draw(
+            EmptyVertexAttributes { len: 3 },
+            NoIndices(PrimitiveType::TrianglesList),
+            program,
+            &uniforms(face as i32),
+            &Default::default(),
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,326 @@
+use crate::error::{allocation, shader};
+use crate::instancing::{self, InstanceBuffer};
+use crate::lod;
+use crate::post::{draw_fullscreen, fullscreen_program};
+use crate::{
+    uniform, Camera, Display, DrawParameters, Error, Program, Scalar, SceneObject, Surface,
+};
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{
+    MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerBehavior, SamplerWrapFunction,
+    UniformValue, Uniforms,
+};
+
+/// The kernel is stored in a texture, this keeps the loop in the shader bounded
+pub const MAX_KERNEL_SIZE: usize = 64;
+/// Width and height of the tiled rotation texture
+const NOISE_SIZE: u32 = 4;
+
+#[derive(Copy, Clone, Debug)]
+pub struct SsaoSettings {
+    /// View space radius of the hemisphere searched for occluders
+    pub radius: Scalar,
+    /// Exponent darkening the occlusion, 1.0 leaves it as sampled
+    pub intensity: Scalar,
+    /// Depth difference below which a sample is not occluded, against self-occlusion
+    pub bias: Scalar,
+    /// Samples per pixel, at most `MAX_KERNEL_SIZE`
+    pub kernel_size: usize,
+    /// Smooths out the noise pattern left by the random kernel rotations
+    pub blur: bool,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            intensity: 1.0,
+            bias: 0.025,
+            kernel_size: 32,
+            blur: true,
+        }
+    }
+}
+
+struct Programs {
+    prepass: Program,
+    occlusion: Program,
+    blur: Program,
+}
+
+/// Screen space ambient occlusion. A prepass stores the view space normals and
+/// depth of the scene, which are then searched for geometry around every pixel.
+pub struct Ssao {
+    settings: SsaoSettings,
+    programs: Programs,
+    normal_depth: Texture2d,
+    depth: DepthRenderBuffer,
+    occlusion: Texture2d,
+    blurred: Texture2d,
+    noise: Texture2d,
+    kernel: Texture2d,
+    instances: InstanceBuffer,
+}
+
+impl Ssao {
+    pub fn new(display: &Display, settings: SsaoSettings) -> Result<Self, Error> {
+        let programs = Programs {
+            prepass: Program::from_source(
+                display,
+                include_str!(r"shaders/ssao_prepass_vertex.glsl"),
+                include_str!(r"shaders/ssao_prepass_fragment.glsl"),
+                None,
+            )
+            .map_err(shader("SSAO prepass"))?,
+            occlusion: fullscreen_program(
+                display,
+                include_str!(r"shaders/ssao_fragment.glsl"),
+                "SSAO",
+            )?,
+            blur: fullscreen_program(
+                display,
+                include_str!(r"shaders/ssao_blur_fragment.glsl"),
+                "SSAO blur",
+            )?,
+        };
+
+        let mut random = Xorshift(0x9e37_79b9);
+        let kernel_size = settings.kernel_size.clamp(1, MAX_KERNEL_SIZE);
+        let kernel = hemisphere_kernel(&mut random, kernel_size);
+        let noise: Vec<f32> = (0..NOISE_SIZE * NOISE_SIZE)
+            .flat_map(|_| {
+                let (x, y) = (random.next() * 2.0 - 1.0, random.next() * 2.0 - 1.0);
+                vec![x, y, 0.0]
+            })
+            .collect();
+
+        let (normal_depth, depth, occlusion, blurred) = allocate(display, (1, 1))?;
+        Ok(Self {
+            settings,
+            programs,
+            normal_depth,
+            depth,
+            occlusion,
+            blurred,
+            noise: Texture2d::with_format(
+                display,
+                RawImage2d::from_raw_rgb(noise, (NOISE_SIZE, NOISE_SIZE)),
+                UncompressedFloatFormat::F16F16F16,
+                MipmapsOption::NoMipmap,
+            )
+            .map_err(allocation("SSAO noise"))?,
+            kernel: Texture2d::with_format(
+                display,
+                RawImage2d::from_raw_rgb(kernel, (kernel_size as u32, 1)),
+                UncompressedFloatFormat::F32F32F32,
+                MipmapsOption::NoMipmap,
+            )
+            .map_err(allocation("SSAO kernel"))?,
+            instances: InstanceBuffer::new(display)?,
+        })
+    }
+
+    pub fn settings(&self) -> &SsaoSettings {
+        &self.settings
+    }
+
+    /// Changes the settings, regenerating the kernel if its size changed
+    pub fn set_settings(&mut self, display: &Display, settings: SsaoSettings) -> Result<(), Error> {
+        if settings.kernel_size != self.settings.kernel_size {
+            *self = Self::new(display, settings)?;
+        } else {
+            self.settings = settings;
+        }
+        Ok(())
+    }
+
+    /// The ambient occlusion of the last update, 1.0 is unoccluded
+    pub fn occlusion(&self) -> &Texture2d {
+        if self.settings.blur {
+            &self.blurred
+        } else {
+            &self.occlusion
+        }
+    }
+
+    /// Renders the normal and depth prepass from the camera, then the occlusion
+    /// and its blur. The buffers follow the camera's resolution.
+    pub fn update(
+        &mut self,
+        display: &Display,
+        object_list: &[Box<SceneObject>],
+        camera: &Camera,
+    ) -> Result<(), Error> {
+        if self.normal_depth.dimensions() != camera.resolution() {
+            let (normal_depth, depth, occlusion, blurred) = allocate(display, camera.resolution())?;
+            self.normal_depth = normal_depth;
+            self.depth = depth;
+            self.occlusion = occlusion;
+            self.blurred = blurred;
+        }
+        lod::select_levels(object_list, camera);
+
+        let perspective = camera.get_perspective_matrix();
+        let view = camera.get_view_matrix();
+        let draw_params = DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut target =
+            SimpleFrameBuffer::with_depth_buffer(display, &self.normal_depth, &self.depth)
+                .map_err(allocation("SSAO prepass framebuffer"))?;
+        target.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
+
+        // Only the geometry matters, so batches may mix materials. Transparent
+        // objects are left out, they would hide the surfaces behind them.
+        let opaque = object_list
+            .iter()
+            .filter(|object| !object.material.is_transparent());
+        for batch in instancing::batches(opaque, false) {
+            let mesh = batch.object.drawn_mesh();
+            let instance_slice = self.instances.upload(&batch.instances)?;
+            let instances = instance_slice
+                .per_instance()
+                .map_err(|_| Error::InstancingNotSupported)?;
+            let uniforms = uniform! {
+                view: view,
+                perspective: perspective,
+            };
+
+            target.draw(
+                (&mesh.vertex_bfr, &mesh.normal_bfr, instances),
+                &mesh.ind_bfr,
+                &self.programs.prepass,
+                &uniforms,
+                &draw_params,
+            )?;
+        }
+
+        let mut target = SimpleFrameBuffer::new(display, &self.occlusion)
+            .map_err(allocation("SSAO framebuffer"))?;
+        let uniforms = uniform! {
+            normal_depth: nearest(&self.normal_depth),
+            noise: nearest(&self.noise).wrap_function(SamplerWrapFunction::Repeat),
+            kernel: nearest(&self.kernel),
+            kernel_size: self.kernel.width() as i32,
+            perspective: perspective,
+            radius: self.settings.radius,
+            bias: self.settings.bias,
+            intensity: self.settings.intensity,
+        };
+        draw_fullscreen(&mut target, &self.programs.occlusion, &uniforms)?;
+
+        if self.settings.blur {
+            let mut target = SimpleFrameBuffer::new(display, &self.blurred)
+                .map_err(allocation("SSAO blur framebuffer"))?;
+            let uniforms = uniform! {
+                source: nearest(&self.occlusion),
+            };
+            draw_fullscreen(&mut target, &self.programs.blur, &uniforms)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Unfiltered sampling, for buffers read one texel per pixel
+pub(crate) fn nearest(texture: &Texture2d) -> Sampler<'_, Texture2d> {
+    texture
+        .sampled()
+        .wrap_function(SamplerWrapFunction::Clamp)
+        .minify_filter(MinifySamplerFilter::Nearest)
+        .magnify_filter(MagnifySamplerFilter::Nearest)
+}
+
+fn allocate(
+    display: &Display,
+    (width, height): (u32, u32),
+) -> Result<(Texture2d, DepthRenderBuffer, Texture2d, Texture2d), Error> {
+    let texture = |format, what| {
+        Texture2d::empty_with_format(display, format, MipmapsOption::NoMipmap, width, height)
+            .map_err(allocation(what))
+    };
+    Ok((
+        texture(
+            UncompressedFloatFormat::F32F32F32F32,
+            "SSAO normal and depth buffer",
+        )?,
+        DepthRenderBuffer::new(display, DepthFormat::I24, width, height)
+            .map_err(allocation("SSAO depth buffer"))?,
+        texture(UncompressedFloatFormat::U8, "SSAO buffer")?,
+        texture(UncompressedFloatFormat::U8, "SSAO blur buffer")?,
+    ))
+}
+
+/// Sample offsets in the unit hemisphere around +z, packed as rgb.
+/// Samples cluster towards the center, where occluders matter most.
+fn hemisphere_kernel(random: &mut Xorshift, size: usize) -> Vec<f32> {
+    let mut kernel = Vec::with_capacity(size * 3);
+    for i in 0..size {
+        let mut sample = crate::Vector3::new(
+            random.next() * 2.0 - 1.0,
+            random.next() * 2.0 - 1.0,
+            random.next(),
+        );
+        sample = sample.try_normalize(1e-6).unwrap_or_else(crate::Vector3::z) * random.next();
+
+        let t = i as Scalar / size as Scalar;
+        sample *= 0.1 + 0.9 * t * t;
+        kernel.extend_from_slice(&[sample.x, sample.y, sample.z]);
+    }
+    kernel
+}
+
+/// Small deterministic generator, the kernel only has to look random
+struct Xorshift(u32);
+
+impl Xorshift {
+    /// Uniform in [0, 1)
+    fn next(&mut self) -> Scalar {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as Scalar / (1 << 24) as Scalar
+    }
+}
+
+/// Ambient occlusion uniforms for the main shading pass. Without SSAO the
+/// sampler is still bound to `fallback` so the program sees a valid texture.
+#[derive(Copy, Clone)]
+pub struct SsaoUniforms<'s> {
+    pub ssao: Option<&'s Ssao>,
+    pub fallback: &'s Texture2d,
+}
+
+impl<'s> Uniforms for SsaoUniforms<'s> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        let sampler = SamplerBehavior {
+            minify_filter: MinifySamplerFilter::Nearest,
+            magnify_filter: MagnifySamplerFilter::Nearest,
+            ..Default::default()
+        };
+
+        match self.ssao {
+            Some(ssao) => {
+                output("u_ssao_enabled", UniformValue::Bool(true));
+                output(
+                    "u_ssao_map",
+                    UniformValue::Texture2d(ssao.occlusion(), Some(sampler)),
+                );
+            }
+            None => {
+                output("u_ssao_enabled", UniformValue::Bool(false));
+                output(
+                    "u_ssao_map",
+                    UniformValue::Texture2d(self.fallback, Some(sampler)),
+                );
+            }
+        }
+    }
+}
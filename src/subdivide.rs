@@ -0,0 +1,621 @@
+//! Subdivision surfaces: Loop for triangle meshes and Catmull-Clark for
+//! polygon meshes. Both run on `PolygonMesh`, which shares positions between
+//! faces and keeps texture coordinates and colors per face corner, so seams
+//! survive. Open borders and crease edges stay sharp, following the rules of
+//! Hoppe et al. (1994), and corners where three or more of them meet stay put.
+
+use crate::{MeshData, Normal, Scalar, TexCoord, Vector3, Vertex, VertexColor, NORM_EPS, PI, UP};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Cosine of the largest angle between the normals on either side of an edge
+/// for `PolygonMesh::from_data` to still consider it smooth
+const HARD_EDGE_COS: Scalar = 0.99;
+
+/// Subdivides a triangle mesh `levels` times by Loop's scheme, each level
+/// splitting every triangle into four. Hard edges become creases.
+pub fn loop_subdivide(data: &MeshData, levels: u32) -> MeshData {
+    let mut mesh = PolygonMesh::from_data(data);
+    for _ in 0..levels {
+        mesh = mesh.loop_subdivide();
+    }
+    mesh.to_data()
+}
+
+/// Subdivides a mesh `levels` times by Catmull-Clark, each level splitting
+/// every face into quads. Hard edges become creases.
+pub fn catmull_clark(data: &MeshData, levels: u32) -> MeshData {
+    let mut mesh = PolygonMesh::from_data(data);
+    for _ in 0..levels {
+        mesh = mesh.catmull_clark();
+    }
+    mesh.to_data()
+}
+
+/// A face corner: the shared position and the attributes of the face there
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Corner {
+    pub position: u32,
+    pub tex_coords: (Scalar, Scalar),
+    pub color: (Scalar, Scalar, Scalar, Scalar),
+}
+
+impl Corner {
+    pub fn new(position: u32) -> Self {
+        Self {
+            position,
+            tex_coords: (0.0, 0.0),
+            color: VertexColor::default().color,
+        }
+    }
+
+    /// A corner at `position` with the average attributes of `corners`
+    fn mix(position: u32, corners: &[Corner]) -> Self {
+        let n = corners.len() as Scalar;
+        let (mut u, mut v) = (0.0, 0.0);
+        let (mut r, mut g, mut b, mut a) = (0.0, 0.0, 0.0, 0.0);
+        for corner in corners {
+            u += corner.tex_coords.0 / n;
+            v += corner.tex_coords.1 / n;
+            r += corner.color.0 / n;
+            g += corner.color.1 / n;
+            b += corner.color.2 / n;
+            a += corner.color.3 / n;
+        }
+        Self {
+            position,
+            tex_coords: (u, v),
+            color: (r, g, b, a),
+        }
+    }
+}
+
+/// Polygons sharing positions, with their adjacency built on demand. Faces
+/// wind like the triangles of `MeshData`.
+#[derive(Clone, Debug, Default)]
+pub struct PolygonMesh {
+    pub positions: Vec<Vector3>,
+    pub faces: Vec<Vec<Corner>>,
+    /// Edges kept sharp, as pairs of position indices with the smaller first
+    pub creases: HashSet<[u32; 2]>,
+}
+
+/// The faces around an edge and whether it stays sharp
+struct Edge {
+    /// Face and index of the corner the edge starts at within it
+    faces: Vec<(usize, usize)>,
+    sharp: bool,
+}
+
+impl PolygonMesh {
+    /// Polygons given by the indices of their positions
+    pub fn new(positions: Vec<Vector3>, faces: Vec<Vec<u32>>) -> Self {
+        let faces = faces
+            .into_iter()
+            .map(|face| face.into_iter().map(Corner::new).collect())
+            .collect();
+        Self {
+            positions,
+            faces,
+            creases: HashSet::new(),
+        }
+    }
+
+    /// The triangles of a mesh, joined where vertices share a position. Edges
+    /// along which the normals differ become creases. Positions that only
+    /// nearly match stay apart, `MeshData::weld` closes such cracks first.
+    pub fn from_data(data: &MeshData) -> Self {
+        let mut mesh = Self::default();
+        let mut by_position: HashMap<[u32; 3], u32> = HashMap::new();
+        let position_of: Vec<u32> = (0..data.vertex_count())
+            .map(|vertex| {
+                *by_position
+                    .entry(data.position_key(vertex))
+                    .or_insert_with(|| {
+                        mesh.positions.push(data.position(vertex));
+                        mesh.positions.len() as u32 - 1
+                    })
+            })
+            .collect();
+
+        // The vertex each face uses at each of its positions, to compare normals
+        let mut corner_vertices: HashMap<[u32; 2], Vec<usize>> = HashMap::new();
+        for triangle in data.triangles() {
+            let positions = triangle.map(|vertex| position_of[vertex]);
+            let [a, b, c] = positions;
+            if a == b || b == c || c == a {
+                continue;
+            }
+            let face = triangle
+                .iter()
+                .map(|&vertex| Corner {
+                    position: position_of[vertex],
+                    tex_coords: data
+                        .tex_coords
+                        .get(vertex)
+                        .copied()
+                        .unwrap_or_default()
+                        .tex_coords,
+                    color: data.colors.get(vertex).copied().unwrap_or_default().color,
+                })
+                .collect();
+            mesh.faces.push(face);
+            for corner in 0..3 {
+                let next = (corner + 1) % 3;
+                let key = edge_key(positions[corner], positions[next]);
+                let vertices = corner_vertices.entry(key).or_default();
+                let (first, second) = if key[0] == positions[corner] {
+                    (triangle[corner], triangle[next])
+                } else {
+                    (triangle[next], triangle[corner])
+                };
+                vertices.extend_from_slice(&[first, second]);
+            }
+        }
+
+        let normal = |vertex: usize| {
+            let (x, y, z) = data
+                .normals
+                .get(vertex)
+                .map_or((0.0, 0.0, 0.0), |n| n.normal);
+            Vector3::new(x, y, z)
+        };
+        for (key, vertices) in corner_vertices {
+            // Only edges between exactly two faces can be smooth or creased
+            if let [a1, b1, a2, b2] = vertices[..] {
+                let hard = |v: usize, w: usize| normal(v).dot(&normal(w)) < HARD_EDGE_COS;
+                if !data.normals.is_empty() && (hard(a1, a2) || hard(b1, b2)) {
+                    mesh.creases.insert(key);
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Marks the edge between two positions as a crease
+    pub fn set_crease(&mut self, a: u32, b: u32) {
+        self.creases.insert(edge_key(a, b));
+    }
+
+    /// Splits the faces with more than three corners into triangle fans
+    pub fn triangulate(&self) -> Self {
+        let mut faces = Vec::with_capacity(self.faces.len());
+        for face in &self.faces {
+            for i in 1..face.len().saturating_sub(1) {
+                faces.push(vec![face[0], face[i], face[i + 1]]);
+            }
+        }
+        Self {
+            positions: self.positions.clone(),
+            faces,
+            creases: self.creases.clone(),
+        }
+    }
+
+    /// One level of Loop subdivision, triangulating other faces first
+    pub fn loop_subdivide(&self) -> Self {
+        if self.faces.iter().any(|face| face.len() != 3) {
+            return self.triangulate().loop_subdivide();
+        }
+        let edges = self.edges();
+        let edge_index: HashMap<[u32; 2], u32> = edges
+            .keys()
+            .enumerate()
+            .map(|(i, &key)| (key, (self.positions.len() + i) as u32))
+            .collect();
+
+        let mut positions = self.vertex_points(&edges, |v, neighbours| {
+            // Loop's original weights
+            let n = neighbours.len() as Scalar;
+            let w = 3.0 / 8.0 + (2.0 * PI / n).cos() / 4.0;
+            let beta = (5.0 / 8.0 - w * w) / n;
+            let sum: Vector3 = neighbours
+                .iter()
+                .map(|&(other, _)| self.position(other))
+                .sum();
+            v * (1.0 - n * beta) + sum * beta
+        });
+        positions.resize(self.positions.len() + edges.len(), Vector3::zeros());
+        for (key, edge) in &edges {
+            let (a, b) = (self.position(key[0]), self.position(key[1]));
+            positions[edge_index[key] as usize] = if edge.sharp {
+                (a + b) / 2.0
+            } else {
+                let opposite: Vector3 = edge
+                    .faces
+                    .iter()
+                    .map(|&(face, corner)| {
+                        self.position(self.faces[face][(corner + 2) % 3].position)
+                    })
+                    .sum();
+                (a + b) * (3.0 / 8.0) + opposite / 8.0
+            };
+        }
+
+        let mut faces = Vec::with_capacity(self.faces.len() * 4);
+        for face in &self.faces {
+            let mid = |i: usize| {
+                let (from, to) = (face[i], face[(i + 1) % 3]);
+                Corner::mix(
+                    edge_index[&edge_key(from.position, to.position)],
+                    &[from, to],
+                )
+            };
+            let (ab, bc, ca) = (mid(0), mid(1), mid(2));
+            faces.push(vec![face[0], ab, ca]);
+            faces.push(vec![ab, face[1], bc]);
+            faces.push(vec![ca, bc, face[2]]);
+            faces.push(vec![ab, bc, ca]);
+        }
+
+        Self {
+            positions,
+            faces,
+            creases: self.split_creases(&edge_index),
+        }
+    }
+
+    /// One level of Catmull-Clark subdivision
+    pub fn catmull_clark(&self) -> Self {
+        let edges = self.edges();
+        let edge_index: HashMap<[u32; 2], u32> = edges
+            .keys()
+            .enumerate()
+            .map(|(i, &key)| (key, (self.positions.len() + i) as u32))
+            .collect();
+        let face_base = self.positions.len() + edges.len();
+        let face_points: Vec<Vector3> = self
+            .faces
+            .iter()
+            .map(|face| {
+                let sum: Vector3 = face
+                    .iter()
+                    .map(|corner| self.position(corner.position))
+                    .sum();
+                sum / face.len() as Scalar
+            })
+            .collect();
+
+        let mut positions = self.vertex_points(&edges, |v, neighbours| {
+            let n = neighbours.len() as Scalar;
+            let midpoints: Vector3 = neighbours
+                .iter()
+                .map(|&(other, _)| (v + self.position(other)) / 2.0)
+                .sum();
+            let faces: Vec<usize> = neighbours
+                .iter()
+                .flat_map(|&(_, key)| edges[&key].faces.iter().map(|&(face, _)| face))
+                .collect::<BTreeSet<usize>>()
+                .into_iter()
+                .collect();
+            let average: Vector3 = faces.iter().map(|&face| face_points[face]).sum();
+            let average = average / faces.len().max(1) as Scalar;
+            (average + midpoints * (2.0 / n) + v * (n - 3.0)) / n
+        });
+        positions.resize(face_base, Vector3::zeros());
+        for (key, edge) in &edges {
+            let (a, b) = (self.position(key[0]), self.position(key[1]));
+            positions[edge_index[key] as usize] = if edge.sharp {
+                (a + b) / 2.0
+            } else {
+                let (f, g) = (edge.faces[0].0, edge.faces[1].0);
+                (a + b + face_points[f] + face_points[g]) / 4.0
+            };
+        }
+        positions.extend(face_points);
+
+        let mut faces = Vec::new();
+        for (index, face) in self.faces.iter().enumerate() {
+            let n = face.len();
+            let center = Corner::mix((face_base + index) as u32, face);
+            let mid = |i: usize| {
+                let (from, to) = (face[i], face[(i + 1) % n]);
+                Corner::mix(
+                    edge_index[&edge_key(from.position, to.position)],
+                    &[from, to],
+                )
+            };
+            for (i, &corner) in face.iter().enumerate() {
+                faces.push(vec![corner, mid(i), center, mid((i + n - 1) % n)]);
+            }
+        }
+
+        Self {
+            positions,
+            faces,
+            creases: self.split_creases(&edge_index),
+        }
+    }
+
+    /// Triangles with a vertex per distinct corner. Normals are smooth across
+    /// edges and hard along creases and borders.
+    pub fn to_data(&self) -> MeshData {
+        let face_normals: Vec<Vector3> = self
+            .faces
+            .iter()
+            .map(|face| {
+                // Newell's method, matching `MeshData::face_normal` for triangles
+                (0..face.len())
+                    .map(|i| {
+                        let next = face[(i + 1) % face.len()].position;
+                        self.position(face[i].position).cross(&self.position(next))
+                    })
+                    .sum()
+            })
+            .collect();
+
+        // Corners of faces meeting at a smooth edge share their normal
+        let mut first_corner = Vec::with_capacity(self.faces.len());
+        let mut count = 0;
+        for face in &self.faces {
+            first_corner.push(count);
+            count += face.len();
+        }
+        let mut groups = UnionFind::new(count);
+        for edge in self.edges().values() {
+            if let (false, &[(f, i), (g, j)]) = (edge.sharp, &edge.faces[..]) {
+                let corners = |face: usize, start: usize| {
+                    let next = (start + 1) % self.faces[face].len();
+                    let corner = |i: usize| (self.faces[face][i].position, first_corner[face] + i);
+                    [corner(start), corner(next)]
+                };
+                for (position, corner) in corners(f, i).iter() {
+                    for (other_position, other) in corners(g, j).iter() {
+                        if position == other_position {
+                            groups.union(*corner, *other);
+                        }
+                    }
+                }
+            }
+        }
+        let mut group_normals = vec![Vector3::zeros(); count];
+        for (face, normal) in face_normals.iter().enumerate() {
+            for i in 0..self.faces[face].len() {
+                group_normals[groups.find(first_corner[face] + i)] += normal;
+            }
+        }
+
+        let colors = self
+            .faces
+            .iter()
+            .flatten()
+            .any(|corner| corner.color != VertexColor::default().color);
+        let mut data = MeshData::default();
+        let mut vertices: HashMap<(usize, [u32; 6]), u32> = HashMap::new();
+        for (face, corners) in self.faces.iter().enumerate() {
+            let indices: Vec<u32> = corners
+                .iter()
+                .enumerate()
+                .map(|(i, corner)| {
+                    let group = groups.find(first_corner[face] + i);
+                    let (u, v) = corner.tex_coords;
+                    let (r, g, b, a) = corner.color;
+                    let bits = [u, v, r, g, b, a].map(Scalar::to_bits);
+                    *vertices.entry((group, bits)).or_insert_with(|| {
+                        let p = self.position(corner.position);
+                        let n = group_normals[group].try_normalize(NORM_EPS).unwrap_or(UP);
+                        data.vertices.push(Vertex {
+                            position: (p.x, p.y, p.z),
+                        });
+                        data.normals.push(Normal {
+                            normal: (n.x, n.y, n.z),
+                        });
+                        data.tex_coords.push(TexCoord {
+                            tex_coords: corner.tex_coords,
+                        });
+                        if colors {
+                            data.colors.push(VertexColor {
+                                color: corner.color,
+                            });
+                        }
+                        data.vertices.len() as u32 - 1
+                    })
+                })
+                .collect();
+            for i in 1..indices.len().saturating_sub(1) {
+                data.indices
+                    .extend_from_slice(&[indices[0], indices[i], indices[i + 1]]);
+            }
+        }
+        data.generate_tangents();
+        data
+    }
+
+    fn position(&self, index: u32) -> Vector3 {
+        self.positions[index as usize]
+    }
+
+    /// The edges and the faces around them. Borders, edges of more than two
+    /// faces and creases are sharp. Sorted, so new edge points are numbered
+    /// the same way every time.
+    fn edges(&self) -> BTreeMap<[u32; 2], Edge> {
+        let mut edges: BTreeMap<[u32; 2], Edge> = BTreeMap::new();
+        for (index, face) in self.faces.iter().enumerate() {
+            for corner in 0..face.len() {
+                let next = face[(corner + 1) % face.len()].position;
+                let key = edge_key(face[corner].position, next);
+                edges
+                    .entry(key)
+                    .or_insert_with(|| Edge {
+                        faces: Vec::new(),
+                        sharp: false,
+                    })
+                    .faces
+                    .push((index, corner));
+            }
+        }
+        for (key, edge) in edges.iter_mut() {
+            edge.sharp = edge.faces.len() != 2 || self.creases.contains(key);
+        }
+        edges
+    }
+
+    /// The new positions of the existing vertices. Vertices on two sharp edges
+    /// move along them, ones on more stay, the others go where `smooth` puts
+    /// them given the neighbouring positions and the edges to them.
+    fn vertex_points<F>(&self, edges: &BTreeMap<[u32; 2], Edge>, smooth: F) -> Vec<Vector3>
+    where
+        F: Fn(Vector3, &[(u32, [u32; 2])]) -> Vector3,
+    {
+        let mut neighbours: Vec<Vec<(u32, [u32; 2])>> = vec![Vec::new(); self.positions.len()];
+        let mut sharp: Vec<Vec<u32>> = vec![Vec::new(); self.positions.len()];
+        for (&key, edge) in edges {
+            let [a, b] = key;
+            neighbours[a as usize].push((b, key));
+            neighbours[b as usize].push((a, key));
+            if edge.sharp {
+                sharp[a as usize].push(b);
+                sharp[b as usize].push(a);
+            }
+        }
+
+        (0..self.positions.len())
+            .map(|vertex| {
+                let v = self.positions[vertex];
+                match sharp[vertex][..] {
+                    _ if neighbours[vertex].is_empty() => v,
+                    [a, b] => v * 0.75 + (self.position(a) + self.position(b)) / 8.0,
+                    [] | [_] => smooth(v, &neighbours[vertex]),
+                    _ => v,
+                }
+            })
+            .collect()
+    }
+
+    /// The creases after subdivision: both halves of each creased edge
+    fn split_creases(&self, edge_index: &HashMap<[u32; 2], u32>) -> HashSet<[u32; 2]> {
+        let mut creases = HashSet::new();
+        for key in &self.creases {
+            if let Some(&middle) = edge_index.get(key) {
+                creases.insert(edge_key(key[0], middle));
+                creases.insert(edge_key(middle, key[1]));
+            }
+        }
+        creases
+    }
+}
+
+fn edge_key(a: u32, b: u32) -> [u32; 2] {
+    [a.min(b), a.max(b)]
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(count: usize) -> Self {
+        Self {
+            parent: (0..count).collect(),
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parent[a] = b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh_data::tests::cube;
+
+    /// The cube of `mesh_data::tests::cube` as six quads
+    fn quad_cube() -> PolygonMesh {
+        let data = cube();
+        let positions = (0..8).map(|vertex| data.position(vertex)).collect();
+        let faces = data
+            .indices
+            .chunks(6)
+            .map(|quad| vec![quad[0], quad[1], quad[2], quad[5]])
+            .collect();
+        PolygonMesh::new(positions, faces)
+    }
+
+    /// Euler characteristic, 2 for a closed surface without holes
+    fn euler(mesh: &PolygonMesh) -> i64 {
+        mesh.positions.len() as i64 - mesh.edges().len() as i64 + mesh.faces.len() as i64
+    }
+
+    fn assert_closed_and_shrunk(data: &MeshData) {
+        let stats = data.stats();
+        assert!(stats.is_closed());
+        assert!(data.triangles().all(|triangle| {
+            let center = (data.position(triangle[0])
+                + data.position(triangle[1])
+                + data.position(triangle[2]))
+                / 3.0;
+            data.face_normal(triangle).dot(&center) > 0.0
+        }));
+        // Smooth subdivision pulls the surface inside the cube
+        assert!(stats.volume > 2.0 && stats.volume < 8.0);
+    }
+
+    #[test]
+    fn catmull_clark_splits_faces_into_quads() {
+        let cube = quad_cube();
+        assert_eq!(euler(&cube), 2);
+        let subdivided = cube.catmull_clark();
+        // A point per vertex, edge and face, and four quads per quad
+        assert_eq!(subdivided.positions.len(), 8 + 12 + 6);
+        assert_eq!(subdivided.faces.len(), 24);
+        assert!(subdivided.faces.iter().all(|face| face.len() == 4));
+        assert_eq!(euler(&subdivided), 2);
+
+        let data = subdivided.to_data();
+        assert_eq!((data.vertex_count(), data.triangle_count()), (26, 48));
+        assert_closed_and_shrunk(&data);
+    }
+
+    #[test]
+    fn loop_splits_triangles_into_four() {
+        let cube = quad_cube().triangulate();
+        assert_eq!(cube.faces.len(), 12);
+        let subdivided = cube.loop_subdivide();
+        // A point per vertex and edge
+        assert_eq!(subdivided.positions.len(), 8 + 18);
+        assert_eq!(subdivided.faces.len(), 48);
+        assert_eq!(euler(&subdivided), 2);
+
+        let data = subdivided.to_data();
+        assert_eq!((data.vertex_count(), data.triangle_count()), (26, 48));
+        assert_closed_and_shrunk(&data);
+    }
+
+    #[test]
+    fn mesh_data_subdivides_with_hard_edges_as_creases() {
+        let mut data = cube();
+        data.normals.clear();
+        let smooth = loop_subdivide(&data, 2);
+        assert_eq!(smooth.triangle_count(), 12 * 16);
+        assert_closed_and_shrunk(&smooth);
+
+        // Every edge of a cube with flat faces is a crease, so the shape stays
+        data.smooth_normals(PI / 3.0);
+        for subdivided in [loop_subdivide(&data, 2), catmull_clark(&data, 2)] {
+            let stats = subdivided.stats();
+            assert!(stats.is_closed());
+            assert!((stats.volume - 8.0).abs() < 1e-4);
+        }
+    }
+    #[test]
+    fn subdivision_is_reproducible() {
+        let mut data = cube();
+        data.smooth_normals(PI / 3.0);
+        // Every run hashes differently, the output must not depend on it
+        for subdivide in [loop_subdivide, catmull_clark] {
+            let first = format!("{:?}", subdivide(&data, 2));
+            for _ in 0..4 {
+                assert_eq!(format!("{:?}", subdivide(&data, 2)), first);
+            }
+        }
+    }
+}
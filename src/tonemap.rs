@@ -0,0 +1,131 @@
+use crate::error::allocation;
+use crate::post::{draw_fullscreen, fullscreen_program};
+use crate::{uniform, Color, Display, Error, Program, Scalar, Shaders, Surface};
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+
+/// Maps the unbounded scene radiance to the [0, 1] range of the display
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tonemapper {
+    /// Cuts off everything brighter than 1
+    Clamp,
+    Reinhard,
+    /// Filmic curve of the Academy Color Encoding System
+    Aces,
+}
+
+impl Tonemapper {
+    pub(crate) fn index(self) -> i32 {
+        match self {
+            Tonemapper::Clamp => 0,
+            Tonemapper::Reinhard => 1,
+            Tonemapper::Aces => 2,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ToneMapping {
+    pub operator: Tonemapper,
+    /// Scales the radiance before it is mapped
+    pub exposure: Scalar,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            operator: Tonemapper::Aces,
+            exposure: 1.0,
+        }
+    }
+}
+
+/// Floating point color and depth buffers the scene is rendered into before
+/// it is tonemapped to the window
+pub struct HdrTarget {
+    color: Texture2d,
+    depth: DepthRenderBuffer,
+}
+
+impl HdrTarget {
+    pub fn new(display: &Display, (width, height): (u32, u32)) -> Result<Self, Error> {
+        Ok(Self {
+            color: Texture2d::empty_with_format(
+                display,
+                UncompressedFloatFormat::F16F16F16F16,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )
+            .map_err(allocation("HDR color buffer"))?,
+            depth: DepthRenderBuffer::new(display, DepthFormat::I24, width, height)
+                .map_err(allocation("HDR depth buffer"))?,
+        })
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.color.dimensions()
+    }
+
+    /// Reallocates the buffers when the window size changed
+    pub fn resize(&mut self, display: &Display, dimensions: (u32, u32)) -> Result<(), Error> {
+        if self.dimensions() != dimensions {
+            *self = Self::new(display, dimensions)?;
+        }
+        Ok(())
+    }
+
+    /// The linear scene radiance
+    pub fn color(&self) -> &Texture2d {
+        &self.color
+    }
+
+    /// A framebuffer to render the scene into, cleared to `background`
+    pub fn framebuffer(
+        &self,
+        display: &Display,
+        background: Color,
+    ) -> Result<SimpleFrameBuffer<'_>, Error> {
+        let mut framebuffer =
+            SimpleFrameBuffer::with_depth_buffer(display, &self.color, &self.depth)
+                .map_err(allocation("HDR framebuffer"))?;
+        let color = background.to_linear();
+        framebuffer.clear_color_and_depth((color.r, color.g, color.b, 1.0), 1.0);
+        Ok(framebuffer)
+    }
+}
+
+/// Compiles the tonemapping program. It encodes sRGB itself, so the output
+/// is right whether the window's framebuffer is sRGB capable or not.
+pub(crate) fn define_tonemap_shaders(display: &Display) -> Result<Program, Error> {
+    fullscreen_program(
+        display,
+        include_str!(r"shaders/tonemap_fragment.glsl"),
+        "tonemapping",
+    )
+}
+
+/// Draws the HDR target over the whole `target`, usually the window's frame.
+/// See `PostProcessor` for tone mapping together with other effects.
+pub fn tonemap<S: Surface>(
+    target: &mut S,
+    hdr: &HdrTarget,
+    settings: &ToneMapping,
+    shaders: &Shaders,
+) -> Result<(), Error> {
+    let color = hdr
+        .color
+        .sampled()
+        .minify_filter(MinifySamplerFilter::Nearest)
+        .magnify_filter(MagnifySamplerFilter::Nearest);
+    let uniforms = uniform! {
+        hdr_color: color,
+        // Without bloom the sampler is bound to a texture of the right type anyway
+        bloom: color,
+        bloom_intensity: 0.0f32,
+        operator: settings.operator.index(),
+        exposure: settings.exposure,
+    };
+    draw_fullscreen(target, &shaders.tonemap, &uniforms)
+}
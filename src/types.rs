@@ -1,20 +1,88 @@
-use crate::implement_vertex;
+use crate::{implement_vertex, linear_to_srgb, srgb_to_linear};
 
 pub type Scalar = f32;
 pub type Matrix4 = nalgebra::Matrix4<Scalar>;
 pub type Vector3 = nalgebra::Vector3<Scalar>;
+pub type Point3 = nalgebra::Point3<Scalar>;
 pub type Unit<T> = nalgebra::Unit<T>;
+pub type UnitQuaternion = nalgebra::UnitQuaternion<Scalar>;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Color {
     pub r: Scalar,
     pub g: Scalar,
     pub b: Scalar,
+    /// Opacity, 1.0 is opaque. Only used by materials that blend, see `BlendMode`.
+    pub a: Scalar,
 }
 
+/// Colors are sRGB encoded, like the values in a color picker. Shaders work in
+/// linear light, so colors are converted with `to_linear` when they are uploaded.
+/// Alpha is linear and never converted.
 impl Color {
     pub const fn new(r: Scalar, g: Scalar, b: Scalar) -> Self {
-        Self { r, g, b }
+        Self { r, g, b, a: 1.0 }
+    }
+
+    pub const fn rgba(r: Scalar, g: Scalar, b: Scalar, a: Scalar) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn with_alpha(mut self, a: Scalar) -> Self {
+        self.a = a;
+        self
+    }
+
+    pub fn to_linear(self) -> Color {
+        Color::rgba(
+            srgb_to_linear(self.r),
+            srgb_to_linear(self.g),
+            srgb_to_linear(self.b),
+            self.a,
+        )
+    }
+
+    /// The sRGB color of a linear one, e.g. of a glTF color factor
+    pub fn from_linear(linear: Color) -> Color {
+        Color::rgba(
+            linear_to_srgb(linear.r),
+            linear_to_srgb(linear.g),
+            linear_to_srgb(linear.b),
+            linear.a,
+        )
+    }
+}
+
+/// Scales the color, the alpha stays as it is
+impl std::ops::Mul<Scalar> for Color {
+    type Output = Color;
+
+    fn mul(self, factor: Scalar) -> Color {
+        Color::rgba(self.r * factor, self.g * factor, self.b * factor, self.a)
+    }
+}
+
+impl From<Color> for [f32; 3] {
+    fn from(color: Color) -> Self {
+        [color.r, color.g, color.b]
+    }
+}
+
+impl From<[f32; 3]> for Color {
+    fn from([r, g, b]: [f32; 3]) -> Self {
+        Color::new(r, g, b)
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        [color.r, color.g, color.b, color.a]
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from([r, g, b, a]: [f32; 4]) -> Self {
+        Color::rgba(r, g, b, a)
     }
 }
 
@@ -31,3 +99,35 @@ pub struct Normal {
 }
 
 implement_vertex!(Normal, normal);
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TexCoord {
+    pub tex_coords: (Scalar, Scalar),
+}
+
+implement_vertex!(TexCoord, tex_coords);
+
+/// Tangent with the bitangent handedness (+1 or -1) stored in w
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Tangent {
+    pub tangent: (Scalar, Scalar, Scalar, Scalar),
+}
+
+implement_vertex!(Tangent, tangent);
+
+/// Linear color and opacity of a vertex, multiplied with the material's color
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VertexColor {
+    pub color: (Scalar, Scalar, Scalar, Scalar),
+}
+
+impl Default for VertexColor {
+    /// White, which leaves the material's color as it is
+    fn default() -> Self {
+        Self {
+            color: (1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+implement_vertex!(VertexColor, color);
@@ -0,0 +1,12 @@
+use glium::uniforms::{UniformValue, Uniforms};
+
+/// Joins two sets of uniforms so they can be passed to a single draw call
+#[derive(Copy, Clone)]
+pub struct UniformChain<A, B>(pub A, pub B);
+
+impl<A: Uniforms, B: Uniforms> Uniforms for UniformChain<A, B> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut output: F) {
+        self.0.visit_values(&mut output);
+        self.1.visit_values(output);
+    }
+}